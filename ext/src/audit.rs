@@ -0,0 +1,190 @@
+use std::fmt::{self, Debug};
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+
+/// A session store operation recorded by [`AuditedStore`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditOperation {
+    /// A new session was created.
+    Create,
+
+    /// An existing session was saved.
+    Save,
+
+    /// A session was deleted.
+    Delete,
+}
+
+impl AuditOperation {
+    /// The lowercase name of the operation, e.g. `"create"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Save => "save",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl fmt::Display for AuditOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The outcome of an audited operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditOutcome {
+    /// The operation succeeded.
+    Success,
+
+    /// The operation failed with the given error message.
+    Failure(String),
+}
+
+impl AuditOutcome {
+    /// Whether the operation succeeded.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Success)
+    }
+
+    /// The error message of a failed operation.
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Self::Success => None,
+            Self::Failure(message) => Some(message),
+        }
+    }
+}
+
+/// A single entry in the audit trail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEvent {
+    /// When the operation completed.
+    pub timestamp: OffsetDateTime,
+
+    /// The operation performed.
+    pub operation: AuditOperation,
+
+    /// The hex-encoded SHA-256 hash of the session ID.
+    ///
+    /// Session IDs are bearer credentials, so the audit trail never contains
+    /// them in the clear.
+    pub session_id_hash: String,
+
+    /// The outcome of the operation.
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEvent {
+    fn new<T>(
+        operation: AuditOperation,
+        session_id: &Id,
+        result: &session_store::Result<T>,
+    ) -> Self {
+        Self {
+            timestamp: OffsetDateTime::now_utc(),
+            operation,
+            session_id_hash: hash_session_id(session_id),
+            outcome: match result {
+                Ok(_) => AuditOutcome::Success,
+                Err(err) => AuditOutcome::Failure(err.to_string()),
+            },
+        }
+    }
+}
+
+/// Returns the hex-encoded SHA-256 hash of a session ID, as recorded in
+/// [`AuditEvent::session_id_hash`].
+pub fn hash_session_id(session_id: &Id) -> String {
+    hex::encode(Sha256::digest(session_id.to_string()))
+}
+
+/// A destination for audit events, such as an append-only database table.
+#[async_trait]
+pub trait AuditSink: Debug + Send + Sync + 'static {
+    /// Append an event to the audit trail.
+    async fn record(&self, event: AuditEvent) -> session_store::Result<()>;
+}
+
+/// A session store wrapper recording every create, save, and delete to an
+/// [`AuditSink`].
+///
+/// Events are recorded after the wrapped operation completes, whether or not
+/// it succeeded. If the event cannot be recorded the operation reports that
+/// error, so that no change goes unaudited.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use tower_sessions_ext::AuditedStore;
+///
+/// let session_store = AuditedStore::new(session_store, audit_sink);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuditedStore<S: SessionStore, A: AuditSink> {
+    store: S,
+    sink: A,
+}
+
+impl<S: SessionStore, A: AuditSink> AuditedStore<S, A> {
+    /// Create a new audited store wrapping the provided store.
+    pub fn new(store: S, sink: A) -> Self {
+        Self { store, sink }
+    }
+
+    async fn audit<T>(
+        &self,
+        operation: AuditOperation,
+        session_id: &Id,
+        result: session_store::Result<T>,
+    ) -> session_store::Result<T> {
+        self.sink
+            .record(AuditEvent::new(operation, session_id, &result))
+            .await?;
+        result
+    }
+}
+
+#[async_trait]
+impl<S, A> SessionStore for AuditedStore<S, A>
+where
+    S: SessionStore,
+    A: AuditSink,
+{
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let result = self.store.create(record).await;
+        self.audit(AuditOperation::Create, &record.id, result).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let result = self.store.save(record).await;
+        self.audit(AuditOperation::Save, &record.id, result).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        self.store.load(session_id).await
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let result = self.store.delete(session_id).await;
+        self.audit(AuditOperation::Delete, session_id, result).await
+    }
+}
+
+#[async_trait]
+impl<S, A> ExpiredDeletion for AuditedStore<S, A>
+where
+    S: ExpiredDeletion,
+    A: AuditSink,
+{
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.store.delete_expired().await
+    }
+}
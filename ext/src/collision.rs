@@ -0,0 +1,66 @@
+use std::{fmt, sync::Arc};
+
+use tower_sessions_core::{session::Id, session_store};
+
+type IdGenerator = Arc<dyn Fn() -> Id + Send + Sync>;
+
+/// How a store replaces a session ID that collides with an existing session
+/// during `create`.
+///
+/// By default colliding IDs are replaced with new random IDs until one is
+/// free. A maximum number of attempts turns pathological conditions, such as
+/// a broken ID generator, into an error instead of an endless loop.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_core::session::Id;
+/// use tower_sessions_ext::IdCollisionPolicy;
+///
+/// let policy = IdCollisionPolicy::default()
+///     .with_max_attempts(8)
+///     .with_id_generator(Id::default);
+/// ```
+#[derive(Clone, Default)]
+pub struct IdCollisionPolicy {
+    max_attempts: Option<u32>,
+    generator: Option<IdGenerator>,
+}
+
+impl IdCollisionPolicy {
+    /// Allow at most this many replacement IDs before `create` fails.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Use the provided function to generate replacement IDs.
+    pub fn with_id_generator(mut self, generator: impl Fn() -> Id + Send + Sync + 'static) -> Self {
+        self.generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Returns a replacement ID after the given number of collisions, or an
+    /// error if the policy's attempts are exhausted.
+    pub fn next_id(&self, collisions: u32) -> session_store::Result<Id> {
+        if let Some(max_attempts) = self.max_attempts {
+            if collisions > max_attempts {
+                return Err(session_store::Error::Backend(format!(
+                    "Session ID collided {collisions} times; giving up after {max_attempts} \
+                     attempts"
+                )));
+            }
+        }
+
+        Ok(self.generator.as_ref().map_or_else(Id::default, |f| f()))
+    }
+}
+
+impl fmt::Debug for IdCollisionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdCollisionPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("generator", &self.generator.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
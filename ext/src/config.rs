@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{DecodeFailurePolicy, ExpiryEnforcement, IdCollisionPolicy, StoreOptions, TtlPolicy};
+
+/// The options of [`StoreOptions`], as read from a configuration file.
+///
+/// Each store's configuration flattens these next to its own settings, so
+/// they're written at the top level of the store's section. Every field is
+/// optional, and options that are left out keep the store's defaults.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_ext::StoreOptionsConfig;
+///
+/// let config: StoreOptionsConfig = serde_json::from_str(
+///     r#"{ "namespace": "sessions", "slow_op_threshold_ms": 50, "expiry_enforcement": "strict" }"#,
+/// )
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct StoreOptionsConfig {
+    /// Passed to [`StoreOptions::with_namespace`].
+    pub namespace: Option<String>,
+
+    /// The replacement IDs allowed by the [`IdCollisionPolicy`] before
+    /// `create` fails.
+    pub max_id_collisions: Option<u32>,
+
+    /// Passed to [`StoreOptions::with_decode_failure_policy`].
+    pub decode_failure_policy: Option<DecodeFailurePolicy>,
+
+    /// Passed to [`StoreOptions::with_slow_op_threshold`], in milliseconds.
+    pub slow_op_threshold_ms: Option<u64>,
+
+    /// Passed to [`StoreOptions::with_expiry_enforcement`].
+    pub expiry_enforcement: Option<ExpiryEnforcement>,
+
+    /// The minimum TTL of the [`TtlPolicy`], in seconds.
+    pub min_ttl_secs: Option<i64>,
+
+    /// The maximum TTL of the [`TtlPolicy`], in seconds.
+    pub max_ttl_secs: Option<i64>,
+}
+
+impl StoreOptionsConfig {
+    /// Apply the options that are set to the provided store.
+    pub fn apply<S: StoreOptions>(&self, mut store: S) -> Result<S, String> {
+        if let Some(namespace) = &self.namespace {
+            store = store.with_namespace(namespace)?;
+        }
+        if let Some(max_attempts) = self.max_id_collisions {
+            store = store.with_id_collision_policy(
+                IdCollisionPolicy::default().with_max_attempts(max_attempts),
+            );
+        }
+        if let Some(decode_failure_policy) = self.decode_failure_policy {
+            store = store.with_decode_failure_policy(decode_failure_policy);
+        }
+        if let Some(threshold) = self.slow_op_threshold_ms {
+            store = store.with_slow_op_threshold(Duration::from_millis(threshold));
+        }
+        if let Some(expiry_enforcement) = self.expiry_enforcement {
+            store = store.with_expiry_enforcement(expiry_enforcement);
+        }
+        if self.min_ttl_secs.is_some() || self.max_ttl_secs.is_some() {
+            let mut ttl_policy = TtlPolicy::default();
+            if let Some(min_ttl) = self.min_ttl_secs {
+                ttl_policy = ttl_policy.with_min_ttl(time::Duration::seconds(min_ttl));
+            }
+            if let Some(max_ttl) = self.max_ttl_secs {
+                ttl_policy = ttl_policy.with_max_ttl(time::Duration::seconds(max_ttl));
+            }
+            store = store.with_ttl_policy(ttl_policy);
+        }
+        Ok(store)
+    }
+}
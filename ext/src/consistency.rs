@@ -0,0 +1,202 @@
+use time::{Duration, OffsetDateTime};
+use tower_sessions_core::{session::Id, session_store, SessionStore};
+
+use crate::ExportSessions;
+
+/// Options for [`check_consistency`].
+#[derive(Clone, Debug)]
+pub struct ConsistencyOptions {
+    batch_size: u32,
+    sample_every: u64,
+    expiry_tolerance: Duration,
+    max_drifts: usize,
+}
+
+impl Default for ConsistencyOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 1_000,
+            sample_every: 1,
+            expiry_tolerance: Duration::seconds(1),
+            max_drifts: 100,
+        }
+    }
+}
+
+impl ConsistencyOptions {
+    /// Set the number of records read from the primary store at a time.
+    ///
+    /// Defaults to 1,000.
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Compare only one in every `sample_every` records of the primary store,
+    /// limiting the load placed on the secondary store.
+    ///
+    /// Defaults to comparing every record.
+    pub fn with_sample_every(mut self, sample_every: u64) -> Self {
+        self.sample_every = sample_every.max(1);
+        self
+    }
+
+    /// Set how far apart the expiry dates of a session may be in each store
+    /// before they count as drift, allowing for stores that keep expiry
+    /// dates at a lower precision.
+    ///
+    /// Defaults to one second.
+    pub fn with_expiry_tolerance(mut self, expiry_tolerance: Duration) -> Self {
+        self.expiry_tolerance = expiry_tolerance.abs();
+        self
+    }
+
+    /// Set the most drifted sessions listed in the report; all are counted.
+    ///
+    /// Defaults to 100.
+    pub fn with_max_drifts(mut self, max_drifts: usize) -> Self {
+        self.max_drifts = max_drifts;
+        self
+    }
+}
+
+/// How a session in the secondary store differs from the primary store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionDrift {
+    /// The secondary store has no live session with the ID.
+    Missing,
+
+    /// The session data differs.
+    Data,
+
+    /// The session data matches but the expiry dates differ by more than the
+    /// tolerance.
+    Expiry {
+        /// The expiry date in the primary store.
+        primary: OffsetDateTime,
+
+        /// The expiry date in the secondary store.
+        secondary: OffsetDateTime,
+    },
+}
+
+/// Totals for a run of [`check_consistency`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// Records read from the primary store.
+    pub read: u64,
+
+    /// Records compared against the secondary store.
+    pub compared: u64,
+
+    /// Compared records that match.
+    pub matched: u64,
+
+    /// Compared records missing from the secondary store.
+    pub missing: u64,
+
+    /// Compared records whose data differs.
+    pub data_mismatches: u64,
+
+    /// Compared records whose expiry dates differ.
+    pub expiry_mismatches: u64,
+
+    /// Records skipped because they have expired.
+    pub skipped_expired: u64,
+
+    /// The IDs of drifted sessions and how they drifted, up to the limit set
+    /// with [`ConsistencyOptions::with_max_drifts`].
+    pub drifts: Vec<(Id, SessionDrift)>,
+}
+
+impl ConsistencyReport {
+    /// Returns `true` if every compared record matches.
+    pub fn is_consistent(&self) -> bool {
+        self.matched == self.compared
+    }
+}
+
+/// Compare a sample of the sessions in one store with another, reporting
+/// sessions that are missing or differ in the second store.
+///
+/// This is meant to validate that two stores agree before switching from one
+/// to the other, e.g. while writing to both during a migration. Only the
+/// primary store is read in full, so sessions only found in the secondary
+/// store aren't reported. Sessions changing while the check runs may be
+/// reported as drift, so repeat the check to tell lasting drift apart.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use tower_sessions_ext::{check_consistency, ConsistencyOptions};
+///
+/// let options = ConsistencyOptions::default().with_sample_every(100);
+/// let report = check_consistency(&redis_store, &postgres_store, &options).await?;
+/// if !report.is_consistent() {
+///     println!("{} of {} sessions drifted", report.compared - report.matched, report.compared);
+/// }
+/// ```
+pub async fn check_consistency<P, S>(
+    primary: &P,
+    secondary: &S,
+    options: &ConsistencyOptions,
+) -> session_store::Result<ConsistencyReport>
+where
+    P: ExportSessions,
+    S: SessionStore,
+{
+    let mut report = ConsistencyReport::default();
+    let mut after = None;
+
+    loop {
+        let records = primary.export(after, options.batch_size).await?;
+        let Some(last) = records.last() else {
+            break;
+        };
+        after = Some(last.id);
+
+        for record in records {
+            report.read += 1;
+            if !(report.read - 1).is_multiple_of(options.sample_every) {
+                continue;
+            }
+
+            if record.expiry_date <= OffsetDateTime::now_utc() {
+                report.skipped_expired += 1;
+                continue;
+            }
+
+            report.compared += 1;
+            let drift = match secondary.load(&record.id).await? {
+                None => {
+                    report.missing += 1;
+                    SessionDrift::Missing
+                }
+                Some(loaded) if loaded.data != record.data => {
+                    report.data_mismatches += 1;
+                    SessionDrift::Data
+                }
+                Some(loaded)
+                    if (loaded.expiry_date - record.expiry_date).abs()
+                        > options.expiry_tolerance =>
+                {
+                    report.expiry_mismatches += 1;
+                    SessionDrift::Expiry {
+                        primary: record.expiry_date,
+                        secondary: loaded.expiry_date,
+                    }
+                }
+                Some(_) => {
+                    report.matched += 1;
+                    continue;
+                }
+            };
+
+            if report.drifts.len() < options.max_drifts {
+                report.drifts.push((record.id, drift));
+            }
+        }
+    }
+
+    Ok(report)
+}
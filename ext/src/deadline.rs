@@ -0,0 +1,45 @@
+use std::future::Future;
+
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static REQUEST_DEADLINE: Instant;
+}
+
+/// Run the provided future with a deadline for the session store operations
+/// it performs.
+///
+/// Stores wrapped in [`TimeoutStore`](crate::TimeoutStore) abandon operations
+/// that can no longer complete before the deadline, and fail operations
+/// started after it without calling the backend. This is meant to be applied
+/// by middleware around the session layer, with the deadline derived from the
+/// request's overall budget, so that session I/O is not carried out for
+/// requests whose client has already given up.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tokio::time::Instant;
+/// use tower_sessions_ext::{request_deadline, with_request_deadline};
+///
+/// # tokio_test::block_on(async {
+/// let deadline = Instant::now() + Duration::from_millis(500);
+/// with_request_deadline(deadline, async {
+///     assert_eq!(request_deadline(), Some(deadline));
+/// })
+/// .await;
+/// # })
+/// ```
+pub async fn with_request_deadline<F: Future>(deadline: Instant, future: F) -> F::Output {
+    // Nested scopes may only tighten the deadline.
+    let deadline = request_deadline().map_or(deadline, |outer| outer.min(deadline));
+    REQUEST_DEADLINE.scope(deadline, future).await
+}
+
+/// Returns the deadline set by [`with_request_deadline`] for the current task,
+/// if any.
+pub fn request_deadline() -> Option<Instant> {
+    REQUEST_DEADLINE.try_with(|deadline| *deadline).ok()
+}
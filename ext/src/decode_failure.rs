@@ -0,0 +1,55 @@
+use serde::Deserialize;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, SessionStore,
+};
+
+/// How a store responds to loading a session whose stored data can't be
+/// decoded.
+///
+/// By default a decode failure is returned as an error, so every request
+/// carrying the session's cookie fails until the session expires. After a
+/// change to the serialization format or the layout of session data, the
+/// other policies let applications recover by starting over with a new
+/// session instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeFailurePolicy {
+    /// Return the decode error.
+    #[default]
+    Error,
+
+    /// Load nothing, leaving the undecodable session in place until it is
+    /// overwritten or expires.
+    TreatAsMissing,
+
+    /// Delete the undecodable session and load nothing.
+    DeleteAndTreatAsMissing,
+}
+
+impl DecodeFailurePolicy {
+    /// Apply the policy to the result of loading the session with the
+    /// provided ID from the provided store.
+    ///
+    /// Errors other than [`session_store::Error::Decode`] are returned as is.
+    pub async fn apply<S: SessionStore>(
+        self,
+        store: &S,
+        session_id: &Id,
+        loaded: session_store::Result<Option<Record>>,
+    ) -> session_store::Result<Option<Record>> {
+        let err = match loaded {
+            Err(session_store::Error::Decode(err)) if self != DecodeFailurePolicy::Error => err,
+            loaded => return loaded,
+        };
+
+        warn!(error = %err, policy = ?self, "failed to decode session");
+        if self == DecodeFailurePolicy::DeleteAndTreatAsMissing {
+            if let Err(err) = store.delete(session_id).await {
+                warn!(error = %err, "failed to delete undecodable session");
+            }
+        }
+
+        Ok(None)
+    }
+}
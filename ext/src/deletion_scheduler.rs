@@ -0,0 +1,179 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::task::JoinSet;
+use tower_sessions_core::{session_store, ExpiredDeletion};
+
+type DeleteExpired =
+    Arc<dyn Fn() -> Pin<Box<dyn Future<Output = session_store::Result<()>> + Send>> + Send + Sync>;
+
+struct ScheduledStore {
+    name: String,
+    interval: Duration,
+    delete_expired: DeleteExpired,
+}
+
+/// Runs `delete_expired` for several stores, each on its own interval.
+///
+/// This replaces spawning `continuously_delete_expired` for every store, and
+/// unlike it keeps running when a deletion fails:
+///
+/// - Each store's first deletion is delayed by a random share of the startup
+///   jitter, so that instances started together don't all sweep at once.
+/// - A failed deletion is logged with `tracing::warn!` and retried after a
+///   delay that doubles with each consecutive failure, up to the maximum
+///   backoff. Once a deletion succeeds, the store returns to its interval.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use std::time::Duration;
+///
+/// use tower_sessions_ext::DeletionScheduler;
+///
+/// let scheduler = DeletionScheduler::new()
+///     .with_store("postgres", postgres_store, Duration::from_secs(60))
+///     .with_store("mongodb", mongodb_store, Duration::from_secs(300))
+///     .with_startup_jitter(Duration::from_secs(30));
+///
+/// tokio::task::spawn(scheduler.run());
+/// ```
+pub struct DeletionScheduler {
+    stores: Vec<ScheduledStore>,
+    startup_jitter: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for DeletionScheduler {
+    fn default() -> Self {
+        Self {
+            stores: Vec::new(),
+            startup_jitter: Duration::ZERO,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(600),
+        }
+    }
+}
+
+impl DeletionScheduler {
+    /// Create a scheduler without any stores.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delete expired sessions from the provided store every `interval`.
+    ///
+    /// The name identifies the store in logs.
+    pub fn with_store<S>(mut self, name: impl Into<String>, store: S, interval: Duration) -> Self
+    where
+        S: ExpiredDeletion + Clone + 'static,
+    {
+        self.stores.push(ScheduledStore {
+            name: name.into(),
+            interval,
+            delete_expired: Arc::new(move || {
+                let store = store.clone();
+                Box::pin(async move { store.delete_expired().await })
+            }),
+        });
+        self
+    }
+
+    /// Delay each store's first deletion by a random duration of up to the
+    /// provided jitter.
+    ///
+    /// Defaults to no delay.
+    pub fn with_startup_jitter(mut self, startup_jitter: Duration) -> Self {
+        self.startup_jitter = startup_jitter;
+        self
+    }
+
+    /// Set the delay before retrying a failed deletion, which doubles with
+    /// each consecutive failure up to `max`.
+    ///
+    /// Defaults to one second, up to ten minutes.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Run the scheduled deletions until the returned future is dropped.
+    ///
+    /// Each store is swept from its own task, so a slow store doesn't delay
+    /// the others.
+    pub async fn run(self) {
+        let mut tasks = JoinSet::new();
+        for store in self.stores {
+            let startup_delay = self.startup_jitter.mul_f64(random_fraction());
+            let backoff = (self.initial_backoff, self.max_backoff);
+            tasks.spawn(run_store(store, startup_delay, backoff));
+        }
+
+        while tasks.join_next().await.is_some() {}
+    }
+}
+
+async fn run_store(
+    store: ScheduledStore,
+    startup_delay: Duration,
+    (initial_backoff, max_backoff): (Duration, Duration),
+) {
+    tokio::time::sleep(startup_delay).await;
+
+    let mut failures = 0;
+    loop {
+        let delay = match (store.delete_expired)().await {
+            Ok(()) => {
+                failures = 0;
+                store.interval
+            }
+            Err(err) => {
+                let backoff = initial_backoff
+                    .saturating_mul(2u32.saturating_pow(failures))
+                    .min(max_backoff);
+                failures += 1;
+                warn!(
+                    store = %store.name,
+                    error = %err,
+                    retry_in = ?backoff,
+                    "failed to delete expired sessions"
+                );
+                backoff
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Returns a random number between 0 and 1.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl fmt::Debug for DeletionScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeletionScheduler")
+            .field(
+                "stores",
+                &self
+                    .stores
+                    .iter()
+                    .map(|store| (&store.name, store.interval))
+                    .collect::<Vec<_>>(),
+            )
+            .field("startup_jitter", &self.startup_jitter)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .finish()
+    }
+}
@@ -0,0 +1,590 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+
+use crate::{ExportSessions, TouchSessionStore};
+
+const ENVELOPE_KEY: &str = "__tower_sessions_encrypted";
+const NONCE_LEN: usize = 12;
+const MAX_CACHED_DATA_KEYS: usize = 1024;
+
+/// A data key wrapped by a [`KeyProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    /// The ID of the key-encryption key that wrapped the data key.
+    pub key_id: String,
+
+    /// The wrapped data key.
+    pub ciphertext: Vec<u8>,
+}
+
+/// A source of key-encryption keys for [`EncryptedStore`].
+///
+/// Sessions are encrypted with data keys generated by the store, which are
+/// in turn wrapped by the provider, e.g. by a call to a managed KMS, and
+/// stored next to each session. Key-encryption keys can therefore be rotated
+/// by wrapping new data keys with the new key while still unwrapping those
+/// wrapped with earlier keys, without re-encrypting stored sessions.
+#[async_trait]
+pub trait KeyProvider: fmt::Debug + Send + Sync + 'static {
+    /// Wrap the provided data key with the current key-encryption key.
+    async fn wrap_key(&self, data_key: &[u8]) -> session_store::Result<WrappedKey>;
+
+    /// Unwrap a data key previously wrapped by this provider.
+    async fn unwrap_key(&self, wrapped_key: &WrappedKey) -> session_store::Result<Vec<u8>>;
+}
+
+/// A [`KeyProvider`] wrapping data keys with AES-256-GCM keys held in memory.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_ext::StaticKeyProvider;
+///
+/// let key_provider = StaticKeyProvider::new("2024-06", [7; 32]).with_previous_key("2023-12", [3; 32]);
+/// ```
+#[derive(Clone)]
+pub struct StaticKeyProvider {
+    current_key_id: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl fmt::Debug for StaticKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticKeyProvider")
+            .field("current_key_id", &self.current_key_id)
+            .field("key_ids", &self.keys.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl StaticKeyProvider {
+    /// Create a new provider wrapping data keys with the provided key.
+    pub fn new(key_id: impl Into<String>, key: [u8; 32]) -> Self {
+        let key_id = key_id.into();
+        let mut keys = HashMap::new();
+        keys.insert(key_id.clone(), Aes256Gcm::new(&Key::<Aes256Gcm>::from(key)));
+        Self {
+            current_key_id: key_id,
+            keys,
+        }
+    }
+
+    /// Add a retired key, used only to unwrap data keys wrapped before the
+    /// current key was introduced.
+    pub fn with_previous_key(mut self, key_id: impl Into<String>, key: [u8; 32]) -> Self {
+        self.keys
+            .entry(key_id.into())
+            .or_insert_with(|| Aes256Gcm::new(&Key::<Aes256Gcm>::from(key)));
+        self
+    }
+}
+
+#[async_trait]
+impl KeyProvider for StaticKeyProvider {
+    async fn wrap_key(&self, data_key: &[u8]) -> session_store::Result<WrappedKey> {
+        let cipher = &self.keys[&self.current_key_id];
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut ciphertext = nonce.to_vec();
+        ciphertext.extend(
+            cipher
+                .encrypt(&nonce, data_key)
+                .map_err(|_| session_store::Error::Encode("Failed to wrap data key".into()))?,
+        );
+
+        Ok(WrappedKey {
+            key_id: self.current_key_id.clone(),
+            ciphertext,
+        })
+    }
+
+    async fn unwrap_key(&self, wrapped_key: &WrappedKey) -> session_store::Result<Vec<u8>> {
+        let cipher = self.keys.get(&wrapped_key.key_id).ok_or_else(|| {
+            session_store::Error::Decode(format!("Unknown key ID `{}`", wrapped_key.key_id))
+        })?;
+        if wrapped_key.ciphertext.len() < NONCE_LEN {
+            return Err(session_store::Error::Decode(
+                "Malformed wrapped data key".into(),
+            ));
+        }
+        let (nonce, ciphertext) = wrapped_key.ciphertext.split_at(NONCE_LEN);
+
+        cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| session_store::Error::Decode("Failed to unwrap data key".into()))
+    }
+}
+
+/// The data key used to encrypt new sessions.
+struct CurrentDataKey {
+    cipher: Aes256Gcm,
+    wrapped_key: WrappedKey,
+    created_at: Instant,
+}
+
+#[derive(Default)]
+struct DataKeys {
+    current: Option<Arc<CurrentDataKey>>,
+    // Unwrapped data keys by wrapped key, so that loads need not call the
+    // provider for every session.
+    unwrapped: HashMap<(String, Vec<u8>), Aes256Gcm>,
+}
+
+/// A session store wrapper encrypting session data with envelope encryption.
+///
+/// Session data is encrypted with AES-256-GCM under a data key generated by
+/// the store. The data key is wrapped by a [`KeyProvider`] and stored with the
+/// encrypted data, and is reused for new sessions until it reaches the
+/// configured lifetime, so the provider is called once per data key rather
+/// than once per operation. Unwrapped data keys are cached for loads.
+///
+/// The ciphertext is bound to the session ID, so the encrypted data of one
+/// session cannot be moved to another. Expiry dates are left in the clear for
+/// the backend to expire sessions.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::{EncryptedStore, StaticKeyProvider};
+///
+/// let key_provider = StaticKeyProvider::new("2024-06", [7; 32]);
+/// let session_store = EncryptedStore::new(MemoryStore::default(), key_provider);
+/// ```
+pub struct EncryptedStore<S: SessionStore, K: KeyProvider> {
+    store: S,
+    key_provider: Arc<K>,
+    data_key_lifetime: Duration,
+    data_keys: Arc<Mutex<DataKeys>>,
+}
+
+// Implemented by hand so that key providers need not be `Clone`.
+impl<S: SessionStore + Clone, K: KeyProvider> Clone for EncryptedStore<S, K> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            key_provider: self.key_provider.clone(),
+            data_key_lifetime: self.data_key_lifetime,
+            data_keys: self.data_keys.clone(),
+        }
+    }
+}
+
+impl<S: SessionStore, K: KeyProvider> fmt::Debug for EncryptedStore<S, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedStore")
+            .field("store", &self.store)
+            .field("key_provider", &self.key_provider)
+            .field("data_key_lifetime", &self.data_key_lifetime)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: SessionStore, K: KeyProvider> EncryptedStore<S, K> {
+    /// Create a new encrypted store wrapping the provided store, with data
+    /// keys wrapped by the provided key provider.
+    pub fn new(store: S, key_provider: K) -> Self {
+        Self {
+            store,
+            key_provider: Arc::new(key_provider),
+            data_key_lifetime: Duration::from_secs(60 * 60),
+            data_keys: Arc::default(),
+        }
+    }
+
+    /// Set how long a data key is used to encrypt new sessions before a new
+    /// one is generated, one hour by default.
+    pub fn with_data_key_lifetime(mut self, data_key_lifetime: Duration) -> Self {
+        self.data_key_lifetime = data_key_lifetime;
+        self
+    }
+
+    async fn current_data_key(&self) -> session_store::Result<Arc<CurrentDataKey>> {
+        if let Some(current) = &self.data_keys.lock().unwrap().current {
+            if current.created_at.elapsed() < self.data_key_lifetime {
+                return Ok(current.clone());
+            }
+        }
+
+        self.renew_data_key().await
+    }
+
+    /// Generate a data key and use it to encrypt new sessions from now on.
+    async fn renew_data_key(&self) -> session_store::Result<Arc<CurrentDataKey>> {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let wrapped_key = self.key_provider.wrap_key(&data_key).await?;
+        let current = Arc::new(CurrentDataKey {
+            cipher: Aes256Gcm::new(&data_key),
+            wrapped_key,
+            created_at: Instant::now(),
+        });
+        self.data_keys.lock().unwrap().current = Some(current.clone());
+        Ok(current)
+    }
+
+    async fn data_key(&self, wrapped_key: WrappedKey) -> session_store::Result<Aes256Gcm> {
+        let cache_key = (wrapped_key.key_id, wrapped_key.ciphertext);
+        if let Some(cipher) = self.data_keys.lock().unwrap().unwrapped.get(&cache_key) {
+            return Ok(cipher.clone());
+        }
+
+        let wrapped_key = WrappedKey {
+            key_id: cache_key.0,
+            ciphertext: cache_key.1,
+        };
+        let data_key = self.key_provider.unwrap_key(&wrapped_key).await?;
+        if data_key.len() != 32 {
+            return Err(session_store::Error::Decode(
+                "Unwrapped data key has the wrong length".into(),
+            ));
+        }
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+
+        let mut data_keys = self.data_keys.lock().unwrap();
+        if data_keys.unwrapped.len() >= MAX_CACHED_DATA_KEYS {
+            data_keys.unwrapped.clear();
+        }
+        data_keys
+            .unwrapped
+            .insert((wrapped_key.key_id, wrapped_key.ciphertext), cipher.clone());
+        Ok(cipher)
+    }
+
+    async fn encrypt(&self, record: &Record) -> session_store::Result<Record> {
+        let data_key = self.current_data_key().await?;
+        let plaintext = rmp_serde::to_vec(&record.data)
+            .map_err(|err| session_store::Error::Encode(err.to_string()))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = record.id.to_string();
+        let ciphertext = data_key
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| session_store::Error::Encode("Failed to encrypt session".into()))?;
+
+        let envelope = json!({
+            "kid": data_key.wrapped_key.key_id,
+            "dek": hex::encode(&data_key.wrapped_key.ciphertext),
+            "nonce": hex::encode(nonce),
+            "data": hex::encode(ciphertext),
+        });
+        Ok(Record {
+            id: record.id,
+            data: HashMap::from([(ENVELOPE_KEY.to_string(), envelope)]),
+            expiry_date: record.expiry_date,
+        })
+    }
+
+    async fn decrypt(&self, mut record: Record) -> session_store::Result<Record> {
+        let envelope = record
+            .data
+            .remove(ENVELOPE_KEY)
+            .ok_or_else(|| session_store::Error::Decode("Session is not encrypted".into()))?;
+        let field = |name: &str| -> session_store::Result<Vec<u8>> {
+            envelope
+                .get(name)
+                .and_then(Value::as_str)
+                .and_then(|value| hex::decode(value).ok())
+                .ok_or_else(|| {
+                    session_store::Error::Decode(format!("Malformed encrypted session `{name}`"))
+                })
+        };
+        let key_id = envelope
+            .get("kid")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                session_store::Error::Decode("Malformed encrypted session `kid`".into())
+            })?
+            .to_string();
+        let nonce = field("nonce")?;
+        if nonce.len() != NONCE_LEN {
+            return Err(session_store::Error::Decode(
+                "Malformed encrypted session `nonce`".into(),
+            ));
+        }
+
+        let cipher = self
+            .data_key(WrappedKey {
+                key_id,
+                ciphertext: field("dek")?,
+            })
+            .await?;
+        let aad = record.id.to_string();
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &field("data")?,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| session_store::Error::Decode("Failed to decrypt session".into()))?;
+        record.data = rmp_serde::from_slice(&plaintext)
+            .map_err(|err| session_store::Error::Decode(err.to_string()))?;
+
+        Ok(record)
+    }
+}
+
+impl<S: ExportSessions, K: KeyProvider> EncryptedStore<S, K> {
+    /// Re-encrypt every stored session under a fresh data key, wrapped by the
+    /// provider's current key-encryption key, so that earlier keys can be
+    /// retired.
+    ///
+    /// Sessions are read from the wrapped store in batches, loaded again
+    /// right before they're re-encrypted, and saved back. Sessions deleted in
+    /// the meantime are skipped, as are sessions already encrypted under the
+    /// current data key, e.g. because they were saved since the rotation
+    /// started. The report's `last_id` can be passed to
+    /// [`RotateKeysOptions::with_after`] to resume an interrupted rotation.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::session_store;
+    /// use tower_sessions_ext::{EncryptedStore, ExportSessions, RotateKeysOptions, StaticKeyProvider};
+    ///
+    /// async fn rotate(store: impl ExportSessions) -> session_store::Result<()> {
+    ///     let key_provider =
+    ///         StaticKeyProvider::new("2024-06", [7; 32]).with_previous_key("2023-12", [3; 32]);
+    ///     let session_store = EncryptedStore::new(store, key_provider);
+    ///
+    ///     let options = RotateKeysOptions::default().with_progress(|report| {
+    ///         println!("{} sessions rotated, up to {:?}", report.rotated, report.last_id);
+    ///     });
+    ///     session_store.rotate_keys(&options).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn rotate_keys(
+        &self,
+        options: &RotateKeysOptions,
+    ) -> session_store::Result<KeyRotationReport> {
+        let started = Instant::now();
+        self.renew_data_key().await?;
+        let mut report = KeyRotationReport {
+            last_id: options.after,
+            ..Default::default()
+        };
+
+        loop {
+            let records = self
+                .store
+                .export(report.last_id, options.batch_size)
+                .await?;
+            if records.is_empty() {
+                break;
+            }
+
+            for record in records {
+                report.read += 1;
+                if self.rotate_key(&record.id).await? {
+                    report.rotated += 1;
+                } else {
+                    report.skipped += 1;
+                }
+                report.last_id = Some(record.id);
+            }
+
+            report.elapsed = started.elapsed();
+            if let Some(progress) = &options.progress {
+                progress(&report);
+            }
+        }
+
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+
+    /// Re-encrypt the session under the current data key, returning whether
+    /// it was rewritten.
+    async fn rotate_key(&self, session_id: &Id) -> session_store::Result<bool> {
+        let Some(encrypted) = self.store.load(session_id).await? else {
+            return Ok(false);
+        };
+
+        let current = self.current_data_key().await?;
+        let current_dek = hex::encode(&current.wrapped_key.ciphertext);
+        let is_current = encrypted
+            .data
+            .get(ENVELOPE_KEY)
+            .and_then(|envelope| envelope.get("dek"))
+            .and_then(Value::as_str)
+            .is_some_and(|dek| dek == current_dek);
+        if is_current {
+            return Ok(false);
+        }
+
+        let record = self.decrypt(encrypted).await?;
+        self.store.save(&self.encrypt(&record).await?).await?;
+        Ok(true)
+    }
+}
+
+type ProgressCallback = Arc<dyn Fn(&KeyRotationReport) + Send + Sync>;
+
+/// Options for [`EncryptedStore::rotate_keys`].
+#[derive(Clone)]
+pub struct RotateKeysOptions {
+    batch_size: u32,
+    after: Option<Id>,
+    progress: Option<ProgressCallback>,
+}
+
+impl Default for RotateKeysOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 1_000,
+            after: None,
+            progress: None,
+        }
+    }
+}
+
+impl RotateKeysOptions {
+    /// Set the number of sessions read from the wrapped store at a time.
+    ///
+    /// Defaults to 1,000.
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Start after the session with the provided ID, as reported in
+    /// [`KeyRotationReport::last_id`], to resume an interrupted rotation.
+    pub fn with_after(mut self, after: Id) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Call the provided function with the running totals after each batch.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&KeyRotationReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+impl fmt::Debug for RotateKeysOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RotateKeysOptions")
+            .field("batch_size", &self.batch_size)
+            .field("after", &self.after)
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// Totals for a run of [`EncryptedStore::rotate_keys`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyRotationReport {
+    /// Sessions read from the wrapped store.
+    pub read: u64,
+
+    /// Sessions re-encrypted and saved back.
+    pub rotated: u64,
+
+    /// Sessions deleted since they were read, or already encrypted under the
+    /// current data key.
+    pub skipped: u64,
+
+    /// The ID of the last session read, from which the rotation can be
+    /// resumed.
+    pub last_id: Option<Id>,
+
+    /// Time spent so far.
+    pub elapsed: Duration,
+}
+
+#[async_trait]
+impl<S: SessionStore, K: KeyProvider> SessionStore for EncryptedStore<S, K> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let mut encrypted = self.encrypt(record).await?;
+        self.store.create(&mut encrypted).await?;
+        if encrypted.id != record.id {
+            // The ciphertext is bound to the ID it was created with, so re-encrypt
+            // under the ID the store assigned after a collision.
+            record.id = encrypted.id;
+            self.store.save(&self.encrypt(record).await?).await?;
+        }
+        Ok(())
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.store.save(&self.encrypt(record).await?).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        match self.store.load(session_id).await? {
+            Some(record) => Ok(Some(self.decrypt(record).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.store.delete(session_id).await
+    }
+}
+
+#[async_trait]
+impl<S, K> ExpiredDeletion for EncryptedStore<S, K>
+where
+    S: ExpiredDeletion,
+    K: KeyProvider,
+{
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.store.delete_expired().await
+    }
+}
+
+#[async_trait]
+impl<S, K> TouchSessionStore for EncryptedStore<S, K>
+where
+    S: TouchSessionStore,
+    K: KeyProvider,
+{
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        self.store.touch(session_id, expiry_date).await
+    }
+}
+
+#[async_trait]
+impl<S, K> ExportSessions for EncryptedStore<S, K>
+where
+    S: ExportSessions,
+    K: KeyProvider,
+{
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        let mut records = Vec::new();
+        for record in self.store.export(after, limit).await? {
+            records.push(self.decrypt(record).await?);
+        }
+        Ok(records)
+    }
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use time::OffsetDateTime;
+use tower_sessions_core::{session_store, SessionStore};
+
+/// A session store that can erase every session belonging to a user.
+///
+/// This supports right-to-be-forgotten workflows, where all sessions of a
+/// data subject must be removed on request. Stores implement it on top of a
+/// user index, so the user ID must be mirrored out of session data as
+/// configured by the store.
+#[async_trait]
+pub trait EraseUserSessions: SessionStore {
+    /// Delete all sessions, expired or not, whose user matches the given
+    /// value.
+    ///
+    /// The returned report identifies the user by a hash keyed with `key`;
+    /// see [`ErasureReport::new`].
+    async fn erase_user(&self, user_id: &Value, key: &[u8])
+        -> session_store::Result<ErasureReport>;
+}
+
+/// A record of the sessions erased for a user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErasureReport {
+    /// The hex-encoded HMAC-SHA256 of the JSON-encoded user ID.
+    pub user_id_hash: String,
+
+    /// The number of sessions erased.
+    pub erased: u64,
+
+    /// When the erasure completed.
+    pub timestamp: OffsetDateTime,
+}
+
+impl ErasureReport {
+    /// Create a report of `erased` sessions erased for the given user now.
+    ///
+    /// The user ID is hashed with the provided key, so that the report can be
+    /// kept without identifying the user, as an unkeyed hash of a guessable
+    /// ID such as an email address would. Hashing the same user ID with the
+    /// same key gives the same hash, so a report can still be matched to a
+    /// later request from the user.
+    pub fn new(user_id: &Value, erased: u64, key: &[u8]) -> Self {
+        Self {
+            user_id_hash: Self::user_id_hash(user_id, key),
+            erased,
+            timestamp: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Returns the hash identifying the user in reports created with the
+    /// provided key.
+    pub fn user_id_hash(user_id: &Value, key: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(user_id.to_string().as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Sign the report with the provided key, returning a hex-encoded
+    /// HMAC-SHA256 signature.
+    ///
+    /// The signature allows the report to be kept as evidence of the erasure
+    /// and checked later with [`ErasureReport::verify`].
+    pub fn sign(&self, key: &[u8]) -> String {
+        hex::encode(self.mac(key).finalize().into_bytes())
+    }
+
+    /// Check a signature produced by [`ErasureReport::sign`] with the same
+    /// key.
+    pub fn verify(&self, key: &[u8], signature: &str) -> bool {
+        hex::decode(signature)
+            .map(|signature| self.mac(key).verify_slice(&signature).is_ok())
+            .unwrap_or(false)
+    }
+
+    fn mac(&self, key: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(
+            format!(
+                "{}:{}:{}",
+                self.user_id_hash,
+                self.erased,
+                self.timestamp.unix_timestamp_nanos()
+            )
+            .as_bytes(),
+        );
+        mac
+    }
+}
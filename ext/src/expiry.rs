@@ -0,0 +1,43 @@
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tower_sessions_core::session::Record;
+
+/// Whether a store checks a session's expiry date itself when loading it.
+///
+/// Redis and Moka expire entries on their own, but not at the exact expiry
+/// date of the session: Redis expiry has a resolution of one second, and Moka
+/// tracks expiry with a monotonic clock that can drift from the wall clock.
+/// [`ExpiryEnforcement::Strict`] makes `load` additionally compare the expiry
+/// date with the current time, so that a session is never returned past its
+/// expiry date.
+///
+/// SQL and MongoDB stores compare expiry dates in their load queries and
+/// always behave as [`ExpiryEnforcement::Strict`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryEnforcement {
+    /// Check the expiry date of each loaded session against the current time.
+    Strict,
+
+    /// Rely on the backend to no longer return expired sessions.
+    #[default]
+    BackendOnly,
+}
+
+impl ExpiryEnforcement {
+    /// Filter out the loaded record if it is expired and expiry is enforced
+    /// strictly.
+    pub fn apply(self, record: Option<Record>) -> Option<Record> {
+        self.apply_at(record, OffsetDateTime::now_utc())
+    }
+
+    /// Like [`ExpiryEnforcement::apply`], comparing the expiry date with the
+    /// provided time rather than the current time, for stores with their own
+    /// clock.
+    pub fn apply_at(self, record: Option<Record>, now: OffsetDateTime) -> Option<Record> {
+        match self {
+            ExpiryEnforcement::Strict => record.filter(|record| record.expiry_date > now),
+            ExpiryEnforcement::BackendOnly => record,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use async_trait::async_trait;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, SessionStore,
+};
+
+/// A session store whose records can be read out in bulk.
+///
+/// Records are returned in a stable, store-defined order by ID, one page at a
+/// time, so that every record can be visited without holding them all in
+/// memory.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions::session_store;
+/// use tower_sessions_ext::ExportSessions;
+///
+/// async fn count_sessions(session_store: &impl ExportSessions) -> session_store::Result<usize> {
+///     let mut count = 0;
+///     let mut after = None;
+///     loop {
+///         let records = session_store.export(after, 100).await?;
+///         let Some(last) = records.last() else {
+///             break;
+///         };
+///         after = Some(last.id);
+///         count += records.len();
+///     }
+///     Ok(count)
+/// }
+/// ```
+#[async_trait]
+pub trait ExportSessions: SessionStore {
+    /// Returns up to `limit` records following the record with ID `after`, or
+    /// from the start if `after` is `None`.
+    ///
+    /// Expired records that have not yet been removed may be included.
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>>;
+}
@@ -0,0 +1,155 @@
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+
+use crate::{IdCollisionPolicy, TouchSessionStore};
+
+/// A session store wrapper storing sessions under a salted hash of their ID
+/// rather than the ID itself.
+///
+/// Session IDs are what session cookies carry, so a store keyed by them lets
+/// anyone with a database dump or Redis snapshot impersonate every user with a
+/// live session. This wrapper instead passes the wrapped store an ID derived
+/// from an HMAC-SHA256 of the session ID keyed with the salt, and restores the
+/// original ID on load, so stored keys can't be turned back into cookies
+/// without the salt.
+///
+/// The salt must be kept secret and be the same for every instance sharing
+/// the backend; changing it makes every stored session unreachable. Wrap the
+/// backend store directly, so that every other layer sees the original IDs.
+/// Stores that can be exported aren't exported through this wrapper, as
+/// their records can't be mapped back to the original IDs.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::HashedIdStore;
+///
+/// let session_store = HashedIdStore::new(MemoryStore::default(), b"keep me secret".to_vec());
+/// ```
+#[derive(Clone)]
+pub struct HashedIdStore<S: SessionStore> {
+    store: S,
+    salt: Arc<[u8]>,
+    id_collision_policy: IdCollisionPolicy,
+}
+
+impl<S: SessionStore> fmt::Debug for HashedIdStore<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashedIdStore")
+            .field("store", &self.store)
+            .field("id_collision_policy", &self.id_collision_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: SessionStore> HashedIdStore<S> {
+    /// Create a new hashed ID store wrapping the provided store, with IDs
+    /// hashed under the provided salt.
+    pub fn new(store: S, salt: impl Into<Vec<u8>>) -> Self {
+        Self {
+            store,
+            salt: salt.into().into(),
+            id_collision_policy: IdCollisionPolicy::default(),
+        }
+    }
+
+    /// Set the policy used to replace session IDs whose hashes collide with
+    /// existing sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    /// Returns the ID the session with the provided ID is stored under.
+    pub fn hashed_id(&self, session_id: &Id) -> Id {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.salt).expect("HMAC accepts keys of any length");
+        mac.update(session_id.to_string().as_bytes());
+        let digest = mac.finalize().into_bytes();
+        let (hashed, _) = digest
+            .split_first_chunk::<16>()
+            .expect("SHA-256 digests are 32 bytes");
+        Id(i128::from_le_bytes(*hashed))
+    }
+
+    fn hashed(&self, record: &Record) -> Record {
+        Record {
+            id: self.hashed_id(&record.id),
+            ..record.clone()
+        }
+    }
+}
+
+#[async_trait]
+impl<S: SessionStore> SessionStore for HashedIdStore<S> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let mut collisions = 0;
+        loop {
+            let mut hashed = self.hashed(record);
+            self.store.create(&mut hashed).await?;
+            if hashed.id == self.hashed_id(&record.id) {
+                return Ok(());
+            }
+
+            // The store replaced the hashed ID, which can't be mapped back to a
+            // session ID, so remove that session and retry with a new ID.
+            self.store.delete(&hashed.id).await?;
+            collisions += 1;
+            record.id = self.id_collision_policy.next_id(collisions)?;
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.store.save(&self.hashed(record)).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        Ok(self
+            .store
+            .load(&self.hashed_id(session_id))
+            .await?
+            .map(|record| Record {
+                id: *session_id,
+                ..record
+            }))
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.store.delete(&self.hashed_id(session_id)).await
+    }
+}
+
+#[async_trait]
+impl<S> ExpiredDeletion for HashedIdStore<S>
+where
+    S: ExpiredDeletion,
+{
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.store.delete_expired().await
+    }
+}
+
+#[async_trait]
+impl<S> TouchSessionStore for HashedIdStore<S>
+where
+    S: TouchSessionStore,
+{
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        self.store
+            .touch(&self.hashed_id(session_id), expiry_date)
+            .await
+    }
+}
@@ -0,0 +1,82 @@
+#[macro_use]
+mod trace;
+
+pub use self::{
+    collision::IdCollisionPolicy,
+    config::StoreOptionsConfig,
+    consistency::{check_consistency, ConsistencyOptions, ConsistencyReport, SessionDrift},
+    decode_failure::DecodeFailurePolicy,
+    expiry::ExpiryEnforcement,
+    export::ExportSessions,
+    migrate::{migrate_sessions, MigrateOptions, MigrationReport},
+    options::StoreOptions,
+    replay::{Fixture, RecordingStore, ReplayStore},
+    size_profile::{SizeProfile, SizeProfilingStore},
+    slow_op::SlowOpTracer,
+    tag::ErrorTag,
+    touch::TouchSessionStore,
+    transient::{is_transient, transient_backend_error},
+    ttl::TtlPolicy,
+    versioned::VersionedStore,
+};
+
+#[cfg(feature = "runtime")]
+pub use self::{
+    deadline::{request_deadline, with_request_deadline},
+    deletion_scheduler::DeletionScheduler,
+    rate_limit::{is_rate_limited, with_create_rate_key, CreateRateLimitedStore},
+    timeout::{is_timeout, TimeoutStore},
+};
+
+#[cfg(feature = "crypto")]
+pub use self::{
+    audit::{hash_session_id, AuditEvent, AuditOperation, AuditOutcome, AuditSink, AuditedStore},
+    encrypted::{
+        EncryptedStore, KeyProvider, KeyRotationReport, RotateKeysOptions, StaticKeyProvider,
+        WrappedKey,
+    },
+    erase::{EraseUserSessions, ErasureReport},
+    hashed_id::HashedIdStore,
+};
+
+#[cfg(all(feature = "runtime", feature = "crypto"))]
+pub use self::stack::StoreStack;
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+#[cfg(feature = "crypto")]
+mod audit;
+mod collision;
+mod config;
+mod consistency;
+#[cfg(feature = "runtime")]
+mod deadline;
+mod decode_failure;
+#[cfg(feature = "runtime")]
+mod deletion_scheduler;
+#[cfg(feature = "crypto")]
+mod encrypted;
+#[cfg(feature = "crypto")]
+mod erase;
+mod expiry;
+mod export;
+#[cfg(feature = "crypto")]
+mod hashed_id;
+mod migrate;
+mod options;
+#[cfg(feature = "runtime")]
+mod rate_limit;
+mod replay;
+mod size_profile;
+mod slow_op;
+#[cfg(all(feature = "runtime", feature = "crypto"))]
+mod stack;
+mod tag;
+#[cfg(feature = "runtime")]
+mod timeout;
+mod touch;
+mod transient;
+mod ttl;
+mod versioned;
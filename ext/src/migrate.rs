@@ -0,0 +1,208 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use time::OffsetDateTime;
+use tower_sessions_core::{session_store, SessionStore};
+
+use crate::ExportSessions;
+
+type ProgressCallback = Arc<dyn Fn(&MigrationReport) + Send + Sync>;
+
+/// Options for [`migrate_sessions`].
+#[derive(Clone)]
+pub struct MigrateOptions {
+    batch_size: u32,
+    dry_run: bool,
+    expiring_after: Option<OffsetDateTime>,
+    overwrite: bool,
+    progress: Option<ProgressCallback>,
+}
+
+impl Default for MigrateOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 1_000,
+            dry_run: false,
+            expiring_after: None,
+            overwrite: false,
+            progress: None,
+        }
+    }
+}
+
+impl MigrateOptions {
+    /// Set the number of records read from the source store at a time.
+    ///
+    /// Defaults to 1,000.
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Read and count records without writing them to the destination store.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Only copy sessions expiring after the provided date.
+    ///
+    /// Defaults to the time the migration starts, so expired sessions are
+    /// never copied.
+    pub fn with_expiring_after(mut self, expiring_after: OffsetDateTime) -> Self {
+        self.expiring_after = Some(expiring_after);
+        self
+    }
+
+    /// Overwrite sessions that already exist in the destination store instead
+    /// of skipping them.
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Call the provided function with the running totals after each batch.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&MigrationReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+impl fmt::Debug for MigrateOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MigrateOptions")
+            .field("batch_size", &self.batch_size)
+            .field("dry_run", &self.dry_run)
+            .field("expiring_after", &self.expiring_after)
+            .field("overwrite", &self.overwrite)
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// Totals for a run of [`migrate_sessions`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Records read from the source store.
+    pub read: u64,
+
+    /// Records written to the destination store, or that would have been
+    /// written in a dry run.
+    pub copied: u64,
+
+    /// Records skipped because they expire too soon.
+    pub skipped_expired: u64,
+
+    /// Records skipped because the destination store already has them.
+    pub skipped_existing: u64,
+
+    /// Time spent so far.
+    pub elapsed: Duration,
+}
+
+impl MigrationReport {
+    /// Records copied per second.
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.copied as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Copy sessions from one store to another, preserving their IDs.
+///
+/// Any store implementing [`ExportSessions`] can be copied from, e.g. from a
+/// `RedisStore` to a `PostgresStore` when moving sessions to the database.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions::{session_store, SessionStore};
+/// use tower_sessions_ext::{migrate_sessions, ExportSessions, MigrateOptions};
+///
+/// async fn migrate(
+///     from: &impl ExportSessions,
+///     to: &impl SessionStore,
+/// ) -> session_store::Result<()> {
+///     let options = MigrateOptions::default().with_progress(|report| {
+///         println!("{} sessions copied ({:.0}/s)", report.copied, report.throughput());
+///     });
+///     let report = migrate_sessions(from, to, &options).await?;
+///     println!("{} sessions skipped as expired", report.skipped_expired);
+///     Ok(())
+/// }
+/// ```
+pub async fn migrate_sessions<F, T>(
+    from: &F,
+    to: &T,
+    options: &MigrateOptions,
+) -> session_store::Result<MigrationReport>
+where
+    F: ExportSessions,
+    T: SessionStore,
+{
+    let started = Instant::now();
+    let expiring_after = options
+        .expiring_after
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    let mut report = MigrationReport::default();
+    let mut after = None;
+
+    loop {
+        let records = from.export(after, options.batch_size).await?;
+        let Some(last) = records.last() else {
+            break;
+        };
+        after = Some(last.id);
+
+        for mut record in records {
+            report.read += 1;
+
+            if record.expiry_date <= expiring_after {
+                report.skipped_expired += 1;
+                continue;
+            }
+
+            let exists = to.load(&record.id).await?.is_some();
+            if exists && !options.overwrite {
+                report.skipped_existing += 1;
+                continue;
+            }
+
+            if !options.dry_run {
+                if exists {
+                    to.save(&record).await?;
+                } else {
+                    let id = record.id;
+                    to.create(&mut record).await?;
+                    if record.id != id {
+                        // Another writer took the ID since it was checked, so the
+                        // store assigned a new one; undo rather than fork the
+                        // session.
+                        to.delete(&record.id).await?;
+                        report.skipped_existing += 1;
+                        continue;
+                    }
+                }
+            }
+            report.copied += 1;
+        }
+
+        report.elapsed = started.elapsed();
+        if let Some(progress) = &options.progress {
+            progress(&report);
+        }
+    }
+
+    report.elapsed = started.elapsed();
+    Ok(report)
+}
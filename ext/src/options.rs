@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use crate::{DecodeFailurePolicy, ExpiryEnforcement, IdCollisionPolicy, TtlPolicy};
+
+/// Options shared by the stores of this workspace, so that code configuring a
+/// store can be generic over its backend.
+///
+/// Every store is configured by chaining `with_*` methods on it after
+/// construction; this trait gives the ones that apply to several backends a
+/// common signature. Switching backends then only changes how the store is
+/// constructed. Options that don't apply to a store are ignored by it, e.g.
+/// the decode failure policy of a store that doesn't serialize sessions.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::{ExpiryEnforcement, StoreOptions};
+///
+/// fn configure<S: StoreOptions>(store: S) -> Result<S, String> {
+///     Ok(store
+///         .with_namespace("sessions")?
+///         .with_expiry_enforcement(ExpiryEnforcement::Strict)
+///         .with_slow_op_threshold(Duration::from_millis(50)))
+/// }
+/// ```
+pub trait StoreOptions: Sized {
+    /// Store sessions under the provided name: the table of SQL stores, the
+    /// collection of MongoDB stores, or the key prefix of Redis stores,
+    /// followed by a colon.
+    ///
+    /// Stores that keep sessions in process memory ignore it.
+    fn with_namespace(self, _namespace: &str) -> Result<Self, String> {
+        Ok(self)
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    fn with_id_collision_policy(self, id_collision_policy: IdCollisionPolicy) -> Self;
+
+    /// Set how `load` responds to a session whose stored data can't be
+    /// decoded.
+    fn with_decode_failure_policy(self, _decode_failure_policy: DecodeFailurePolicy) -> Self {
+        self
+    }
+
+    /// Log operations taking longer than the provided threshold with
+    /// `tracing::warn!`.
+    fn with_slow_op_threshold(self, _threshold: Duration) -> Self {
+        self
+    }
+
+    /// Set whether `load` checks the expiry date of sessions in addition to
+    /// the backend's own expiry.
+    ///
+    /// Stores that compare expiry dates in their load queries always behave
+    /// as [`ExpiryEnforcement::Strict`] and ignore it.
+    fn with_expiry_enforcement(self, _expiry_enforcement: ExpiryEnforcement) -> Self {
+        self
+    }
+
+    /// Set the policy adjusting the expiry dates persisted for sessions.
+    ///
+    /// Stores that keep sessions in process memory ignore it.
+    fn with_ttl_policy(self, _ttl_policy: TtlPolicy) -> Self {
+        self
+    }
+}
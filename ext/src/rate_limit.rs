@@ -0,0 +1,299 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+
+use crate::ErrorTag;
+
+/// The number of keyed buckets kept, beyond which the least recently used
+/// is evicted.
+const MAX_KEYS: usize = 10_000;
+
+/// How often keyed buckets that have refilled are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+tokio::task_local! {
+    static CREATE_RATE_KEY: String;
+}
+
+/// Run the provided future with a key that [`CreateRateLimitedStore`] limits
+/// session creation by, e.g. the client's IP address.
+///
+/// This is meant to be applied by middleware around the session layer.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_ext::with_create_rate_key;
+///
+/// # tokio_test::block_on(async {
+/// with_create_rate_key("203.0.113.7", async {
+///     // Handle the request.
+/// })
+/// .await;
+/// # })
+/// ```
+pub async fn with_create_rate_key<F: Future>(key: impl Into<String>, future: F) -> F::Output {
+    CREATE_RATE_KEY.scope(key.into(), future).await
+}
+
+/// Returns `true` if the error was produced by [`CreateRateLimitedStore`]
+/// because too many sessions were created.
+///
+/// Such errors are reported as backend errors tagged with
+/// [`ErrorTag::RateLimited`], which this function recognizes.
+pub fn is_rate_limited(err: &session_store::Error) -> bool {
+    ErrorTag::RateLimited.is_tagged(err)
+}
+
+/// A number of sessions that may be created per period.
+#[derive(Debug, Clone, Copy)]
+struct Rate {
+    burst: f64,
+    per_second: f64,
+}
+
+impl Rate {
+    fn new(limit: u32, per: Duration) -> Self {
+        let burst = f64::from(limit.max(1));
+        Self {
+            burst,
+            per_second: burst / per.as_secs_f64().max(f64::EPSILON),
+        }
+    }
+
+    /// How long an empty bucket takes to refill.
+    fn refill_time(self) -> Duration {
+        Duration::from_secs_f64(self.burst / self.per_second)
+    }
+}
+
+/// A token bucket holding the sessions that may still be created.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn full(rate: Rate, now: Instant) -> Self {
+        Self {
+            tokens: rate.burst,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, rate: Rate, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst);
+        self.updated_at = now;
+    }
+
+    fn has_token(&mut self, rate: Rate, now: Instant) -> bool {
+        self.refill(rate, now);
+        self.tokens >= 1.0
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+/// A keyed bucket, along with its position in [`Buckets::by_use`].
+#[derive(Debug)]
+struct KeyedBucket {
+    bucket: Bucket,
+    used: (Instant, u64),
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    global: Option<Bucket>,
+    keyed: HashMap<String, KeyedBucket>,
+
+    // Keys by when their bucket was last used, least recently first, with a
+    // counter telling apart buckets used at the same instant.
+    by_use: BTreeMap<(Instant, u64), String>,
+    uses: u64,
+    pruned_at: Option<Instant>,
+}
+
+impl Buckets {
+    /// The bucket for the key, which is marked as the most recently used.
+    fn keyed_bucket(&mut self, key: String, rate: Rate, now: Instant) -> &mut Bucket {
+        self.uses += 1;
+        let used = (now, self.uses);
+
+        if let Some(keyed) = self.keyed.get_mut(&key) {
+            self.by_use.remove(&keyed.used);
+            keyed.used = used;
+        } else if self.keyed.len() >= MAX_KEYS {
+            // An evicted bucket starts over full, which the overall limit
+            // still bounds.
+            if let Some((_, evicted)) = self.by_use.pop_first() {
+                self.keyed.remove(&evicted);
+            }
+        }
+
+        self.by_use.insert(used, key.clone());
+        &mut self
+            .keyed
+            .entry(key)
+            .or_insert_with(|| KeyedBucket {
+                bucket: Bucket::full(rate, now),
+                used,
+            })
+            .bucket
+    }
+
+    /// Drop the keyed buckets unused for long enough to have refilled, which
+    /// are the same as new ones, at most once per [`PRUNE_INTERVAL`].
+    fn prune(&mut self, rate: Rate, now: Instant) {
+        if self
+            .pruned_at
+            .is_some_and(|pruned_at| now.duration_since(pruned_at) < PRUNE_INTERVAL)
+        {
+            return;
+        }
+        self.pruned_at = Some(now);
+
+        let refill_time = rate.refill_time();
+        while let Some(entry) = self.by_use.first_entry() {
+            if now.duration_since(entry.key().0) < refill_time {
+                break;
+            }
+            let key = entry.remove();
+            self.keyed.remove(&key);
+        }
+    }
+}
+
+/// A session store wrapper capping how many sessions are created per period.
+///
+/// Bots that don't keep cookies create a new session on every request, which
+/// can fill a backend with millions of sessions used only once. This store
+/// rejects `create` calls beyond the limit with an error for which
+/// [`is_rate_limited`] returns `true`; other operations aren't limited.
+///
+/// The limit applies to all sessions created through the store. With
+/// [`CreateRateLimitedStore::with_key_limit`], each key set via
+/// [`with_create_rate_key`] is limited separately as well. Limits allow
+/// bursts of up to the given number of sessions and then refill evenly over
+/// the period.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::CreateRateLimitedStore;
+///
+/// let session_store =
+///     CreateRateLimitedStore::new(MemoryStore::default(), 1_000, Duration::from_secs(1))
+///         .with_key_limit(10, Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CreateRateLimitedStore<S: SessionStore> {
+    store: S,
+    global_rate: Rate,
+    key_rate: Option<Rate>,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl<S: SessionStore> CreateRateLimitedStore<S> {
+    /// Create a new rate-limited store wrapping the provided store, allowing
+    /// up to `limit` sessions to be created per period.
+    pub fn new(store: S, limit: u32, per: Duration) -> Self {
+        Self {
+            store,
+            global_rate: Rate::new(limit, per),
+            key_rate: None,
+            buckets: Arc::default(),
+        }
+    }
+
+    /// Also allow up to `limit` sessions to be created per period for each
+    /// key set via [`with_create_rate_key`].
+    ///
+    /// Sessions created without a key are only subject to the overall limit.
+    /// The limits of up to 10,000 keys are tracked, beyond which those of the
+    /// least recently used keys are forgotten, leaving the overall limit to
+    /// bound them.
+    pub fn with_key_limit(mut self, limit: u32, per: Duration) -> Self {
+        self.key_rate = Some(Rate::new(limit, per));
+        self
+    }
+
+    /// Take a token from each bucket that applies, or none if any is empty,
+    /// in which case the limit reached is returned.
+    fn acquire(&self) -> Result<(), &'static str> {
+        let now = Instant::now();
+        let key = CREATE_RATE_KEY.try_with(Clone::clone).ok();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut global = buckets
+            .global
+            .unwrap_or_else(|| Bucket::full(self.global_rate, now));
+        if !global.has_token(self.global_rate, now) {
+            buckets.global = Some(global);
+            return Err("overall limit reached");
+        }
+
+        if let (Some(key_rate), Some(key)) = (self.key_rate, key) {
+            buckets.prune(key_rate, now);
+            let bucket = buckets.keyed_bucket(key, key_rate, now);
+            if !bucket.has_token(key_rate, now) {
+                buckets.global = Some(global);
+                return Err("limit for key reached");
+            }
+            bucket.take();
+        }
+
+        global.take();
+        buckets.global = Some(global);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: SessionStore> SessionStore for CreateRateLimitedStore<S> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        self.acquire().map_err(|limit| {
+            ErrorTag::RateLimited
+                .backend_error(format_args!("Session creation rate limited: {limit}"))
+        })?;
+        self.store.create(record).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.store.save(record).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        self.store.load(session_id).await
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.store.delete(session_id).await
+    }
+}
+
+#[async_trait]
+impl<S> ExpiredDeletion for CreateRateLimitedStore<S>
+where
+    S: ExpiredDeletion,
+{
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.store.delete_expired().await
+    }
+}
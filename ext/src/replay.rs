@@ -0,0 +1,338 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, SessionStore,
+};
+
+/// A session store operation captured in a [`Fixture`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Operation {
+    Create(Record),
+    Save(Record),
+    Load(Id),
+    Delete(Id),
+}
+
+impl Operation {
+    /// The ID the operation applies to; creates are assigned their ID by the
+    /// store, so the one passed in doesn't identify them.
+    fn session_id(&self) -> Option<Id> {
+        match self {
+            Operation::Create(_) => None,
+            Operation::Save(record) => Some(record.id),
+            Operation::Load(session_id) | Operation::Delete(session_id) => Some(*session_id),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Create(_) => "create",
+            Operation::Save(_) => "save",
+            Operation::Load(_) => "load",
+            Operation::Delete(_) => "delete",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.session_id() {
+            Some(session_id) => write!(f, "{} of session {session_id}", self.name()),
+            None => f.write_str(self.name()),
+        }
+    }
+}
+
+/// The result of a captured operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Created(Id),
+    Saved,
+    Loaded(Option<Record>),
+    Deleted,
+    Failed(RecordedError),
+}
+
+/// A captured store error, which can be replayed as the same kind of error.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordedError {
+    Encode(String),
+    Decode(String),
+    Backend(String),
+}
+
+impl From<&session_store::Error> for RecordedError {
+    fn from(err: &session_store::Error) -> Self {
+        match err {
+            session_store::Error::Encode(message) => RecordedError::Encode(message.clone()),
+            session_store::Error::Decode(message) => RecordedError::Decode(message.clone()),
+            session_store::Error::Backend(message) => RecordedError::Backend(message.clone()),
+        }
+    }
+}
+
+impl From<RecordedError> for session_store::Error {
+    fn from(err: RecordedError) -> Self {
+        match err {
+            RecordedError::Encode(message) => session_store::Error::Encode(message),
+            RecordedError::Decode(message) => session_store::Error::Decode(message),
+            RecordedError::Backend(message) => session_store::Error::Backend(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Interaction {
+    operation: Operation,
+    outcome: Outcome,
+}
+
+/// Session store operations and their results, captured by
+/// [`RecordingStore`] and replayed by [`ReplayStore`].
+///
+/// Fixtures are serialized as JSON, so that they can be checked in alongside
+/// the tests replaying them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+    interactions: Vec<Interaction>,
+}
+
+impl Fixture {
+    /// Serialize the fixture as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("fixtures serialize to JSON")
+    }
+
+    /// Deserialize a fixture from JSON produced by [`Fixture::to_json`].
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Returns the number of captured operations.
+    pub fn len(&self) -> usize {
+        self.interactions.len()
+    }
+
+    /// Returns `true` if no operations were captured.
+    pub fn is_empty(&self) -> bool {
+        self.interactions.is_empty()
+    }
+}
+
+/// A session store wrapper capturing every operation and its result in a
+/// [`Fixture`].
+///
+/// Run application code against a real store wrapped in this store once,
+/// save the fixture, and replay it with [`ReplayStore`] in tests that then
+/// need no backend and behave the same on every run, including the
+/// backend's errors.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::RecordingStore;
+///
+/// let session_store = RecordingStore::new(MemoryStore::default());
+///
+/// // Exercise the application, then save the fixture.
+/// let json = session_store.fixture().to_json();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordingStore<S: SessionStore> {
+    store: S,
+    fixture: Arc<Mutex<Fixture>>,
+}
+
+impl<S: SessionStore> RecordingStore<S> {
+    /// Create a new recording store wrapping the provided store.
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            fixture: Arc::default(),
+        }
+    }
+
+    /// Returns the operations captured so far, across all clones of the
+    /// store.
+    pub fn fixture(&self) -> Fixture {
+        self.lock().clone()
+    }
+
+    fn capture(&self, operation: Operation, outcome: Outcome) {
+        self.lock()
+            .interactions
+            .push(Interaction { operation, outcome });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Fixture> {
+        self.fixture.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn outcome<T>(res: &session_store::Result<T>, ok: impl FnOnce(&T) -> Outcome) -> Outcome {
+    match res {
+        Ok(value) => ok(value),
+        Err(err) => Outcome::Failed(err.into()),
+    }
+}
+
+#[async_trait]
+impl<S: SessionStore> SessionStore for RecordingStore<S> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let operation = Operation::Create(record.clone());
+        let res = self.store.create(record).await;
+        self.capture(operation, outcome(&res, |_| Outcome::Created(record.id)));
+        res
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let res = self.store.save(record).await;
+        self.capture(
+            Operation::Save(record.clone()),
+            outcome(&res, |_| Outcome::Saved),
+        );
+        res
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let res = self.store.load(session_id).await;
+        self.capture(
+            Operation::Load(*session_id),
+            outcome(&res, |loaded| Outcome::Loaded(loaded.clone())),
+        );
+        res
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let res = self.store.delete(session_id).await;
+        self.capture(
+            Operation::Delete(*session_id),
+            outcome(&res, |_| Outcome::Deleted),
+        );
+        res
+    }
+}
+
+/// A session store answering operations from a [`Fixture`] captured by
+/// [`RecordingStore`], without a backend.
+///
+/// Operations must arrive in the captured order and apply to the captured
+/// sessions; creates are given the captured ID, so that the operations
+/// following them match. Any other operation, or one past the end of the
+/// fixture, fails with a backend error describing the mismatch. Session data
+/// passed to creates and saves isn't compared.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_ext::{Fixture, ReplayStore};
+///
+/// let fixture = Fixture::from_json(r#"{ "interactions": [] }"#).unwrap();
+/// let session_store = ReplayStore::new(fixture);
+/// assert_eq!(session_store.remaining(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplayStore {
+    interactions: Arc<Mutex<VecDeque<Interaction>>>,
+}
+
+impl ReplayStore {
+    /// Create a new replay store answering from the provided fixture.
+    pub fn new(fixture: Fixture) -> Self {
+        Self {
+            interactions: Arc::new(Mutex::new(fixture.interactions.into())),
+        }
+    }
+
+    /// Returns the number of captured operations not yet replayed, e.g. to
+    /// assert that a test performed all of them.
+    pub fn remaining(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Take the next captured interaction if it matches the operation.
+    fn next(&self, operation: &Operation) -> session_store::Result<Outcome> {
+        let mut interactions = self.lock();
+        let Some(next) = interactions.front() else {
+            return Err(session_store::Error::Backend(format!(
+                "Replay fixture exhausted; unexpected {operation}"
+            )));
+        };
+
+        if next.operation.name() != operation.name()
+            || next.operation.session_id() != operation.session_id()
+        {
+            return Err(session_store::Error::Backend(format!(
+                "Replay mismatch: expected {}, got {operation}",
+                next.operation
+            )));
+        }
+
+        let next = interactions.pop_front().expect("checked above");
+        match next.outcome {
+            Outcome::Failed(err) => Err(err.into()),
+            outcome => Ok(outcome),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Interaction>> {
+        self.interactions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn unexpected_outcome(operation: &Operation, outcome: Outcome) -> session_store::Error {
+    session_store::Error::Backend(format!(
+        "Replay fixture is corrupt: {operation} resulted in {outcome:?}"
+    ))
+}
+
+#[async_trait]
+impl SessionStore for ReplayStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let operation = Operation::Create(record.clone());
+        match self.next(&operation)? {
+            Outcome::Created(session_id) => {
+                record.id = session_id;
+                Ok(())
+            }
+            outcome => Err(unexpected_outcome(&operation, outcome)),
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let operation = Operation::Save(record.clone());
+        match self.next(&operation)? {
+            Outcome::Saved => Ok(()),
+            outcome => Err(unexpected_outcome(&operation, outcome)),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let operation = Operation::Load(*session_id);
+        match self.next(&operation)? {
+            Outcome::Loaded(loaded) => Ok(loaded),
+            outcome => Err(unexpected_outcome(&operation, outcome)),
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let operation = Operation::Delete(*session_id);
+        match self.next(&operation)? {
+            Outcome::Deleted => Ok(()),
+            outcome => Err(unexpected_outcome(&operation, outcome)),
+        }
+    }
+}
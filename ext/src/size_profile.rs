@@ -0,0 +1,256 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+
+use crate::{ExportSessions, TouchSessionStore};
+
+/// The most distinct session data keys tracked, bounding memory use when
+/// applications generate keys dynamically.
+const MAX_TRACKED_KEYS: usize = 1024;
+
+/// A session store wrapper profiling the serialized size of written
+/// sessions.
+///
+/// A sample of creates and saves is serialized with MessagePack to record
+/// the size of the whole session and of the value under each key. The
+/// resulting [`SizeProfile`] shows how session sizes are distributed and
+/// which keys are the largest, helping to find the fields that bloat session
+/// storage. Use [`SizeProfilingStore::profile`] to inspect it, or
+/// [`SizeProfilingStore::log_profile`] to log a summary with
+/// `tracing::info!`, e.g. periodically.
+///
+/// Sizes are measured before the inner store encodes sessions, so they
+/// approximate rather than equal the stored sizes.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::SizeProfilingStore;
+///
+/// let session_store = SizeProfilingStore::new(MemoryStore::default()).with_sample_every(100);
+/// let profile = session_store.profile();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SizeProfilingStore<S: SessionStore> {
+    store: S,
+    sample_every: u64,
+    state: Arc<Mutex<ProfileState>>,
+}
+
+#[derive(Debug, Default)]
+struct ProfileState {
+    writes: u64,
+    samples: u64,
+    total_bytes: u64,
+    max_bytes: usize,
+    /// Counts of sampled sessions whose size is at most `2^i` bytes and more
+    /// than `2^(i - 1)` bytes.
+    buckets: Vec<u64>,
+    key_bytes: HashMap<String, usize>,
+}
+
+/// A snapshot of the sizes recorded by a [`SizeProfilingStore`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeProfile {
+    /// The number of sessions measured.
+    pub samples: u64,
+
+    /// The combined size of the sessions measured, in bytes.
+    pub total_bytes: u64,
+
+    /// The size of the largest session measured, in bytes.
+    pub max_bytes: usize,
+
+    /// The number of sessions measured by size, as pairs of an upper bound in
+    /// bytes and a count, in ascending order of size. Each count includes the
+    /// sessions larger than the previous bound.
+    pub histogram: Vec<(usize, u64)>,
+
+    /// The largest size measured for the value under each session data key,
+    /// in bytes, largest first.
+    pub largest_keys: Vec<(String, usize)>,
+}
+
+impl SizeProfile {
+    /// The upper bound of the histogram bucket holding the provided
+    /// percentile of sessions, or `None` if no sessions were measured.
+    pub fn percentile(&self, percentile: f64) -> Option<usize> {
+        let rank = (self.samples as f64 * percentile / 100.0).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        self.histogram.iter().find_map(|&(bound, count)| {
+            seen += count;
+            (seen >= rank).then_some(bound)
+        })
+    }
+}
+
+impl<S: SessionStore> SizeProfilingStore<S> {
+    /// Create a new size-profiling store wrapping the provided store,
+    /// measuring every write.
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            sample_every: 1,
+            state: Arc::new(Mutex::new(ProfileState::default())),
+        }
+    }
+
+    /// Measure only one in every `sample_every` creates and saves, limiting
+    /// the cost of serializing sessions a second time.
+    pub fn with_sample_every(mut self, sample_every: u64) -> Self {
+        self.sample_every = sample_every.max(1);
+        self
+    }
+
+    /// Take a snapshot of the sizes recorded so far, listing up to ten of the
+    /// largest keys.
+    pub fn profile(&self) -> SizeProfile {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+
+        let histogram = state
+            .buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(i, &count)| (1 << i, count))
+            .collect();
+
+        let mut largest_keys: Vec<(String, usize)> = state
+            .key_bytes
+            .iter()
+            .map(|(key, &bytes)| (key.clone(), bytes))
+            .collect();
+        largest_keys.sort_unstable_by(|(a_key, a), (b_key, b)| b.cmp(a).then(a_key.cmp(b_key)));
+        largest_keys.truncate(10);
+
+        SizeProfile {
+            samples: state.samples,
+            total_bytes: state.total_bytes,
+            max_bytes: state.max_bytes,
+            histogram,
+            largest_keys,
+        }
+    }
+
+    /// Log a summary of the sizes recorded so far with `tracing::info!`.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub fn log_profile(&self) {
+        let profile = self.profile();
+        if profile.samples == 0 {
+            return;
+        }
+
+        tracing::info!(
+            samples = profile.samples,
+            mean_bytes = profile.total_bytes / profile.samples,
+            p50_bytes = profile.percentile(50.0),
+            p95_bytes = profile.percentile(95.0),
+            p99_bytes = profile.percentile(99.0),
+            max_bytes = profile.max_bytes,
+            largest_keys = ?profile.largest_keys,
+            "session size profile"
+        );
+    }
+
+    fn sample(&self, record: &Record) {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+            state.writes += 1;
+            if !(state.writes - 1).is_multiple_of(self.sample_every) {
+                return;
+            }
+        }
+
+        // Serialize outside the lock so that concurrent writes aren't held up.
+        let Ok(record_bytes) = rmp_serde::to_vec(record).map(|bytes| bytes.len()) else {
+            return;
+        };
+        let key_bytes: Vec<(&String, usize)> = record
+            .data
+            .iter()
+            .filter_map(|(key, value)| Some((key, rmp_serde::to_vec(value).ok()?.len())))
+            .collect();
+
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.samples += 1;
+        state.total_bytes += record_bytes as u64;
+        state.max_bytes = state.max_bytes.max(record_bytes);
+        let bucket = record_bytes.next_power_of_two().trailing_zeros() as usize;
+        if state.buckets.len() <= bucket {
+            state.buckets.resize(bucket + 1, 0);
+        }
+        state.buckets[bucket] += 1;
+        for (key, bytes) in key_bytes {
+            if let Some(max_bytes) = state.key_bytes.get_mut(key) {
+                *max_bytes = (*max_bytes).max(bytes);
+            } else if state.key_bytes.len() < MAX_TRACKED_KEYS {
+                state.key_bytes.insert(key.clone(), bytes);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S: SessionStore> SessionStore for SizeProfilingStore<S> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        self.sample(record);
+        self.store.create(record).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.sample(record);
+        self.store.save(record).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        self.store.load(session_id).await
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.store.delete(session_id).await
+    }
+}
+
+#[async_trait]
+impl<S> ExpiredDeletion for SizeProfilingStore<S>
+where
+    S: ExpiredDeletion,
+{
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.store.delete_expired().await
+    }
+}
+
+#[async_trait]
+impl<S> TouchSessionStore for SizeProfilingStore<S>
+where
+    S: TouchSessionStore,
+{
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        self.store.touch(session_id, expiry_date).await
+    }
+}
+
+#[async_trait]
+impl<S> ExportSessions for SizeProfilingStore<S>
+where
+    S: ExportSessions,
+{
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        self.store.export(after, limit).await
+    }
+}
@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use tower_sessions_core::session::Record;
+
+/// Logs store operations that take longer than a threshold.
+///
+/// Stores embed this to implement their `with_slow_op_threshold` option.
+/// Operations exceeding the threshold are logged with `tracing::warn!`,
+/// including the backend, the operation, how long it took, and the size of
+/// the record involved, which helps to catch undersized connection pools and
+/// oversized sessions. Without the `tracing` feature, nothing is logged and
+/// the threshold is ignored.
+#[derive(Clone, Copy, Debug)]
+pub struct SlowOpTracer {
+    backend: &'static str,
+    threshold: Option<Duration>,
+}
+
+impl SlowOpTracer {
+    /// Create a disabled tracer for the named backend.
+    pub fn new(backend: &'static str) -> Self {
+        Self {
+            backend,
+            threshold: None,
+        }
+    }
+
+    /// Log operations taking longer than the provided threshold.
+    pub fn with_threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Mark the start of an operation.
+    ///
+    /// Returns `None` when no threshold is set, so disabled tracers never read
+    /// the clock.
+    pub fn start(&self) -> Option<Instant> {
+        if !cfg!(feature = "tracing") {
+            return None;
+        }
+        self.threshold.map(|_| Instant::now())
+    }
+
+    /// Mark the end of an operation started with [`SlowOpTracer::start`],
+    /// logging it if it exceeded the threshold.
+    ///
+    /// The record size is only computed when the operation is logged.
+    pub fn finish(
+        &self,
+        started: Option<Instant>,
+        operation: &'static str,
+        record: Option<&Record>,
+    ) {
+        let (Some(started), Some(threshold)) = (started, self.threshold) else {
+            return;
+        };
+
+        let elapsed = started.elapsed();
+        if elapsed > threshold {
+            let record_size = record
+                .and_then(|record| rmp_serde::to_vec(record).ok())
+                .map(|bytes| bytes.len());
+            warn!(
+                backend = self.backend,
+                operation,
+                elapsed_ms = elapsed.as_millis() as u64,
+                record_size,
+                "slow session store operation"
+            );
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use tower_sessions_core::{session_store::CachingSessionStore, SessionStore};
+
+use crate::{
+    AuditSink, AuditedStore, EncryptedStore, HashedIdStore, KeyProvider, TimeoutStore,
+    VersionedStore,
+};
+
+/// A builder for stacking store wrappers around a session store.
+///
+/// Each method wraps the stack built so far, so layers are added from the
+/// backend outwards. The recommended order is:
+///
+/// 1. [`hashed_ids`](StoreStack::hashed_ids), so that only the backend sees
+///    hashed IDs,
+/// 2. [`encrypted`](StoreStack::encrypted), so that only ciphertext reaches
+///    the backend,
+/// 3. [`cached`](StoreStack::cached), so that cache hits skip decryption,
+/// 4. [`versioned`](StoreStack::versioned), so that cached sessions are
+///    upgraded too,
+/// 5. [`audited`](StoreStack::audited), and
+/// 6. [`timeout`](StoreStack::timeout), so that deadlines cover every layer.
+///
+/// When caching with a store that is not held in memory, e.g. Redis, add the
+/// cache before encryption instead, so that it also stores only ciphertext.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::{StaticKeyProvider, StoreStack};
+///
+/// let session_store = StoreStack::new(MemoryStore::default())
+///     .hashed_ids(b"keep me secret".to_vec())
+///     .encrypted(StaticKeyProvider::new("2024-06", [7; 32]))
+///     .cached(MemoryStore::default())
+///     .versioned(1)
+///     .timeout(Duration::from_millis(200))
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct StoreStack<S: SessionStore> {
+    store: S,
+}
+
+impl<S: SessionStore> StoreStack<S> {
+    /// Start a stack with the provided backend store.
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    /// Serve loads from the provided cache, falling back to the stack.
+    pub fn cached<C: SessionStore>(self, cache: C) -> StoreStack<CachingSessionStore<C, S>> {
+        StoreStack::new(CachingSessionStore::new(cache, self.store))
+    }
+
+    /// Store sessions under a hash of their ID salted with the provided salt.
+    /// See [`HashedIdStore`].
+    pub fn hashed_ids(self, salt: impl Into<Vec<u8>>) -> StoreStack<HashedIdStore<S>> {
+        StoreStack::new(HashedIdStore::new(self.store, salt))
+    }
+
+    /// Encrypt session data with data keys wrapped by the provided key
+    /// provider. See [`EncryptedStore`].
+    pub fn encrypted<K: KeyProvider>(self, key_provider: K) -> StoreStack<EncryptedStore<S, K>> {
+        StoreStack::new(EncryptedStore::new(self.store, key_provider))
+    }
+
+    /// Record the provided schema version in each session. Upgrades can be
+    /// registered on the resulting store with
+    /// [`VersionedStore::with_upgrade`] via [`StoreStack::map`].
+    pub fn versioned(self, version: u32) -> StoreStack<VersionedStore<S>> {
+        StoreStack::new(VersionedStore::new(self.store, version))
+    }
+
+    /// Record every change to the provided audit sink. See [`AuditedStore`].
+    pub fn audited<A: AuditSink>(self, sink: A) -> StoreStack<AuditedStore<S, A>> {
+        StoreStack::new(AuditedStore::new(self.store, sink))
+    }
+
+    /// Enforce the provided deadline on each operation. See
+    /// [`TimeoutStore`].
+    pub fn timeout(self, timeout: Duration) -> StoreStack<TimeoutStore<S>> {
+        StoreStack::new(TimeoutStore::new(self.store, timeout))
+    }
+
+    /// Configure or wrap the top of the stack, e.g. to set options of the
+    /// last added layer or to add a wrapper without a dedicated method.
+    pub fn map<T: SessionStore>(self, f: impl FnOnce(S) -> T) -> StoreStack<T> {
+        StoreStack::new(f(self.store))
+    }
+
+    /// Return the assembled store.
+    pub fn build(self) -> S {
+        self.store
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt::{self, Display};
+
+use tower_sessions_core::session_store;
+
+/// Opens the tag every tagged message starts with, ahead of the tag's name,
+/// e.g. `[tower-sessions:transient]`.
+const TAG_PREFIX: &str = "[tower-sessions:";
+
+/// A kind of error reported by the stores and wrappers of this workspace,
+/// recognizable once it's converted into a `tower-sessions` error.
+///
+/// `session_store::Error` only carries messages, with no source error to
+/// downcast or kind to match on, so errors of these kinds are reported with a
+/// message starting with a tag naming the kind, which is namespaced so that it
+/// won't be confused with the messages of other stores. Each kind has a
+/// function recognizing it, such as [`is_transient`](crate::is_transient),
+/// while a store's own error type, where at hand, says more, e.g. with its
+/// `is_transient` method.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_ext::{is_transient, ErrorTag};
+///
+/// let err = ErrorTag::Transient.backend_error("connection reset");
+/// assert_eq!(err.to_string(), "[tower-sessions:transient] connection reset");
+/// assert!(is_transient(&err));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorTag {
+    /// The operation may succeed if retried; see
+    /// [`transient_backend_error`](crate::transient_backend_error).
+    Transient,
+
+    /// The operation didn't finish in time; see
+    /// [`TimeoutStore`](crate::TimeoutStore).
+    Timeout,
+
+    /// Too many sessions were created; see
+    /// [`CreateRateLimitedStore`](crate::CreateRateLimitedStore).
+    RateLimited,
+
+    /// A new session was refused because the store's cache is evicting live
+    /// sessions, as the Moka store can be set to do.
+    UnderPressure,
+
+    /// A session's key holds a value that isn't a session, as the Redis store
+    /// reports.
+    KeyCollision,
+
+    /// A session is larger than the store allows, as the MongoDB store can
+    /// be set to report.
+    SessionTooLarge,
+}
+
+impl ErrorTag {
+    fn name(self) -> &'static str {
+        match self {
+            ErrorTag::Transient => "transient",
+            ErrorTag::Timeout => "timeout",
+            ErrorTag::RateLimited => "rate-limited",
+            ErrorTag::UnderPressure => "under-pressure",
+            ErrorTag::KeyCollision => "key-collision",
+            ErrorTag::SessionTooLarge => "session-too-large",
+        }
+    }
+
+    /// Tag the provided message.
+    pub fn tag(self, message: impl Display) -> String {
+        format!("{self} {message}")
+    }
+
+    /// Create a backend error with the provided message, tagged.
+    pub fn backend_error(self, message: impl Display) -> session_store::Error {
+        session_store::Error::Backend(self.tag(message))
+    }
+
+    /// Create an encode error with the provided message, tagged.
+    pub fn encode_error(self, message: impl Display) -> session_store::Error {
+        session_store::Error::Encode(self.tag(message))
+    }
+
+    /// Returns `true` if the message of the error, whatever its variant,
+    /// starts with this tag.
+    pub fn is_tagged(self, err: &session_store::Error) -> bool {
+        let (session_store::Error::Encode(message)
+        | session_store::Error::Decode(message)
+        | session_store::Error::Backend(message)) = err;
+        message
+            .strip_prefix(TAG_PREFIX)
+            .and_then(|message| message.strip_prefix(self.name()))
+            .is_some_and(|message| message.starts_with(']'))
+    }
+}
+
+impl Display for ErrorTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{TAG_PREFIX}{}]", self.name())
+    }
+}
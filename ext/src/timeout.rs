@@ -0,0 +1,152 @@
+use std::{future::Future, time::Duration};
+
+use tokio::time::Instant;
+
+use async_trait::async_trait;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+
+use crate::{request_deadline, ErrorTag};
+
+/// Returns `true` if the error was produced by [`TimeoutStore`] because an
+/// operation exceeded its deadline.
+///
+/// `tower-sessions` errors carry no structured kind, so timeouts are reported
+/// as backend errors tagged with [`ErrorTag::Timeout`], which this function
+/// recognizes.
+pub fn is_timeout(err: &session_store::Error) -> bool {
+    ErrorTag::Timeout.is_tagged(err)
+}
+
+/// A session store wrapper enforcing a deadline on each operation.
+///
+/// An operation that does not complete in time is abandoned and fails with an
+/// error for which [`is_timeout`] returns `true`, so that a slow backend
+/// degrades into failed requests rather than piling up stalled ones.
+///
+/// A deadline set for the current request via
+/// [`with_request_deadline`](crate::with_request_deadline) is honored as well,
+/// whichever comes first.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::TimeoutStore;
+///
+/// let session_store = TimeoutStore::new(MemoryStore::default(), Duration::from_millis(200))
+///     .with_load_timeout(Duration::from_millis(50));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimeoutStore<S: SessionStore> {
+    store: S,
+    create_timeout: Duration,
+    save_timeout: Duration,
+    load_timeout: Duration,
+    delete_timeout: Duration,
+}
+
+impl<S: SessionStore> TimeoutStore<S> {
+    /// Create a new timeout store wrapping the provided store, with the same
+    /// deadline for every operation.
+    pub fn new(store: S, timeout: Duration) -> Self {
+        Self {
+            store,
+            create_timeout: timeout,
+            save_timeout: timeout,
+            load_timeout: timeout,
+            delete_timeout: timeout,
+        }
+    }
+
+    /// Set the deadline for `create`.
+    pub fn with_create_timeout(mut self, timeout: Duration) -> Self {
+        self.create_timeout = timeout;
+        self
+    }
+
+    /// Set the deadline for `save`.
+    pub fn with_save_timeout(mut self, timeout: Duration) -> Self {
+        self.save_timeout = timeout;
+        self
+    }
+
+    /// Set the deadline for `load`.
+    pub fn with_load_timeout(mut self, timeout: Duration) -> Self {
+        self.load_timeout = timeout;
+        self
+    }
+
+    /// Set the deadline for `delete`.
+    pub fn with_delete_timeout(mut self, timeout: Duration) -> Self {
+        self.delete_timeout = timeout;
+        self
+    }
+}
+
+async fn with_deadline<T>(
+    operation: &str,
+    timeout: Duration,
+    future: impl Future<Output = session_store::Result<T>>,
+) -> session_store::Result<T> {
+    let operation_deadline = Instant::now() + timeout;
+    match request_deadline() {
+        Some(deadline) if deadline < operation_deadline => {
+            if deadline <= Instant::now() {
+                return Err(ErrorTag::Timeout.backend_error(format_args!(
+                    "Session store operation timed out: `{operation}` started after the request \
+                     deadline"
+                )));
+            }
+
+            tokio::time::timeout_at(deadline, future)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(ErrorTag::Timeout.backend_error(format_args!(
+                        "Session store operation timed out: `{operation}` did not finish before \
+                         the request deadline"
+                    )))
+                })
+        }
+        _ => tokio::time::timeout_at(operation_deadline, future)
+            .await
+            .unwrap_or_else(|_| {
+                Err(ErrorTag::Timeout.backend_error(format_args!(
+                    "Session store operation timed out: `{operation}` took longer than {timeout:?}"
+                )))
+            }),
+    }
+}
+
+#[async_trait]
+impl<S: SessionStore> SessionStore for TimeoutStore<S> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        with_deadline("create", self.create_timeout, self.store.create(record)).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        with_deadline("save", self.save_timeout, self.store.save(record)).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        with_deadline("load", self.load_timeout, self.store.load(session_id)).await
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        with_deadline("delete", self.delete_timeout, self.store.delete(session_id)).await
+    }
+}
+
+#[async_trait]
+impl<S> ExpiredDeletion for TimeoutStore<S>
+where
+    S: ExpiredDeletion,
+{
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.store.delete_expired().await
+    }
+}
@@ -0,0 +1,28 @@
+use async_trait::async_trait;
+use time::OffsetDateTime;
+use tower_sessions_core::{session::Id, session_store, SessionStore};
+
+/// A session store that can extend a session's expiry without rewriting its
+/// data.
+///
+/// Sliding expiration updates the expiry date of a session on every request,
+/// even when its data has not changed. Stores implementing this trait can do
+/// so with a single cheap backend command instead of serializing and writing
+/// the whole record.
+///
+/// Note that the expiry date embedded in the serialized record is not
+/// updated. Stores keeping the expiry date in a separate field report the
+/// touched expiry date from `load`; Redis stores only do when set to read the
+/// expiry of keys on load.
+#[async_trait]
+pub trait TouchSessionStore: SessionStore {
+    /// Set the expiry date of the session with the given ID, leaving its data
+    /// untouched.
+    ///
+    /// Returns `false` if no such session exists.
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool>;
+}
@@ -0,0 +1,59 @@
+//! Logging macros forwarding to `tracing` with the `tracing` feature, and
+//! compiling to nothing without it.
+//!
+//! They're exported for the store crates, which import them by name. The
+//! feature is checked where a macro is used, so each crate logs according to
+//! its own `tracing` feature, which enables this crate's.
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        $crate::__tracing::warn!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        if false {
+            $crate::__discard!($($arg)+);
+        }
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        $crate::__tracing::debug!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        if false {
+            $crate::__discard!($($arg)+);
+        }
+    }};
+}
+
+/// Borrow the values of the fields and message arguments that would have
+/// been logged, so that they aren't reported as unused.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __discard {
+    () => {};
+    ($($field:ident).+ = % $value:expr $(, $($rest:tt)*)?) => {
+        let _ = &$value;
+        $($crate::__discard!($($rest)*);)?
+    };
+    ($($field:ident).+ = ? $value:expr $(, $($rest:tt)*)?) => {
+        let _ = &$value;
+        $($crate::__discard!($($rest)*);)?
+    };
+    ($($field:ident).+ = $value:expr $(, $($rest:tt)*)?) => {
+        let _ = &$value;
+        $($crate::__discard!($($rest)*);)?
+    };
+    ($field:ident $(, $($rest:tt)*)?) => {
+        let _ = &$field;
+        $($crate::__discard!($($rest)*);)?
+    };
+    ($message:literal $(, $arg:expr)* $(,)?) => {
+        $(let _ = &$arg;)*
+    };
+}
@@ -0,0 +1,28 @@
+use std::fmt::Display;
+
+use tower_sessions_core::session_store;
+
+use crate::ErrorTag;
+
+/// Create a backend error for a failure that may succeed if retried, such as
+/// a dropped connection or a deadlock.
+///
+/// Stores use this when mapping their own errors into `tower-sessions` errors,
+/// so that callers can check retriability with [`is_transient`]. The message
+/// is tagged with [`ErrorTag::Transient`].
+pub fn transient_backend_error(err: impl Display) -> session_store::Error {
+    ErrorTag::Transient.backend_error(err)
+}
+
+/// Returns `true` if the operation that produced the error may succeed if
+/// retried.
+///
+/// This covers errors created with [`transient_backend_error`] and timeouts
+/// reported by [`TimeoutStore`](crate::TimeoutStore), which are recognized by
+/// the [`ErrorTag`] of their message, as `session_store::Error` has no source
+/// error to inspect. Where a store's own error type is at hand, e.g. in code
+/// calling the store directly, its `is_transient` method classifies the
+/// underlying error itself.
+pub fn is_transient(err: &session_store::Error) -> bool {
+    ErrorTag::Timeout.is_tagged(err) || ErrorTag::Transient.is_tagged(err)
+}
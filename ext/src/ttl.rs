@@ -0,0 +1,108 @@
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use time::{Duration, OffsetDateTime};
+use tower_sessions_core::session::{Id, Record};
+
+type TtlHook = Arc<dyn Fn(&Id, OffsetDateTime) -> OffsetDateTime + Send + Sync>;
+
+/// Adjusts the expiry date a store persists for a session, independently of
+/// the expiry date the application set on the record.
+///
+/// Stores apply the policy whenever they write an expiry date: to the key
+/// expiry of Redis stores, the expiry column of SQL stores, and the TTL
+/// fields of MongoDB stores, as well as to the expiry date stored with the
+/// record, so that a session loads with the expiry date it was persisted
+/// with. A touch only updates the key expiry of Redis stores, which load
+/// sessions with that expiry, to the second, when set to read it on load.
+///
+/// The hook, if any, runs first, and its result is then clamped between the
+/// minimum and maximum TTLs, measured from the time of the write.
+///
+/// By default expiry dates are persisted as they are.
+///
+/// # Examples
+///
+/// ```rust
+/// use time::Duration;
+/// use tower_sessions_ext::TtlPolicy;
+///
+/// // Never keep a session for more than 30 days, whatever the application
+/// // asks for.
+/// let policy = TtlPolicy::default().with_max_ttl(Duration::days(30));
+/// ```
+#[derive(Clone, Default)]
+pub struct TtlPolicy {
+    min_ttl: Option<Duration>,
+    max_ttl: Option<Duration>,
+    hook: Option<TtlHook>,
+}
+
+impl TtlPolicy {
+    /// Persist sessions for at least the provided duration from each write.
+    pub fn with_min_ttl(mut self, min_ttl: Duration) -> Self {
+        self.min_ttl = Some(min_ttl);
+        self
+    }
+
+    /// Persist sessions for at most the provided duration from each write.
+    pub fn with_max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = Some(max_ttl);
+        self
+    }
+
+    /// Use the provided function to choose the expiry date to persist for a
+    /// session, given its ID and the expiry date set by the application.
+    pub fn with_hook(
+        mut self,
+        hook: impl Fn(&Id, OffsetDateTime) -> OffsetDateTime + Send + Sync + 'static,
+    ) -> Self {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Returns the expiry date to persist for the session with the provided
+    /// ID and expiry date.
+    pub fn expiry_date(&self, session_id: &Id, expiry_date: OffsetDateTime) -> OffsetDateTime {
+        let mut expiry_date = match &self.hook {
+            Some(hook) => hook(session_id, expiry_date),
+            None => expiry_date,
+        };
+
+        let now = OffsetDateTime::now_utc();
+        if let Some(min_ttl) = self.min_ttl {
+            expiry_date = expiry_date.max(now + min_ttl);
+        }
+        if let Some(max_ttl) = self.max_ttl {
+            expiry_date = expiry_date.min(now + max_ttl);
+        }
+        expiry_date
+    }
+
+    /// Returns the record with the expiry date to persist, only cloning it if
+    /// the policy changes its expiry date.
+    pub fn apply<'a>(&self, record: &'a Record) -> Cow<'a, Record> {
+        if self.min_ttl.is_none() && self.max_ttl.is_none() && self.hook.is_none() {
+            return Cow::Borrowed(record);
+        }
+
+        let expiry_date = self.expiry_date(&record.id, record.expiry_date);
+        if expiry_date == record.expiry_date {
+            Cow::Borrowed(record)
+        } else {
+            Cow::Owned(Record {
+                expiry_date,
+                ..record.clone()
+            })
+        }
+    }
+}
+
+impl fmt::Debug for TtlPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtlPolicy")
+            .field("min_ttl", &self.min_ttl)
+            .field("max_ttl", &self.max_ttl)
+            .field("hook", &self.hook.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
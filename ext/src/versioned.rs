@@ -0,0 +1,198 @@
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+
+use crate::{ExportSessions, TouchSessionStore};
+
+type Upgrade = dyn Fn(&mut Record) -> Result<(), String> + Send + Sync;
+
+/// A session store wrapper recording a schema version in each session and
+/// upgrading older sessions as they are loaded.
+///
+/// The version is kept in the session data under a reserved key, so it is
+/// persisted by any store. Sessions written before versioning was enabled have
+/// no key and are treated as version 0. On load, the upgrades registered for
+/// each version from the stored one up to the current one are applied in
+/// order, letting applications change the layout of their session data
+/// without invalidating every existing session. Upgraded sessions are written
+/// back with the current version the next time they are saved.
+///
+/// Loading a session with a version newer than the current one fails with a
+/// decode error, e.g. while an older deployment is still running during a
+/// rollout.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_ext::VersionedStore;
+///
+/// // Version 1 renamed the `uid` key to `user_id`.
+/// let session_store = VersionedStore::new(MemoryStore::default(), 1).with_upgrade(0, |record| {
+///     if let Some(user_id) = record.data.remove("uid") {
+///         record.data.insert("user_id".to_string(), user_id);
+///     }
+///     Ok(())
+/// });
+/// ```
+#[derive(Clone)]
+pub struct VersionedStore<S: SessionStore> {
+    store: S,
+    version: u32,
+    version_key: String,
+    upgrades: BTreeMap<u32, Arc<Upgrade>>,
+}
+
+impl<S: SessionStore> fmt::Debug for VersionedStore<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VersionedStore")
+            .field("store", &self.store)
+            .field("version", &self.version)
+            .field("version_key", &self.version_key)
+            .field("upgrades", &self.upgrades.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<S: SessionStore> VersionedStore<S> {
+    /// Create a new versioned store wrapping the provided store, writing
+    /// sessions with the provided current version.
+    pub fn new(store: S, version: u32) -> Self {
+        Self {
+            store,
+            version,
+            version_key: "__tower_sessions_version".to_string(),
+            upgrades: BTreeMap::new(),
+        }
+    }
+
+    /// Set the session data key under which the version is kept.
+    pub fn with_version_key(mut self, version_key: impl Into<String>) -> Self {
+        self.version_key = version_key.into();
+        self
+    }
+
+    /// Register an upgrade of sessions from `from_version` to the next
+    /// version.
+    ///
+    /// Versions without a registered upgrade are assumed to share the layout
+    /// of the next version. An error returned by the upgrade fails the load
+    /// with a decode error.
+    pub fn with_upgrade<F>(mut self, from_version: u32, upgrade: F) -> Self
+    where
+        F: Fn(&mut Record) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.upgrades.insert(from_version, Arc::new(upgrade));
+        self
+    }
+
+    fn stamp(&self, record: &Record) -> Record {
+        let mut record = record.clone();
+        record
+            .data
+            .insert(self.version_key.clone(), Value::from(self.version));
+        record
+    }
+
+    fn upgrade(&self, mut record: Record) -> session_store::Result<Record> {
+        let version = match record.data.remove(&self.version_key) {
+            None => 0,
+            Some(value) => value
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| {
+                    session_store::Error::Decode(format!(
+                        "Invalid session schema version `{value}`"
+                    ))
+                })?,
+        };
+
+        if version > self.version {
+            return Err(session_store::Error::Decode(format!(
+                "Session schema version {version} is newer than the supported version {}",
+                self.version
+            )));
+        }
+
+        for (from_version, upgrade) in self.upgrades.range(version..self.version) {
+            upgrade(&mut record).map_err(|err| {
+                session_store::Error::Decode(format!(
+                    "Failed to upgrade session from schema version {from_version}: {err}"
+                ))
+            })?;
+        }
+
+        Ok(record)
+    }
+}
+
+#[async_trait]
+impl<S: SessionStore> SessionStore for VersionedStore<S> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let mut stamped = self.stamp(record);
+        self.store.create(&mut stamped).await?;
+        record.id = stamped.id;
+        Ok(())
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.store.save(&self.stamp(record)).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        self.store
+            .load(session_id)
+            .await?
+            .map(|record| self.upgrade(record))
+            .transpose()
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.store.delete(session_id).await
+    }
+}
+
+#[async_trait]
+impl<S> ExpiredDeletion for VersionedStore<S>
+where
+    S: ExpiredDeletion,
+{
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.store.delete_expired().await
+    }
+}
+
+#[async_trait]
+impl<S> TouchSessionStore for VersionedStore<S>
+where
+    S: TouchSessionStore,
+{
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        self.store.touch(session_id, expiry_date).await
+    }
+}
+
+#[async_trait]
+impl<S> ExportSessions for VersionedStore<S>
+where
+    S: ExportSessions,
+{
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        self.store
+            .export(after, limit)
+            .await?
+            .into_iter()
+            .map(|record| self.upgrade(record))
+            .collect()
+    }
+}
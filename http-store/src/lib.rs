@@ -0,0 +1,245 @@
+use std::{error::Error as StdError, fmt::Debug};
+
+use async_trait::async_trait;
+use http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode};
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, SessionStore,
+};
+use tower_sessions_ext::{transient_backend_error, IdCollisionPolicy};
+
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+pub use self::server::session_api;
+
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+mod server;
+
+/// The content type of session records sent to and received from the API.
+pub const CONTENT_TYPE: &str = "application/msgpack";
+
+/// An error returned by an [`HttpClient`].
+pub type HttpClientError = Box<dyn StdError + Send + Sync>;
+
+/// A client sending requests to the session API.
+///
+/// The store leaves HTTP itself to the application, so that it can use
+/// whatever client its platform provides, such as `wasi:http` on WASI
+/// runtimes, the `fetch` binding of an edge platform, or `reqwest` elsewhere.
+#[async_trait]
+pub trait HttpClient: Debug + Send + Sync + 'static {
+    /// Send the request and return the response, with its body read in full.
+    ///
+    /// Responses with error statuses are returned rather than treated as
+    /// errors.
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, HttpClientError>;
+}
+
+/// An error type for the HTTP store.
+#[derive(thiserror::Error, Debug)]
+pub enum HttpStoreError {
+    /// A variant to map errors from the [`HttpClient`].
+    #[error(transparent)]
+    Client(HttpClientError),
+
+    /// A variant to map `http` errors building requests.
+    #[error(transparent)]
+    Http(#[from] http::Error),
+
+    /// A variant for responses with an unexpected status.
+    #[error("Unexpected response status: {0}")]
+    Status(StatusCode),
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error(transparent)]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error(transparent)]
+    Decode(#[from] rmp_serde::decode::Error),
+}
+
+impl HttpStoreError {
+    /// Returns `true` if the operation may succeed if retried, i.e. the
+    /// request timed out, was rate limited, or failed on the server.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HttpStoreError::Status(status) => {
+                status.is_server_error()
+                    || matches!(
+                        *status,
+                        StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+                    )
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<HttpStoreError> for session_store::Error {
+    fn from(err: HttpStoreError) -> Self {
+        match err {
+            HttpStoreError::Status(_) if err.is_transient() => transient_backend_error(err),
+            HttpStoreError::Client(inner) => session_store::Error::Backend(inner.to_string()),
+            HttpStoreError::Http(inner) => session_store::Error::Backend(inner.to_string()),
+            HttpStoreError::Status(_) => session_store::Error::Backend(err.to_string()),
+            HttpStoreError::Decode(inner) => session_store::Error::Decode(inner.to_string()),
+            HttpStoreError::Encode(inner) => session_store::Error::Encode(inner.to_string()),
+        }
+    }
+}
+
+/// A session store keeping sessions behind an HTTP API.
+///
+/// This store has no native dependencies and doesn't require an async
+/// runtime, so it can be used where database clients can't, such as
+/// `wasm32-wasip1` server runtimes and edge platforms. The API may be a small
+/// service in front of any storage, e.g. an edge key-value store.
+///
+/// Each session is a resource at the base URL followed by the session ID,
+/// with the record serialized with MessagePack as its body:
+///
+/// - `GET` returns `200 OK` with the record, or `404 Not Found` if there is no
+///   live session.
+/// - `PUT` stores the record, which expires at its expiry date, and returns
+///   any success status. When sent with `If-None-Match: *` to create a
+///   session, it returns `412 Precondition Failed` if the ID is already taken.
+/// - `DELETE` removes the session and returns any success status, or
+///   `404 Not Found`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use tower_sessions_http_store::HttpStore;
+///
+/// let session_store = HttpStore::new(client, "https://sessions.internal/v1/sessions")
+///     .with_header(
+///         http::header::AUTHORIZATION,
+///         http::HeaderValue::from_static("Bearer token"),
+///     );
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpStore<C: HttpClient> {
+    client: C,
+    base_url: String,
+    headers: HeaderMap,
+    id_collision_policy: IdCollisionPolicy,
+}
+
+impl<C: HttpClient> HttpStore<C> {
+    /// Create a new HTTP store with the provided client and base URL.
+    pub fn new(client: C, base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        if base_url.ends_with('/') {
+            base_url.pop();
+        }
+
+        Self {
+            client,
+            base_url,
+            headers: HeaderMap::new(),
+            id_collision_policy: IdCollisionPolicy::default(),
+        }
+    }
+
+    /// Send the provided header with every request, e.g. for authorization.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        session_id: &Id,
+        headers: &[(HeaderName, HeaderValue)],
+        body: Vec<u8>,
+    ) -> Result<Response<Vec<u8>>, HttpStoreError> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("{}/{session_id}", self.base_url));
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let request = request.body(body)?;
+
+        self.client
+            .send(request)
+            .await
+            .map_err(HttpStoreError::Client)
+    }
+
+    async fn put(&self, record: &Record, create: bool) -> Result<bool, HttpStoreError> {
+        let mut headers = vec![(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE))];
+        if create {
+            headers.push((header::IF_NONE_MATCH, HeaderValue::from_static("*")));
+        }
+
+        let body = rmp_serde::to_vec(record)?;
+        let response = self.send(Method::PUT, &record.id, &headers, body).await?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::PRECONDITION_FAILED if create => Ok(false),
+            status => Err(HttpStoreError::Status(status)),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: HttpClient> SessionStore for HttpStore<C> {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let mut collisions = 0;
+        while !self.put(record, true).await? {
+            collisions += 1;
+            record.id = self.id_collision_policy.next_id(collisions)?;
+        }
+
+        Ok(())
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.put(record, false).await?;
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let accept = [(header::ACCEPT, HeaderValue::from_static(CONTENT_TYPE))];
+        let response = self
+            .send(Method::GET, session_id, &accept, Vec::new())
+            .await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let record: Record =
+                    rmp_serde::from_slice(response.body()).map_err(HttpStoreError::Decode)?;
+                // Don't rely on the API to have expired the session already.
+                Ok((record.expiry_date > OffsetDateTime::now_utc()).then_some(record))
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            status => Err(HttpStoreError::Status(status).into()),
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let response = self
+            .send(Method::DELETE, session_id, &[], Vec::new())
+            .await?;
+
+        match response.status() {
+            status if status.is_success() || status == StatusCode::NOT_FOUND => Ok(()),
+            status => Err(HttpStoreError::Status(status).into()),
+        }
+    }
+}
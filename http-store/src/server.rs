@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, SessionStore,
+};
+use tower_sessions_ext::{is_transient, warn};
+
+use crate::CONTENT_TYPE;
+
+/// Serve the session API used by [`HttpStore`](crate::HttpStore) from the
+/// provided store.
+///
+/// This lets services written in any language share sessions through one
+/// service in front of a single store, e.g. a `PostgresStore`. Nest the
+/// router under the base URL given to the clients; it doesn't authenticate
+/// requests, so add a layer that does when the service is reachable by
+/// anything but trusted clients.
+///
+/// # Examples
+///
+/// ```rust
+/// use axum::Router;
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_http_store::session_api;
+///
+/// let app: Router = Router::new().nest("/v1/sessions", session_api(MemoryStore::default()));
+/// ```
+pub fn session_api<S: SessionStore + Clone>(store: S) -> Router {
+    Router::new()
+        .route("/{id}", get(load::<S>).put(put::<S>).delete(delete::<S>))
+        .with_state(store)
+}
+
+async fn load<S: SessionStore>(State(store): State<S>, Path(id): Path<String>) -> Response {
+    let Ok(id) = Id::from_str(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let record = match store.load(&id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => return error_response(err),
+    };
+
+    match rmp_serde::to_vec(&record) {
+        Ok(body) => ([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response(),
+        Err(err) => error_response(session_store::Error::Encode(err.to_string())),
+    }
+}
+
+async fn put<S: SessionStore>(
+    State(store): State<S>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Ok(mut record) = rmp_serde::from_slice::<Record>(&body) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if record.id.to_string() != id {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    if !headers.contains_key(header::IF_NONE_MATCH) {
+        return match store.save(&record).await {
+            Ok(()) => StatusCode::NO_CONTENT.into_response(),
+            Err(err) => error_response(err),
+        };
+    }
+
+    let id = record.id;
+    if let Err(err) = store.create(&mut record).await {
+        return error_response(err);
+    }
+    if record.id != id {
+        // The store replaced the taken ID, but the client picks IDs itself,
+        // so undo the create and report the collision instead.
+        if let Err(err) = store.delete(&record.id).await {
+            return error_response(err);
+        }
+        return StatusCode::PRECONDITION_FAILED.into_response();
+    }
+
+    StatusCode::CREATED.into_response()
+}
+
+async fn delete<S: SessionStore>(State(store): State<S>, Path(id): Path<String>) -> Response {
+    let Ok(id) = Id::from_str(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    match store.delete(&id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => error_response(err),
+    }
+}
+
+fn error_response(err: session_store::Error) -> Response {
+    warn!(error = %err, "session API request failed");
+    if is_transient(&err) {
+        StatusCode::SERVICE_UNAVAILABLE.into_response()
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store::{self, ExpiredDeletion},
+    SessionStore,
+};
+use tower_sessions_ext::IdCollisionPolicy;
+
+/// The kind of an entry that stores a session record.
+const PUT: u8 = 1;
+
+/// The kind of an entry that deletes a session.
+const DELETE: u8 = 2;
+
+/// The length of an entry's header: the payload length as a little-endian
+/// `u32`, followed by the entry kind.
+const HEADER_LEN: u64 = 5;
+
+/// An error type for the log store.
+#[derive(thiserror::Error, Debug)]
+pub enum LogStoreError {
+    /// A variant to map I/O errors.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error(transparent)]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error(transparent)]
+    Decode(#[from] rmp_serde::decode::Error),
+
+    /// A variant for log entries that can't be read.
+    #[error("Corrupt log entry at offset {0}")]
+    Corrupt(u64),
+}
+
+impl From<LogStoreError> for session_store::Error {
+    fn from(err: LogStoreError) -> Self {
+        match err {
+            LogStoreError::Io(inner) => session_store::Error::Backend(inner.to_string()),
+            LogStoreError::Decode(inner) => session_store::Error::Decode(inner.to_string()),
+            LogStoreError::Encode(inner) => session_store::Error::Encode(inner.to_string()),
+            LogStoreError::Corrupt(_) => session_store::Error::Backend(err.to_string()),
+        }
+    }
+}
+
+/// The location of the latest record of a session in the log.
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    /// The offset of the entry's payload.
+    offset: u64,
+    len: u32,
+    expiry_date: OffsetDateTime,
+}
+
+impl IndexEntry {
+    fn entry_len(&self) -> u64 {
+        HEADER_LEN + u64::from(self.len)
+    }
+}
+
+#[derive(Debug)]
+struct Log {
+    file: File,
+    len: u64,
+    index: HashMap<Id, IndexEntry>,
+    /// The number of bytes taken by entries that have been superseded.
+    garbage: u64,
+}
+
+impl Log {
+    fn append(&mut self, kind: u8, payload: &[u8], sync: bool) -> Result<u64, LogStoreError> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "session too large"))?;
+
+        let mut entry = Vec::with_capacity(HEADER_LEN as usize + payload.len());
+        entry.extend_from_slice(&len.to_le_bytes());
+        entry.push(kind);
+        entry.extend_from_slice(payload);
+        let written =
+            self.file.write_all(&entry).and_then(
+                |()| {
+                    if sync {
+                        self.file.sync_data()
+                    } else {
+                        Ok(())
+                    }
+                },
+            );
+        if let Err(err) = written {
+            // Drop whatever part of the entry was written, so that later
+            // entries aren't appended after it and lost on replay.
+            let _ = self.file.set_len(self.len);
+            return Err(err.into());
+        }
+
+        let offset = self.len + HEADER_LEN;
+        self.len += entry.len() as u64;
+        Ok(offset)
+    }
+
+    fn put(&mut self, record: &Record, sync: bool) -> Result<(), LogStoreError> {
+        let payload = rmp_serde::to_vec(record)?;
+        let offset = self.append(PUT, &payload, sync)?;
+        let entry = IndexEntry {
+            offset,
+            len: payload.len() as u32,
+            expiry_date: record.expiry_date,
+        };
+        if let Some(previous) = self.index.insert(record.id, entry) {
+            self.garbage += previous.entry_len();
+        }
+        Ok(())
+    }
+
+    fn read(&self, entry: &IndexEntry) -> Result<Vec<u8>, LogStoreError> {
+        read_payload(&self.file, entry)
+    }
+}
+
+/// An experimental session store keeping sessions in an append-only file.
+///
+/// Every create, save, and delete appends an entry to the log, and an
+/// in-memory index points to the latest record of each session, so writes
+/// cost a single sequential append. By default writes are left to the
+/// operating system to flush, trading the durability of the most recent
+/// writes on power loss for write rates well beyond those of a database that
+/// syncs every commit; see [`LogStore::with_sync_writes`].
+///
+/// Superseded and expired entries stay in the file until it is compacted by
+/// [`LogStore::compact`], which [`ExpiredDeletion::delete_expired`] calls, so
+/// compaction can run periodically via `continuously_delete_expired`.
+///
+/// The store runs its file I/O on Tokio's blocking thread pool and serializes
+/// operations on a lock, which suits a single node where the log is mostly
+/// served from the page cache. Records are read with positioned reads
+/// (`pread`) rather than through a memory map, so that compaction can replace
+/// the file and a failed write can truncate it without invalidating a
+/// mapping. Only one store may use a file at a time.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions_log_store::LogStore;
+///
+/// let session_store = LogStore::open("sessions.log").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogStore {
+    path: PathBuf,
+    log: Arc<Mutex<Log>>,
+    compaction: Arc<Mutex<()>>,
+    sync_writes: bool,
+    id_collision_policy: IdCollisionPolicy,
+}
+
+impl LogStore {
+    /// Open the log at the provided path, creating it if it doesn't exist.
+    ///
+    /// The index is rebuilt by reading the whole log. An incomplete entry at
+    /// the end, left by a crash during a write, is discarded.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LogStoreError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let (index, len, garbage) = replay(&contents)?;
+        if len < contents.len() as u64 {
+            file.set_len(len)?;
+        }
+
+        Ok(Self {
+            path,
+            log: Arc::new(Mutex::new(Log {
+                file,
+                len,
+                index,
+                garbage,
+            })),
+            compaction: Arc::default(),
+            sync_writes: false,
+            id_collision_policy: IdCollisionPolicy::default(),
+        })
+    }
+
+    /// Sync the log to disk after every write, so that acknowledged writes
+    /// survive power loss at the cost of write throughput.
+    pub fn with_sync_writes(mut self, sync_writes: bool) -> Self {
+        self.sync_writes = sync_writes;
+        self
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    /// Sync all writes so far to disk.
+    pub fn sync(&self) -> Result<(), LogStoreError> {
+        Ok(self.lock().file.sync_data()?)
+    }
+
+    /// Returns the share of the log, between 0 and 1, taken by superseded
+    /// entries that compaction would remove, not counting expired sessions.
+    pub fn garbage_ratio(&self) -> f64 {
+        let log = self.lock();
+        if log.len == 0 {
+            0.0
+        } else {
+            log.garbage as f64 / log.len as f64
+        }
+    }
+
+    /// Rewrite the log with only the latest record of each live session.
+    ///
+    /// The live records are copied to a compacted log next to the current one
+    /// while other operations go on. Only the entries written meanwhile are
+    /// copied under the lock, before the compacted log is renamed over the
+    /// current one. This blocks, so call it from a blocking context, as
+    /// [`ExpiredDeletion::delete_expired`] does.
+    pub fn compact(&self) -> Result<(), LogStoreError> {
+        let _compaction = self
+            .compaction
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let now = OffsetDateTime::now_utc();
+        let compacted_path = self.path.with_extension("compacting");
+
+        // Writes only ever append, so the entries up to the current length
+        // stay as they are and can be read through a handle of our own.
+        let (file, live, copied_len) = {
+            let log = self.lock();
+            let live: Vec<(Id, IndexEntry)> = log
+                .index
+                .iter()
+                .filter(|(_, entry)| entry.expiry_date > now)
+                .map(|(id, entry)| (*id, *entry))
+                .collect();
+            (log.file.try_clone()?, live, log.len)
+        };
+
+        let mut compacted = Log {
+            file: OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create(true)
+                .truncate(false)
+                .open(&compacted_path)?,
+            len: 0,
+            index: HashMap::with_capacity(live.len()),
+            garbage: 0,
+        };
+        compacted.file.set_len(0)?;
+        for (id, entry) in live {
+            let payload = read_payload(&file, &entry)?;
+            let offset = compacted.append(PUT, &payload, false)?;
+            compacted.index.insert(id, IndexEntry { offset, ..entry });
+        }
+
+        let mut log = self.lock();
+        let mut written_since = vec![0; (log.len - copied_len) as usize];
+        read_exact_at(&log.file, &mut written_since, copied_len)?;
+        compacted.file.write_all(&written_since)?;
+        let moved_by = compacted.len;
+        compacted.len += written_since.len() as u64;
+
+        // Sessions written since point into the copied entries, while the
+        // others keep the record copied, unless it had expired.
+        let index: HashMap<Id, IndexEntry> = log
+            .index
+            .iter()
+            .filter_map(|(id, entry)| {
+                let entry = if entry.offset >= copied_len {
+                    IndexEntry {
+                        offset: entry.offset - copied_len + moved_by,
+                        ..*entry
+                    }
+                } else {
+                    *compacted.index.get(id)?
+                };
+                Some((*id, entry))
+            })
+            .collect();
+        compacted.garbage = compacted.len - index.values().map(IndexEntry::entry_len).sum::<u64>();
+        compacted.index = index;
+
+        compacted.file.sync_all()?;
+        fs::rename(&compacted_path, &self.path)?;
+        sync_parent_dir(&self.path)?;
+
+        *log = compacted;
+        Ok(())
+    }
+
+    /// Run the provided function with the store on Tokio's blocking thread
+    /// pool.
+    async fn spawn_blocking<T, F>(&self, f: F) -> session_store::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Self) -> session_store::Result<T> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|err| session_store::Error::Backend(err.to_string()))?
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Rebuild the index from the contents of a log, returning it with the length
+/// of the valid entries and the number of bytes taken by superseded ones.
+fn replay(contents: &[u8]) -> Result<(HashMap<Id, IndexEntry>, u64, u64), LogStoreError> {
+    let mut index: HashMap<Id, IndexEntry> = HashMap::new();
+    let mut garbage = 0;
+    let mut pos = 0;
+
+    while let Some(header) = contents.get(pos..pos + HEADER_LEN as usize) {
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let start = pos + HEADER_LEN as usize;
+        let Some(payload) = contents.get(start..start + len as usize) else {
+            break;
+        };
+
+        match header[4] {
+            PUT => {
+                let record: Record = rmp_serde::from_slice(payload)?;
+                let entry = IndexEntry {
+                    offset: start as u64,
+                    len,
+                    expiry_date: record.expiry_date,
+                };
+                if let Some(previous) = index.insert(record.id, entry) {
+                    garbage += previous.entry_len();
+                }
+            }
+            DELETE => {
+                let id = std::str::from_utf8(payload)
+                    .ok()
+                    .and_then(|id| Id::from_str(id).ok())
+                    .ok_or(LogStoreError::Corrupt(pos as u64))?;
+                if let Some(previous) = index.remove(&id) {
+                    garbage += previous.entry_len();
+                }
+                garbage += HEADER_LEN + u64::from(len);
+            }
+            _ => return Err(LogStoreError::Corrupt(pos as u64)),
+        }
+
+        pos = start + len as usize;
+    }
+
+    Ok((index, pos as u64, garbage))
+}
+
+fn read_payload(file: &File, entry: &IndexEntry) -> Result<Vec<u8>, LogStoreError> {
+    let mut payload = vec![0; entry.len as usize];
+    read_exact_at(file, &mut payload, entry.offset)?;
+    Ok(payload)
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, buf, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sync the directory holding the file, so that renaming the file into place
+/// survives power loss.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+/// Directories can't be opened as files on Windows, where a rename is made
+/// durable with the file's metadata instead.
+#[cfg(windows)]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[async_trait]
+impl ExpiredDeletion for LogStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.spawn_blocking(|store| Ok(store.compact()?)).await
+    }
+}
+
+#[async_trait]
+impl SessionStore for LogStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let mut created = record.clone();
+        *record = self
+            .spawn_blocking(move |store| {
+                let mut log = store.lock();
+
+                let mut collisions = 0;
+                while log.index.contains_key(&created.id) {
+                    collisions += 1;
+                    created.id = store.id_collision_policy.next_id(collisions)?;
+                }
+                log.put(&created, store.sync_writes)?;
+
+                Ok(created)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let record = record.clone();
+        self.spawn_blocking(move |store| Ok(store.lock().put(&record, store.sync_writes)?))
+            .await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let session_id = *session_id;
+        self.spawn_blocking(move |store| {
+            let log = store.lock();
+            let Some(entry) = log.index.get(&session_id) else {
+                return Ok(None);
+            };
+            if entry.expiry_date <= OffsetDateTime::now_utc() {
+                return Ok(None);
+            }
+
+            let payload = log.read(entry)?;
+            let record = rmp_serde::from_slice(&payload).map_err(LogStoreError::Decode)?;
+            Ok(Some(record))
+        })
+        .await
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let session_id = *session_id;
+        self.spawn_blocking(move |store| {
+            let mut log = store.lock();
+            if !log.index.contains_key(&session_id) {
+                return Ok(());
+            }
+
+            let payload = session_id.to_string();
+            log.append(DELETE, payload.as_bytes(), store.sync_writes)?;
+            if let Some(previous) = log.index.remove(&session_id) {
+                log.garbage += previous.entry_len() + HEADER_LEN + payload.len() as u64;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+}
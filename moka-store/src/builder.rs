@@ -0,0 +1,222 @@
+use std::time::Duration as StdDuration;
+
+use moka::{future::Cache, notification::RemovalCause, policy::EvictionPolicy};
+use time::OffsetDateTime;
+use tower_sessions_core::session::Record;
+use tower_sessions_ext::{ExpiryEnforcement, IdCollisionPolicy};
+
+use crate::{
+    clock::Clock, pressure::EvictionPressure, user_index::UserIndex, MokaStore, SessionExpiry,
+};
+
+/// A builder for a [`MokaStore`] exposing more of Moka's cache configuration.
+///
+/// The default TinyLFU eviction policy only admits a new session into a full
+/// cache when it is estimated to be used more often than the session it would
+/// evict. This protects sessions in active use from bursts of one-hit
+/// sessions, such as those created by crawlers, at the cost of sometimes
+/// rejecting new sessions while the cache is full. The LRU policy instead
+/// always admits new sessions and evicts the least recently used ones.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_moka_store::{EvictionPolicy, MokaStore};
+///
+/// let session_store = MokaStore::builder()
+///     .with_max_capacity(10_000)
+///     .with_initial_capacity(1_000)
+///     .with_eviction_policy(EvictionPolicy::tiny_lfu())
+///     .build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MokaStoreBuilder {
+    max_capacity: Option<u64>,
+    initial_capacity: Option<usize>,
+    eviction_policy: EvictionPolicy,
+    eviction_pressure: Option<EvictionPressure>,
+    reject_creates_under_pressure: bool,
+    clock: Clock,
+    user_index: Option<UserIndex>,
+}
+
+impl MokaStoreBuilder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of sessions held by the cache.
+    ///
+    /// Defaults to unbounded.
+    pub fn with_max_capacity(mut self, max_capacity: u64) -> Self {
+        self.max_capacity = Some(max_capacity);
+        self
+    }
+
+    /// Set the number of sessions the cache allocates room for up front.
+    pub fn with_initial_capacity(mut self, initial_capacity: usize) -> Self {
+        self.initial_capacity = Some(initial_capacity);
+        self
+    }
+
+    /// Set the policy used to choose which sessions to evict once the cache
+    /// reaches its maximum capacity.
+    ///
+    /// Defaults to [`EvictionPolicy::tiny_lfu`].
+    pub fn with_eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.eviction_policy = eviction_policy;
+        self
+    }
+
+    /// Call the provided function when the cache evicts `threshold` sessions
+    /// that haven't expired within `window`, to make room for others.
+    ///
+    /// A full cache evicts live sessions, logging their users out, and with
+    /// the TinyLFU policy may drop new sessions as soon as they are created.
+    /// Neither is an error, so this is how to find out the cache is too small
+    /// for its load. The function is called once per window in which the
+    /// threshold is reached, from the task running the cache's maintenance,
+    /// so it should return quickly, e.g. by incrementing a metric.
+    /// [`MokaStore::is_under_pressure`] reports whether the threshold was
+    /// reached within the last window.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions_moka_store::MokaStore;
+    ///
+    /// let session_store = MokaStore::builder()
+    ///     .with_max_capacity(10_000)
+    ///     .with_eviction_pressure(100, Duration::from_secs(60), || {
+    ///         eprintln!("session cache is evicting live sessions");
+    ///     })
+    ///     .with_create_rejection_under_pressure()
+    ///     .build();
+    /// ```
+    pub fn with_eviction_pressure(
+        mut self,
+        threshold: u64,
+        window: StdDuration,
+        on_pressure: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        self.eviction_pressure = Some(EvictionPressure::new(threshold, window, on_pressure));
+        self
+    }
+
+    /// Reject new sessions while the store is under eviction pressure, with
+    /// an error recognized by [`is_under_pressure`](crate::is_under_pressure),
+    /// rather than creating sessions only to evict others or themselves.
+    ///
+    /// This has no effect without [`MokaStoreBuilder::with_eviction_pressure`].
+    pub fn with_create_rejection_under_pressure(mut self) -> Self {
+        self.reject_creates_under_pressure = true;
+        self
+    }
+
+    /// Set the wall clock that expiry dates are compared with.
+    ///
+    /// The cache converts each session's expiry date into a time to live when
+    /// the session is saved, and then expires it with a monotonic clock. With
+    /// a clock of its own, tests can move time forward instead of sleeping,
+    /// and long-running processes can correct for skew between the two
+    /// clocks. Sessions saved with a clock past their expiry date are expired
+    /// immediately; those saved earlier keep their time to live, unless
+    /// [`ExpiryEnforcement::Strict`] is set to also check them against the
+    /// clock on load. The clock is also used by
+    /// [`MokaStore::with_refresh_hint`].
+    ///
+    /// Defaults to [`OffsetDateTime::now_utc`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::{Duration, OffsetDateTime};
+    /// use tower_sessions_moka_store::MokaStore;
+    ///
+    /// // The system clock is known to run a few seconds behind.
+    /// let session_store = MokaStore::builder()
+    ///     .with_clock(|| OffsetDateTime::now_utc() + Duration::seconds(3))
+    ///     .build();
+    /// ```
+    pub fn with_clock(mut self, now: impl Fn() -> OffsetDateTime + Send + Sync + 'static) -> Self {
+        self.clock = Clock::new(now);
+        self
+    }
+
+    /// Index sessions by the user ID the provided function finds in them, so
+    /// that all sessions of a user can be found, e.g. to log out their other
+    /// devices.
+    ///
+    /// The index is held in memory next to the cache: it is updated whenever
+    /// a session is created, saved, or touched, and when the cache removes a
+    /// session, whether deleted, expired, or evicted. Sessions for which the
+    /// function returns `None`, such as those of anonymous visitors, aren't
+    /// indexed. See [`MokaStore::session_ids_for_user`] and
+    /// [`MokaStore::delete_sessions_for_user`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_moka_store::MokaStore;
+    ///
+    /// let session_store = MokaStore::builder()
+    ///     .with_max_capacity(10_000)
+    ///     .with_user_index(|record| {
+    ///         record
+    ///             .data
+    ///             .get("user_id")
+    ///             .and_then(|user_id| user_id.as_str())
+    ///             .map(str::to_string)
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn with_user_index(
+        mut self,
+        user_id: impl Fn(&Record) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.user_index = Some(UserIndex::new(user_id));
+        self
+    }
+
+    /// Build the store.
+    pub fn build(self) -> MokaStore {
+        let mut cache_builder = Cache::builder()
+            .eviction_policy(self.eviction_policy)
+            .expire_after(SessionExpiry {
+                clock: self.clock.clone(),
+            });
+        if let Some(max_capacity) = self.max_capacity {
+            cache_builder = cache_builder.max_capacity(max_capacity);
+        }
+        if let Some(initial_capacity) = self.initial_capacity {
+            cache_builder = cache_builder.initial_capacity(initial_capacity);
+        }
+        if self.eviction_pressure.is_some() || self.user_index.is_some() {
+            let eviction_pressure = self.eviction_pressure.clone();
+            let user_index = self.user_index.clone();
+            cache_builder = cache_builder.eviction_listener(move |session_id, _, cause| {
+                if let (Some(eviction_pressure), RemovalCause::Size) = (&eviction_pressure, cause) {
+                    eviction_pressure.record_eviction();
+                }
+                // Replaced sessions stay in the cache with their new record,
+                // which the store indexes itself.
+                if let (Some(user_index), false) = (&user_index, cause == RemovalCause::Replaced) {
+                    user_index.remove(&session_id);
+                }
+            });
+        }
+
+        MokaStore {
+            cache: cache_builder.build(),
+            id_collision_policy: IdCollisionPolicy::default(),
+            expiry_enforcement: ExpiryEnforcement::BackendOnly,
+            refresh_hint: None,
+            eviction_pressure: self.eviction_pressure,
+            reject_creates_under_pressure: self.reject_creates_under_pressure,
+            clock: self.clock,
+            user_index: self.user_index,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::{fmt, sync::Arc};
+
+use time::OffsetDateTime;
+
+type NowFn = Arc<dyn Fn() -> OffsetDateTime + Send + Sync>;
+
+/// The wall clock the store compares expiry dates with; see
+/// [`MokaStoreBuilder::with_clock`](crate::MokaStoreBuilder::with_clock).
+#[derive(Clone)]
+pub(crate) struct Clock(NowFn);
+
+impl Clock {
+    pub(crate) fn new(now: impl Fn() -> OffsetDateTime + Send + Sync + 'static) -> Self {
+        Self(Arc::new(now))
+    }
+
+    pub(crate) fn now(&self) -> OffsetDateTime {
+        (self.0)()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(OffsetDateTime::now_utc)
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock").finish_non_exhaustive()
+    }
+}
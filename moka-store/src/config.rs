@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use tower_sessions_ext::StoreOptionsConfig;
+
+use crate::MokaStore;
+
+/// The settings of a [`MokaStore`], as read from a configuration file.
+///
+/// The options shared by every store are written next to these settings;
+/// those that don't apply to an in-memory cache, such as the `namespace`, are
+/// ignored. Settings that are left out keep the store's defaults.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_moka_store::MokaStoreConfig;
+///
+/// let config: MokaStoreConfig =
+///     serde_json::from_str(r#"{ "max_capacity": 2000, "expiry_enforcement": "strict" }"#)
+///         .unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MokaStoreConfig {
+    /// The options shared by every store.
+    #[serde(flatten)]
+    pub options: StoreOptionsConfig,
+
+    /// Passed to [`MokaStoreBuilder::with_max_capacity`](crate::MokaStoreBuilder::with_max_capacity).
+    pub max_capacity: Option<u64>,
+
+    /// Passed to [`MokaStoreBuilder::with_initial_capacity`](crate::MokaStoreBuilder::with_initial_capacity).
+    pub initial_capacity: Option<usize>,
+}
+
+impl MokaStore {
+    /// Create a new Moka store configured with the provided settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_moka_store::{MokaStore, MokaStoreConfig};
+    ///
+    /// let config: MokaStoreConfig = serde_json::from_str(r#"{ "max_capacity": 2000 }"#).unwrap();
+    /// let session_store = MokaStore::try_from_config(&config).unwrap();
+    /// ```
+    pub fn try_from_config(config: &MokaStoreConfig) -> Result<Self, String> {
+        let mut builder = Self::builder();
+        if let Some(max_capacity) = config.max_capacity {
+            builder = builder.with_max_capacity(max_capacity);
+        }
+        if let Some(initial_capacity) = config.initial_capacity {
+            builder = builder.with_initial_capacity(initial_capacity);
+        }
+        config.options.apply(builder.build())
+    }
+}
@@ -1,12 +1,31 @@
 use std::time::{Duration as StdDuration, Instant as StdInstant};
 
 use async_trait::async_trait;
-use moka::{future::Cache, Expiry};
+use moka::{
+    future::Cache,
+    ops::compute::{CompResult, Op},
+    Expiry,
+};
 use time::OffsetDateTime;
 use tower_sessions_core::{
     session::{Id, Record},
     session_store, SessionStore,
 };
+use tower_sessions_ext::{
+    EraseUserSessions, ErasureReport, ExpiryEnforcement, ExportSessions, IdCollisionPolicy,
+    StoreOptions, TouchSessionStore,
+};
+
+pub use self::{builder::MokaStoreBuilder, config::MokaStoreConfig, pressure::is_under_pressure};
+use self::{clock::Clock, pressure::EvictionPressure, refresh::RefreshHint, user_index::UserIndex};
+pub use moka::policy::EvictionPolicy;
+
+mod builder;
+mod clock;
+mod config;
+mod pressure;
+mod refresh;
+mod user_index;
 
 /// A session store that uses Moka, a fast and concurrent caching library.
 ///
@@ -16,6 +35,13 @@ use tower_sessions_core::{
 #[derive(Debug, Clone)]
 pub struct MokaStore {
     cache: Cache<Id, Record>,
+    id_collision_policy: IdCollisionPolicy,
+    expiry_enforcement: ExpiryEnforcement,
+    refresh_hint: Option<RefreshHint>,
+    eviction_pressure: Option<EvictionPressure>,
+    reject_creates_under_pressure: bool,
+    clock: Clock,
+    user_index: Option<UserIndex>,
 }
 
 impl MokaStore {
@@ -29,49 +55,309 @@ impl MokaStore {
     /// let session_store = MokaStore::new(Some(2_000));
     /// ```
     pub fn new(max_capacity: Option<u64>) -> Self {
-        // it would be useful to expose more of the CacheBuilder options to the user,
-        // but for now this is the most important one
-        let cache_builder = match max_capacity {
-            Some(capacity) => Cache::builder().max_capacity(capacity),
-            None => Cache::builder(),
+        let builder = Self::builder();
+        match max_capacity {
+            Some(capacity) => builder.with_max_capacity(capacity),
+            None => builder,
         }
-        .expire_after(SessionExpiry);
+        .build()
+    }
+
+    /// Create a builder for configuring the underlying cache.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_moka_store::{EvictionPolicy, MokaStore};
+    ///
+    /// let session_store = MokaStore::builder()
+    ///     .with_max_capacity(2_000)
+    ///     .with_eviction_policy(EvictionPolicy::lru())
+    ///     .build();
+    /// ```
+    pub fn builder() -> MokaStoreBuilder {
+        MokaStoreBuilder::new()
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
 
-        Self {
-            cache: cache_builder.build(),
+    /// Set whether `load` checks the expiry date of sessions in addition to
+    /// the cache's own expiration, which is tracked with a monotonic clock.
+    ///
+    /// Defaults to [`ExpiryEnforcement::BackendOnly`].
+    pub fn with_expiry_enforcement(mut self, expiry_enforcement: ExpiryEnforcement) -> Self {
+        self.expiry_enforcement = expiry_enforcement;
+        self
+    }
+
+    /// Call the provided function with sessions that are loaded within the
+    /// provided window of their expiry date.
+    ///
+    /// This lets an application renew a session, e.g. in the backing store of
+    /// a cache, before the user is logged out in the middle of an action. To
+    /// spread out renewals of sessions expiring together, each session's
+    /// window is between half and all of the provided window, chosen from its
+    /// ID. The function is called on every load within the window, from the
+    /// task loading the session, so it should hand the work off rather than
+    /// block, e.g. by sending the session ID on a channel.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tokio::sync::mpsc;
+    /// use tower_sessions_moka_store::MokaStore;
+    ///
+    /// let (refresh_tx, refresh_rx) = mpsc::channel(1_024);
+    /// let session_store =
+    ///     MokaStore::new(Some(2_000)).with_refresh_hint(Duration::from_secs(300), move |record| {
+    ///         let _ = refresh_tx.try_send(record.id);
+    ///     });
+    /// ```
+    pub fn with_refresh_hint(
+        mut self,
+        window: StdDuration,
+        hint: impl Fn(&Record) + Send + Sync + 'static,
+    ) -> Self {
+        self.refresh_hint = Some(RefreshHint::new(window, hint));
+        self
+    }
+
+    /// Returns `true` if the cache evicted as many live sessions as set with
+    /// [`MokaStoreBuilder::with_eviction_pressure`] within the last window.
+    pub fn is_under_pressure(&self) -> bool {
+        self.eviction_pressure
+            .as_ref()
+            .is_some_and(EvictionPressure::is_under_pressure)
+    }
+
+    /// Remove all sessions from the cache.
+    pub fn clear(&self) {
+        self.cache.invalidate_all();
+        if let Some(user_index) = &self.user_index {
+            user_index.clear();
         }
     }
+
+    /// Returns the IDs of the live sessions of the provided user, as indexed
+    /// with [`MokaStoreBuilder::with_user_index`], in no particular order.
+    ///
+    /// Without a user index, no sessions are returned.
+    pub async fn session_ids_for_user(&self, user_id: &str) -> Vec<Id> {
+        let Some(user_index) = &self.user_index else {
+            return Vec::new();
+        };
+
+        // The index is checked against the cache, as sessions may have expired
+        // or changed users since they were indexed.
+        let now = self.clock.now();
+        let mut session_ids = Vec::new();
+        for session_id in user_index.session_ids(user_id) {
+            let record = self
+                .expiry_enforcement
+                .apply_at(self.cache.get(&session_id).await, now);
+            if record.is_some_and(|record| user_index.user_of(&record).as_deref() == Some(user_id))
+            {
+                session_ids.push(session_id);
+            }
+        }
+        session_ids
+    }
+
+    /// Delete the sessions of the provided user, except for the provided
+    /// session, returning the number of sessions deleted.
+    ///
+    /// Passing the current session logs the user out of their other devices.
+    /// Without a user index, no sessions are deleted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::session::Id;
+    /// use tower_sessions_moka_store::MokaStore;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session_store = MokaStore::builder()
+    ///     .with_user_index(|record| {
+    ///         record
+    ///             .data
+    ///             .get("user_id")
+    ///             .and_then(|user_id| user_id.as_str())
+    ///             .map(str::to_string)
+    ///     })
+    ///     .build();
+    /// let current_session_id = Id::default();
+    /// session_store
+    ///     .delete_sessions_for_user("alice", Some(&current_session_id))
+    ///     .await;
+    /// # })
+    /// ```
+    pub async fn delete_sessions_for_user(&self, user_id: &str, except: Option<&Id>) -> usize {
+        let mut deleted = 0;
+        for session_id in self.session_ids_for_user(user_id).await {
+            if Some(&session_id) != except {
+                self.remove(&session_id).await;
+                deleted += 1;
+            }
+        }
+        deleted
+    }
+
+    async fn insert(&self, record: &Record) {
+        // The cache notifies its listener of the record it replaces before
+        // returning, so the new record is indexed afterwards.
+        self.cache.insert(record.id, record.clone()).await;
+        if let Some(user_index) = &self.user_index {
+            user_index.insert(record);
+        }
+    }
+
+    async fn remove(&self, session_id: &Id) {
+        self.cache.invalidate(session_id).await;
+        if let Some(user_index) = &self.user_index {
+            user_index.remove(session_id);
+        }
+    }
+}
+
+impl StoreOptions for MokaStore {
+    fn with_id_collision_policy(self, id_collision_policy: IdCollisionPolicy) -> Self {
+        Self::with_id_collision_policy(self, id_collision_policy)
+    }
+
+    fn with_expiry_enforcement(self, expiry_enforcement: ExpiryEnforcement) -> Self {
+        Self::with_expiry_enforcement(self, expiry_enforcement)
+    }
 }
 
 #[async_trait]
 impl SessionStore for MokaStore {
     async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        if self.reject_creates_under_pressure && self.is_under_pressure() {
+            return Err(pressure::under_pressure_error());
+        }
+
+        let mut collisions = 0;
         while self.cache.contains_key(&record.id) {
-            record.id = Id::default();
+            collisions += 1;
+            record.id = self.id_collision_policy.next_id(collisions)?;
         }
-        self.cache.insert(record.id, record.clone()).await;
+        self.insert(record).await;
         Ok(())
     }
 
     async fn save(&self, record: &Record) -> session_store::Result<()> {
-        self.cache.insert(record.id, record.clone()).await;
+        self.insert(record).await;
         Ok(())
     }
 
     async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
         // expired sessions are automatically removed from the cache,
         // so it's safe to just call get
-        Ok(self.cache.get(session_id).await)
+        let now = self.clock.now();
+        let record = self
+            .expiry_enforcement
+            .apply_at(self.cache.get(session_id).await, now);
+        if let (Some(refresh_hint), Some(record)) = (&self.refresh_hint, &record) {
+            refresh_hint.check(record, now);
+        }
+        Ok(record)
     }
 
     async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
-        self.cache.invalidate(session_id).await;
+        self.remove(session_id).await;
         Ok(())
     }
 }
 
+#[async_trait]
+impl ExportSessions for MokaStore {
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        // The cache has no ordering of its own, so each page sorts a snapshot of
+        // the remaining entries.
+        let after = after.map(|id| id.to_string());
+        let mut records: Vec<(String, Record)> = self
+            .cache
+            .iter()
+            .map(|(id, record)| (id.to_string(), record))
+            .filter(|(id, _)| after.as_ref().is_none_or(|after| id > after))
+            .collect();
+        records.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        Ok(records
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, record)| record)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl TouchSessionStore for MokaStore {
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        // Only the expiry date is updated, atomically, so that a record saved
+        // concurrently isn't overwritten with the one read here. The user
+        // index is left as is, as the record's data doesn't change.
+        let result = self
+            .cache
+            .entry(*session_id)
+            .and_compute_with(|entry| {
+                let op = match entry {
+                    Some(entry) => {
+                        let mut record = entry.into_value();
+                        record.expiry_date = expiry_date;
+                        Op::Put(record)
+                    }
+                    None => Op::Nop,
+                };
+                std::future::ready(op)
+            })
+            .await;
+        Ok(matches!(result, CompResult::ReplacedWith(_)))
+    }
+}
+
+#[async_trait]
+impl EraseUserSessions for MokaStore {
+    /// Delete the sessions of the user as indexed with
+    /// [`MokaStoreBuilder::with_user_index`], which fails without a user
+    /// index.
+    ///
+    /// The index maps sessions to strings, so a JSON string is matched by its
+    /// contents and any other value by its JSON encoding.
+    async fn erase_user(
+        &self,
+        user_id: &serde_json::Value,
+        key: &[u8],
+    ) -> session_store::Result<ErasureReport> {
+        if self.user_index.is_none() {
+            return Err(session_store::Error::Backend(
+                "no user index configured; see `MokaStoreBuilder::with_user_index`".to_string(),
+            ));
+        }
+
+        let user = user_id
+            .as_str()
+            .map_or_else(|| user_id.to_string(), str::to_string);
+        let erased = self.delete_sessions_for_user(&user, None).await;
+        Ok(ErasureReport::new(user_id, erased as u64, key))
+    }
+}
+
 /// Moka per-entry expiration policy for session records.
-struct SessionExpiry;
+struct SessionExpiry {
+    clock: Clock,
+}
 
 impl SessionExpiry {
     /// Calculates the expiry duration of a record
@@ -79,11 +365,11 @@ impl SessionExpiry {
     ///
     /// If the expiry date of the record is in the past,
     /// returns an empty duration.
-    fn expiry_date_to_duration(record: &Record) -> StdDuration {
+    fn expiry_date_to_duration(&self, record: &Record) -> StdDuration {
         // we use this to calculate the current time
         // because it is not possible to convert
         // StdInstant to OffsetDateTime
-        let now = OffsetDateTime::now_utc();
+        let now = self.clock.now();
         let expiry_date = record.expiry_date;
 
         if expiry_date > now {
@@ -101,7 +387,7 @@ impl Expiry<Id, Record> for SessionExpiry {
         record: &Record,
         _created_at: StdInstant,
     ) -> Option<StdDuration> {
-        Some(Self::expiry_date_to_duration(record))
+        Some(self.expiry_date_to_duration(record))
     }
 
     fn expire_after_update(
@@ -112,6 +398,6 @@ impl Expiry<Id, Record> for SessionExpiry {
         _duration_until_expiry: Option<StdDuration>,
     ) -> Option<StdDuration> {
         // expiry_date could change, so we calculate it again
-        Some(Self::expiry_date_to_duration(record))
+        Some(self.expiry_date_to_duration(record))
     }
 }
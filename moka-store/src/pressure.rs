@@ -0,0 +1,106 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration as StdDuration, Instant as StdInstant},
+};
+
+use tower_sessions_core::session_store;
+use tower_sessions_ext::ErrorTag;
+
+type PressureCallback = Arc<dyn Fn() + Send + Sync>;
+
+/// Returns `true` if the error was produced by a [`MokaStore`] rejecting a new
+/// session because its cache is evicting live sessions; see
+/// [`MokaStoreBuilder::with_create_rejection_under_pressure`].
+///
+/// Such errors are reported as backend errors tagged with
+/// [`ErrorTag::UnderPressure`], which this function recognizes.
+///
+/// [`MokaStore`]: crate::MokaStore
+/// [`MokaStoreBuilder::with_create_rejection_under_pressure`]: crate::MokaStoreBuilder::with_create_rejection_under_pressure
+pub fn is_under_pressure(err: &session_store::Error) -> bool {
+    ErrorTag::UnderPressure.is_tagged(err)
+}
+
+pub(crate) fn under_pressure_error() -> session_store::Error {
+    ErrorTag::UnderPressure.backend_error("Session cache under eviction pressure")
+}
+
+#[derive(Debug)]
+struct PressureWindow {
+    started: StdInstant,
+    evictions: u64,
+    pressured_until: Option<StdInstant>,
+}
+
+/// Tracks how many live sessions the cache evicts for lack of room; see
+/// [`MokaStoreBuilder::with_eviction_pressure`](crate::MokaStoreBuilder::with_eviction_pressure).
+#[derive(Clone)]
+pub(crate) struct EvictionPressure {
+    threshold: u64,
+    window: StdDuration,
+    callback: PressureCallback,
+    state: Arc<Mutex<PressureWindow>>,
+}
+
+impl EvictionPressure {
+    pub(crate) fn new(
+        threshold: u64,
+        window: StdDuration,
+        callback: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            window,
+            callback: Arc::new(callback),
+            state: Arc::new(Mutex::new(PressureWindow {
+                started: StdInstant::now(),
+                evictions: 0,
+                pressured_until: None,
+            })),
+        }
+    }
+
+    /// Count an eviction, calling the callback when the evictions within the
+    /// current window reach the threshold.
+    pub(crate) fn record_eviction(&self) {
+        let now = StdInstant::now();
+        let reached = {
+            let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+            if now.duration_since(state.started) >= self.window {
+                state.started = now;
+                state.evictions = 0;
+            }
+            state.evictions += 1;
+
+            let reached = state.evictions == self.threshold;
+            if state.evictions >= self.threshold {
+                state.pressured_until = Some(now + self.window);
+            }
+            reached
+        };
+
+        // Called without holding the lock, in case the callback inspects the
+        // store.
+        if reached {
+            (self.callback)();
+        }
+    }
+
+    /// Returns `true` if the threshold was reached within the last window.
+    pub(crate) fn is_under_pressure(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state
+            .pressured_until
+            .is_some_and(|until| StdInstant::now() < until)
+    }
+}
+
+impl fmt::Debug for EvictionPressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EvictionPressure")
+            .field("threshold", &self.threshold)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,52 @@
+use std::{fmt, sync::Arc, time::Duration as StdDuration};
+
+use time::{Duration, OffsetDateTime};
+use tower_sessions_core::session::{Id, Record};
+
+type RefreshCallback = Arc<dyn Fn(&Record) + Send + Sync>;
+
+/// Hints that loaded sessions are about to expire; see
+/// [`MokaStore::with_refresh_hint`](crate::MokaStore::with_refresh_hint).
+#[derive(Clone)]
+pub(crate) struct RefreshHint {
+    window: Duration,
+    callback: RefreshCallback,
+}
+
+impl RefreshHint {
+    pub(crate) fn new(
+        window: StdDuration,
+        callback: impl Fn(&Record) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            window: Duration::try_from(window).unwrap_or(Duration::MAX),
+            callback: Arc::new(callback),
+        }
+    }
+
+    /// Call the callback if the record expires within its window of `now`.
+    pub(crate) fn check(&self, record: &Record, now: OffsetDateTime) {
+        let remaining = record.expiry_date - now;
+        if remaining.is_positive() && remaining <= self.window_for(&record.id) {
+            (self.callback)(record);
+        }
+    }
+
+    /// The window of the session, between half and all of the configured
+    /// window.
+    ///
+    /// Deriving it from the ID spreads out the hints for sessions expiring
+    /// together, while keeping each session's window the same across loads.
+    fn window_for(&self, session_id: &Id) -> Duration {
+        let jitter = (session_id.0 as u64) as f64 / u64::MAX as f64;
+        self.window * (1.0 - jitter / 2.0)
+    }
+}
+
+impl fmt::Debug for RefreshHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshHint")
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
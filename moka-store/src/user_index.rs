@@ -0,0 +1,99 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use tower_sessions_core::session::{Id, Record};
+
+type UserIdFn = Arc<dyn Fn(&Record) -> Option<String> + Send + Sync>;
+
+#[derive(Debug, Default)]
+struct Sessions {
+    by_user: HashMap<String, HashSet<Id>>,
+    owners: HashMap<Id, String>,
+}
+
+impl Sessions {
+    fn remove(&mut self, session_id: &Id) {
+        let Some(user_id) = self.owners.remove(session_id) else {
+            return;
+        };
+        if let Some(session_ids) = self.by_user.get_mut(&user_id) {
+            session_ids.remove(session_id);
+            if session_ids.is_empty() {
+                self.by_user.remove(&user_id);
+            }
+        }
+    }
+}
+
+/// Maps user IDs to the IDs of their cached sessions; see
+/// [`MokaStoreBuilder::with_user_index`](crate::MokaStoreBuilder::with_user_index).
+#[derive(Clone)]
+pub(crate) struct UserIndex {
+    user_id: UserIdFn,
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+impl UserIndex {
+    pub(crate) fn new(user_id: impl Fn(&Record) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            user_id: Arc::new(user_id),
+            sessions: Arc::default(),
+        }
+    }
+
+    /// The user the record belongs to, if any.
+    pub(crate) fn user_of(&self, record: &Record) -> Option<String> {
+        (self.user_id)(record)
+    }
+
+    /// Index the record under its user, moving it from its previous user if
+    /// that changed, e.g. on logout.
+    pub(crate) fn insert(&self, record: &Record) {
+        let user_id = self.user_of(record);
+        let mut sessions = self.lock();
+        if sessions.owners.get(&record.id) == user_id.as_ref() {
+            return;
+        }
+
+        sessions.remove(&record.id);
+        if let Some(user_id) = user_id {
+            sessions
+                .by_user
+                .entry(user_id.clone())
+                .or_default()
+                .insert(record.id);
+            sessions.owners.insert(record.id, user_id);
+        }
+    }
+
+    pub(crate) fn remove(&self, session_id: &Id) {
+        self.lock().remove(session_id);
+    }
+
+    pub(crate) fn clear(&self) {
+        *self.lock() = Sessions::default();
+    }
+
+    /// The IDs of the sessions indexed under the user, in no particular
+    /// order.
+    pub(crate) fn session_ids(&self, user_id: &str) -> Vec<Id> {
+        self.lock()
+            .by_user
+            .get(user_id)
+            .map(|session_ids| session_ids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Sessions> {
+        self.sessions.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl fmt::Debug for UserIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserIndex").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,62 @@
+use mongodb::options::ClientOptions;
+
+use crate::MongoDBStoreError;
+
+/// The compression of the traffic between the client and the deployment.
+///
+/// Session records compress well, which lowers transfer costs when the
+/// deployment is in another availability zone or region. Compression is
+/// negotiated when connecting, so it is set on the client options before the
+/// client is created, e.g. with [`MongoDBStore::from_client_options`]. The
+/// server must support the compressor as well, otherwise traffic is sent
+/// uncompressed.
+///
+/// [`MongoDBStore::from_client_options`]: crate::MongoDBStore::from_client_options
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WireCompression {
+    /// Leave the compressors of the client options as they are.
+    #[default]
+    Unchanged,
+
+    /// Compress with zlib, the format used by gzip, at the provided level
+    /// between 0 and 9, or the default level if `None`.
+    ///
+    /// This requires the `zlib-compression` feature.
+    Zlib(Option<i32>),
+
+    /// Compress with Zstandard at the provided level between 1 and 22, or the
+    /// default level if `None`.
+    ///
+    /// This requires the `zstd-compression` feature.
+    Zstd(Option<i32>),
+}
+
+impl WireCompression {
+    /// Set the compressors of the provided client options.
+    ///
+    /// This fails if the feature required by the compression isn't enabled.
+    #[allow(unused_variables)]
+    pub fn apply(self, client_options: &mut ClientOptions) -> Result<(), MongoDBStoreError> {
+        match self {
+            WireCompression::Unchanged => {}
+            #[cfg(feature = "zlib-compression")]
+            WireCompression::Zlib(level) => {
+                client_options.compressors =
+                    Some(vec![mongodb::options::Compressor::Zlib { level }]);
+            }
+            #[cfg(feature = "zstd-compression")]
+            WireCompression::Zstd(level) => {
+                client_options.compressors =
+                    Some(vec![mongodb::options::Compressor::Zstd { level }]);
+            }
+            #[allow(unreachable_patterns)]
+            compression => {
+                return Err(MongoDBStoreError::CompressionDisabled(match compression {
+                    WireCompression::Zlib(_) => "zlib",
+                    _ => "zstd",
+                }));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,13 +1,39 @@
+use std::{collections::HashMap, future::Future, str::FromStr, time::Duration as StdDuration};
+
 use async_trait::async_trait;
-use bson::{doc, to_document};
+use bson::{doc, to_document, Document};
 pub use mongodb;
-use mongodb::{options::UpdateOptions, Client, Collection};
+use mongodb::{
+    error::{ErrorKind, WriteFailure, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR},
+    options::{
+        ClientOptions, Collation, CountOptions, CreateCollectionOptions, DeleteOptions,
+        FindOneAndUpdateOptions, FindOneOptions, FindOptions, IndexOptions, ReadPreference,
+        SelectionCriteria, UpdateOptions,
+    },
+    Client, Collection, Database, IndexModel,
+};
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use tower_sessions_core::{
     session::{Id, Record},
     session_store, ExpiredDeletion, SessionStore,
 };
+use tower_sessions_ext::{
+    transient_backend_error, warn, DecodeFailurePolicy, EraseUserSessions, ErasureReport, ErrorTag,
+    ExportSessions, IdCollisionPolicy, SlowOpTracer, StoreOptions, StoreOptionsConfig,
+    TouchSessionStore, TtlPolicy,
+};
+
+use self::size_guard::SizeGuard;
+pub use self::{
+    compression::WireCompression,
+    size_guard::{is_session_too_large, OversizedSession},
+    ttl::{RedundantSweep, TtlStatus},
+};
+
+mod compression;
+mod size_guard;
+mod ttl;
 
 /// An error type for `MongoDBStore`.
 #[derive(thiserror::Error, Debug)]
@@ -24,36 +50,229 @@ pub enum MongoDBStoreError {
     #[error(transparent)]
     Decode(#[from] rmp_serde::decode::Error),
 
+    /// A variant to map `serde_json` encode errors.
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonEncode(serde_json::Error),
+
+    /// A variant to map `serde_json` decode errors.
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonDecode(serde_json::Error),
+
     /// A variant to map `mongodb::bson` encode errors.
     #[error(transparent)]
     BsonSerialize(#[from] bson::ser::Error),
+
+    /// A variant for stored documents whose `_id` is not a session id.
+    #[error("invalid session id `{0}`")]
+    InvalidId(String),
+
+    /// A variant for user operations on a store without a user field.
+    #[error("no user field configured; see `MongoDBStore::with_user_field`")]
+    NoUserField,
+
+    /// A variant for operations that didn't finish within the timeout set
+    /// with `MongoDBStore::with_operation_timeout`.
+    #[error("operation did not finish within {0:?}")]
+    Timeout(StdDuration),
+
+    /// A variant for wire compression whose feature isn't enabled.
+    #[error("{0} compression requires the `{0}-compression` feature")]
+    CompressionDisabled(&'static str),
+
+    /// A variant for sessions whose document exceeds the maximum size set
+    /// with `MongoDBStore::with_max_document_size`.
+    #[error("Session document is too large: {size} bytes exceed the maximum of {max_size} bytes")]
+    SessionTooLarge { size: usize, max_size: usize },
+}
+
+impl MongoDBStoreError {
+    /// Returns `true` if the operation may succeed if retried, e.g. after a
+    /// network error, a server selection timeout, an operation timeout, or an
+    /// error the server labels as retryable.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            MongoDBStoreError::Timeout(_) => true,
+            MongoDBStoreError::MongoDB(err) => {
+                matches!(
+                    *err.kind,
+                    ErrorKind::Io(_)
+                        | ErrorKind::ConnectionPoolCleared { .. }
+                        | ErrorKind::ServerSelection { .. }
+                ) || err.contains_label(RETRYABLE_WRITE_ERROR)
+                    || err.contains_label(TRANSIENT_TRANSACTION_ERROR)
+            }
+            _ => false,
+        }
+    }
 }
 
 impl From<MongoDBStoreError> for session_store::Error {
     fn from(err: MongoDBStoreError) -> Self {
         match err {
+            MongoDBStoreError::MongoDB(_) | MongoDBStoreError::Timeout(_) if err.is_transient() => {
+                transient_backend_error(err)
+            }
             MongoDBStoreError::MongoDB(inner) => session_store::Error::Backend(inner.to_string()),
             MongoDBStoreError::Decode(inner) => session_store::Error::Decode(inner.to_string()),
             MongoDBStoreError::Encode(inner) => session_store::Error::Encode(inner.to_string()),
+            #[cfg(feature = "json")]
+            MongoDBStoreError::JsonEncode(inner) => session_store::Error::Encode(inner.to_string()),
+            #[cfg(feature = "json")]
+            MongoDBStoreError::JsonDecode(inner) => session_store::Error::Decode(inner.to_string()),
             MongoDBStoreError::BsonSerialize(inner) => {
                 session_store::Error::Encode(inner.to_string())
             }
+            MongoDBStoreError::InvalidId(_) => session_store::Error::Decode(err.to_string()),
+            MongoDBStoreError::SessionTooLarge { .. } => {
+                ErrorTag::SessionTooLarge.encode_error(err)
+            }
+            MongoDBStoreError::NoUserField
+            | MongoDBStoreError::Timeout(_)
+            | MongoDBStoreError::CompressionDisabled(_) => {
+                session_store::Error::Backend(err.to_string())
+            }
         }
     }
 }
 
+/// Serialize a record for the `data` field, with MessagePack or, with the
+/// `json` feature, JSON.
+fn encode_record(record: &Record) -> Result<Vec<u8>, MongoDBStoreError> {
+    #[cfg(feature = "json")]
+    return serde_json::to_vec(record).map_err(MongoDBStoreError::JsonEncode);
+
+    #[cfg(not(feature = "json"))]
+    return rmp_serde::to_vec(record).map_err(MongoDBStoreError::Encode);
+}
+
+/// Deserialize a record read from the `data` field.
+fn decode_record(data: &[u8]) -> Result<Record, MongoDBStoreError> {
+    #[cfg(feature = "json")]
+    return serde_json::from_slice(data).map_err(MongoDBStoreError::JsonDecode);
+
+    #[cfg(not(feature = "json"))]
+    return rmp_serde::from_slice(data).map_err(MongoDBStoreError::Decode);
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct MongoDBSessionRecord {
     data: bson::Binary,
 
     #[serde(rename = "expireAt")]
     expiry_date: bson::DateTime,
+
+    /// Per-document time-to-live in seconds, used by Cosmos DB in place of a
+    /// TTL index over a date field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user: Option<bson::Bson>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MongoDBSessionSummary {
+    #[serde(rename = "_id")]
+    id: String,
+
+    #[serde(rename = "expireAt")]
+    expiry_date: bson::DateTime,
+
+    #[serde(default)]
+    user: Option<bson::Bson>,
+}
+
+/// An overview of a stored session, as returned by
+/// [`MongoDBStore::list_active`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionSummary {
+    /// The session ID.
+    pub id: Id,
+
+    /// When the session expires.
+    pub expiry_date: OffsetDateTime,
+
+    /// The value mirrored from the session data by
+    /// [`MongoDBStore::with_user_field`], if any.
+    pub user: Option<bson::Bson>,
+}
+
+/// A page of active sessions, as returned by [`MongoDBStore::list_active`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionPage {
+    /// The sessions on this page, ordered by ID.
+    pub sessions: Vec<SessionSummary>,
+
+    /// The cursor to pass to [`MongoDBStore::list_active`] to fetch the next
+    /// page, or `None` when this is the last page.
+    pub next_cursor: Option<Id>,
+}
+
+/// The settings of a [`MongoDBStore`], as read from a configuration file.
+///
+/// The options shared by every store, such as the `namespace` naming the
+/// session collection, are written next to these settings. Settings that are
+/// left out keep the store's defaults.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_mongodb_store::MongoDBStoreConfig;
+///
+/// let config: MongoDBStoreConfig =
+///     serde_json::from_str(r#"{ "namespace": "sessions", "operation_timeout_ms": 500 }"#)
+///         .unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MongoDBStoreConfig {
+    /// The options shared by every store.
+    #[serde(flatten)]
+    pub options: StoreOptionsConfig,
+
+    /// Passed to [`MongoDBStore::with_save_guard`].
+    pub save_guard: Option<bool>,
+
+    /// Passed to [`MongoDBStore::with_user_field`].
+    pub user_field: Option<String>,
+
+    /// Passed to [`MongoDBStore::with_cosmos_compat`].
+    pub cosmos_compat: bool,
+
+    /// Passed to [`MongoDBStore::with_sharding`].
+    pub sharding: bool,
+
+    /// Passed to [`MongoDBStore::with_operation_timeout`], in milliseconds.
+    pub operation_timeout_ms: Option<u64>,
+
+    /// Passed to [`MongoDBStore::with_redundant_sweep`].
+    pub redundant_sweep: RedundantSweep,
+
+    /// Passed to [`MongoDBStore::with_max_document_size`], rejecting oversized
+    /// sessions.
+    pub max_document_size: Option<usize>,
 }
 
 /// A MongoDB session store.
 #[derive(Clone, Debug)]
 pub struct MongoDBStore {
+    database: Database,
     collection: Collection<MongoDBSessionRecord>,
+    load_selection_criteria: Option<SelectionCriteria>,
+    operation_timeout: Option<StdDuration>,
+    collation: Collation,
+    cosmos_compat: bool,
+    sharding: bool,
+    user_field: Option<String>,
+    save_guard: bool,
+    redundant_sweep: RedundantSweep,
+    size_guard: Option<SizeGuard>,
+    ttl_policy: TtlPolicy,
+    id_collision_policy: IdCollisionPolicy,
+    decode_failure_policy: DecodeFailurePolicy,
+    slow_ops: SlowOpTracer,
 }
 
 impl MongoDBStore {
@@ -71,19 +290,757 @@ impl MongoDBStore {
     /// # })
     /// ```
     pub fn new(client: Client, database: String) -> Self {
+        let database = client.database(&database);
         Self {
-            collection: client.database(&database).collection("sessions"),
+            collection: database.collection("sessions"),
+            database,
+            load_selection_criteria: None,
+            operation_timeout: None,
+            collation: Collation::builder().locale("simple").build(),
+            cosmos_compat: false,
+            sharding: false,
+            user_field: None,
+            save_guard: true,
+            redundant_sweep: RedundantSweep::default(),
+            size_guard: None,
+            ttl_policy: TtlPolicy::default(),
+            id_collision_policy: IdCollisionPolicy::default(),
+            decode_failure_policy: DecodeFailurePolicy::default(),
+            slow_ops: SlowOpTracer::new("mongodb"),
+        }
+    }
+
+    /// Create a new MongoDBStore store with a client created from the provided
+    /// options, compressing its traffic as provided.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{
+    ///     mongodb::options::ClientOptions, MongoDBStore, WireCompression,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client_options = ClientOptions::parse(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::from_client_options(
+    ///     client_options,
+    ///     "database".to_string(),
+    ///     WireCompression::Zstd(None),
+    /// )
+    /// .unwrap();
+    /// # })
+    /// ```
+    pub fn from_client_options(
+        mut client_options: ClientOptions,
+        database: String,
+        compression: WireCompression,
+    ) -> Result<Self, MongoDBStoreError> {
+        compression.apply(&mut client_options)?;
+        let client = Client::with_options(client_options)?;
+        Ok(Self::new(client, database))
+    }
+
+    /// Create a new MongoDBStore store with the provided client and database
+    /// name, configured with the provided settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore, MongoDBStoreConfig};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let config: MongoDBStoreConfig =
+    ///     serde_json::from_str(r#"{ "namespace": "sessions" }"#).unwrap();
+    /// let session_store =
+    ///     MongoDBStore::try_from_config(client, "database".to_string(), &config).unwrap();
+    /// # })
+    /// ```
+    pub fn try_from_config(
+        client: Client,
+        database: String,
+        config: &MongoDBStoreConfig,
+    ) -> Result<Self, String> {
+        let mut store = config
+            .options
+            .apply(Self::new(client, database))?
+            .with_cosmos_compat(config.cosmos_compat)
+            .with_sharding(config.sharding)
+            .with_redundant_sweep(config.redundant_sweep);
+        if let Some(save_guard) = config.save_guard {
+            store = store.with_save_guard(save_guard);
+        }
+        if let Some(key) = &config.user_field {
+            store = store.with_user_field(key);
+        }
+        if let Some(timeout) = config.operation_timeout_ms {
+            store = store.with_operation_timeout(StdDuration::from_millis(timeout));
+        }
+        if let Some(max_size) = config.max_document_size {
+            store = store.with_max_document_size(max_size, OversizedSession::Reject);
+        }
+        Ok(store)
+    }
+
+    /// Store sessions in the collection with the provided name rather than
+    /// `sessions`.
+    pub fn with_collection_name(mut self, collection_name: impl AsRef<str>) -> Self {
+        self.collection = self.database.collection(collection_name.as_ref());
+        self
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    /// Set how `load` responds to a session whose stored data can't be
+    /// decoded, e.g. after the serialization format has changed.
+    pub fn with_decode_failure_policy(
+        mut self,
+        decode_failure_policy: DecodeFailurePolicy,
+    ) -> Self {
+        self.decode_failure_policy = decode_failure_policy;
+        self
+    }
+
+    /// Log operations taking longer than the provided threshold with
+    /// `tracing::warn!`.
+    pub fn with_slow_op_threshold(mut self, threshold: StdDuration) -> Self {
+        self.slow_ops = self.slow_ops.with_threshold(threshold);
+        self
+    }
+
+    /// Set the policy adjusting the expiry dates written to the `expireAt`
+    /// field, and to the `ttl` field with Cosmos DB compatibility.
+    pub fn with_ttl_policy(mut self, ttl_policy: TtlPolicy) -> Self {
+        self.ttl_policy = ttl_policy;
+        self
+    }
+
+    /// Set whether `save` refuses to write over an expired or missing session.
+    ///
+    /// By default `save` only updates a session document that exists and has
+    /// not yet expired, so a save that is still in flight when a session is
+    /// purged or revoked cannot bring it back. New sessions are written by
+    /// `create` instead. Saving a missing or expired session then writes
+    /// nothing and still succeeds, unlike with the other stores, which upsert.
+    ///
+    /// Disabling the guard upserts sessions, so that missing sessions are
+    /// inserted. The expiry check still applies, though: saving a session
+    /// that has expired but hasn't been removed yet writes nothing and still
+    /// succeeds, leaving the session to expire.
+    pub fn with_save_guard(mut self, save_guard: bool) -> Self {
+        self.save_guard = save_guard;
+        self
+    }
+
+    /// Set what [`ExpiredDeletion::delete_expired`] does when the TTL monitor
+    /// already removes expired sessions.
+    ///
+    /// With [`RedundantSweep::Warn`] or [`RedundantSweep::Skip`], each sweep
+    /// first checks [`MongoDBStore::ttl_status`], and sweeps as usual if the
+    /// check fails. Defaults to [`RedundantSweep::Sweep`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore, RedundantSweep};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string())
+    ///     .with_redundant_sweep(RedundantSweep::Skip);
+    /// # })
+    /// ```
+    pub fn with_redundant_sweep(mut self, redundant_sweep: RedundantSweep) -> Self {
+        self.redundant_sweep = redundant_sweep;
+        self
+    }
+
+    /// Refuse to write session documents larger than `max_size` bytes, or
+    /// shrink them as provided.
+    ///
+    /// MongoDB rejects documents over 16MB, but a session growing anywhere
+    /// near that is usually a bug, such as data appended on every request,
+    /// and slows down every request loading it long before. With a limit
+    /// well below the server's, `create` and `save` fail with
+    /// [`MongoDBStoreError::SessionTooLarge`], recognized by
+    /// [`is_session_too_large`], rather than with a driver error once the
+    /// document no longer fits. The size counts the encoded session data and
+    /// the other fields of the document.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore, OversizedSession};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string())
+    ///     .with_max_document_size(256 * 1024, OversizedSession::Reject);
+    /// # })
+    /// ```
+    pub fn with_max_document_size(
+        mut self,
+        max_size: usize,
+        oversized_session: OversizedSession,
+    ) -> Self {
+        self.size_guard = Some(SizeGuard {
+            max_size,
+            oversized_session,
+        });
+        self
+    }
+
+    /// Set the collation of the session collection.
+    ///
+    /// [`MongoDBStore::migrate`] creates the collection with this collation,
+    /// which also applies to its `_id` index, and every query specifies it.
+    /// Defaults to the `simple` collation, which compares IDs byte by byte.
+    ///
+    /// Session IDs differing only in case are distinct, so don't use a
+    /// collation that ignores case. Only change this to match the default
+    /// collation of an existing collection: a query with another collation
+    /// can't use the `_id` index and scans the whole collection.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{
+    ///     mongodb::{options::Collation, Client},
+    ///     MongoDBStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string())
+    ///     .with_collation(Collation::builder().locale("en_US").build());
+    /// # })
+    /// ```
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    /// The collation passed to queries, if any.
+    ///
+    /// Cosmos DB doesn't support collations, so none is passed with
+    /// [`MongoDBStore::with_cosmos_compat`].
+    fn collation(&self) -> Option<Collation> {
+        (!self.cosmos_compat).then(|| self.collation.clone())
+    }
+
+    /// Mirror the given session data key into a top-level `user` field of
+    /// each session document.
+    ///
+    /// This makes the session owner visible to [`MongoDBStore::list_active`]
+    /// and to queries against the collection without decoding session data,
+    /// and is required by [`EraseUserSessions::erase_user`].
+    pub fn with_user_field(mut self, key: impl Into<String>) -> Self {
+        self.user_field = Some(key.into());
+        self
+    }
+
+    /// Count the sessions that have not yet expired.
+    pub async fn count_active(&self) -> session_store::Result<u64> {
+        let count = self
+            .collection
+            .count_documents(
+                doc! { "expireAt": {"$gt": OffsetDateTime::now_utc()} },
+                CountOptions::builder().collation(self.collation()).build(),
+            )
+            .await
+            .map_err(MongoDBStoreError::MongoDB)?;
+
+        Ok(count)
+    }
+
+    /// List sessions that have not yet expired, one page at a time.
+    ///
+    /// Sessions are ordered by ID. Pass `None` to fetch the first page and the
+    /// returned [`SessionPage::next_cursor`] to fetch subsequent pages.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string());
+    ///
+    /// let mut cursor = None;
+    /// loop {
+    ///     let page = session_store.list_active(cursor, 100).await.unwrap();
+    ///     for session in page.sessions {
+    ///         println!("{} expires at {}", session.id, session.expiry_date);
+    ///     }
+    ///     match page.next_cursor {
+    ///         Some(next) => cursor = Some(next),
+    ///         None => break,
+    ///     }
+    /// }
+    /// # })
+    /// ```
+    pub async fn list_active(
+        &self,
+        after: Option<Id>,
+        limit: u32,
+    ) -> session_store::Result<SessionPage> {
+        let mut filter = doc! { "expireAt": {"$gt": OffsetDateTime::now_utc()} };
+        if let Some(after) = after {
+            filter.insert("_id", doc! { "$gt": after.to_string() });
+        }
+
+        let mut cursor = self
+            .collection
+            .clone_with_type::<MongoDBSessionSummary>()
+            .find(
+                filter,
+                FindOptions::builder()
+                    .projection(doc! { "_id": 1, "expireAt": 1, "user": 1 })
+                    .sort(doc! { "_id": 1 })
+                    .limit(i64::from(limit))
+                    .collation(self.collation())
+                    .build(),
+            )
+            .await
+            .map_err(MongoDBStoreError::MongoDB)?;
+
+        let mut sessions = Vec::new();
+        while cursor.advance().await.map_err(MongoDBStoreError::MongoDB)? {
+            let summary = cursor
+                .deserialize_current()
+                .map_err(MongoDBStoreError::MongoDB)?;
+            let id =
+                Id::from_str(&summary.id).map_err(|_| MongoDBStoreError::InvalidId(summary.id))?;
+            sessions.push(SessionSummary {
+                id,
+                expiry_date: summary.expiry_date.into(),
+                user: summary.user,
+            });
+        }
+
+        let next_cursor = if limit > 0 && sessions.len() == limit as usize {
+            sessions.last().map(|session| session.id)
+        } else {
+            None
+        };
+
+        Ok(SessionPage {
+            sessions,
+            next_cursor,
+        })
+    }
+
+    /// Enable compatibility with Azure Cosmos DB for MongoDB.
+    ///
+    /// Cosmos DB does not expire documents from a TTL index over an arbitrary
+    /// date field. With this enabled, each document carries its own `ttl` field
+    /// and [`MongoDBStore::migrate`] creates the `_ts` index Cosmos DB requires
+    /// instead of the default `expireAt` index. Cosmos DB doesn't support
+    /// collations, so the one set by [`MongoDBStore::with_collation`] isn't
+    /// used.
+    pub fn with_cosmos_compat(mut self, cosmos_compat: bool) -> Self {
+        self.cosmos_compat = cosmos_compat;
+        self
+    }
+
+    /// Shard the session collection on a hashed `_id` when migrating.
+    ///
+    /// Session IDs are random, so hashing them spreads sessions, and the load
+    /// of reading and writing them, evenly across shards with no hot chunks.
+    /// With this enabled, [`MongoDBStore::migrate`] also enables sharding for
+    /// the database and shards the collection if it is not already sharded.
+    /// Migrating then fails unless the client is connected to a sharded
+    /// cluster through `mongos`, as the sharding commands are only available
+    /// there.
+    pub fn with_sharding(mut self, sharding: bool) -> Self {
+        self.sharding = sharding;
+        self
+    }
+
+    /// Build the schema map that encrypts session data with client-side
+    /// field-level encryption, using the provided data key.
+    ///
+    /// Pass the map to `schema_map` when building the client with
+    /// `Client::encrypted_builder`, which requires the driver's
+    /// `in-use-encryption-unstable` feature. The driver then encrypts the
+    /// `data` field of each session document before it is sent to the cluster
+    /// and decrypts it on load. The expiry date stays in plaintext so that the
+    /// TTL index can expire sessions, as does the field written by
+    /// [`MongoDBStore::with_user_field`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{
+    ///     mongodb::{
+    ///         bson::{spec::BinarySubtype, Binary},
+    ///         Client,
+    ///     },
+    ///     MongoDBStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// // The ID of a data key, e.g. as returned by `ClientEncryption::create_data_key`.
+    /// let data_key_id = Binary {
+    ///     subtype: BinarySubtype::Uuid,
+    ///     bytes: vec![0; 16],
+    /// };
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string());
+    /// let schema_map = session_store.encryption_schema_map(data_key_id);
+    /// # })
+    /// ```
+    pub fn encryption_schema_map(&self, data_key_id: bson::Binary) -> HashMap<String, Document> {
+        let schema = doc! {
+            "bsonType": "object",
+            "encryptMetadata": { "keyId": [data_key_id] },
+            "properties": {
+                "data": {
+                    "encrypt": {
+                        "bsonType": "binData",
+                        "algorithm": "AEAD_AES_256_CBC_HMAC_SHA_512-Random",
+                    },
+                },
+            },
+        };
+
+        HashMap::from([(self.collection.namespace().to_string(), schema)])
+    }
+
+    /// Check that the deployment is reachable.
+    ///
+    /// The MongoDB client connects lazily, so a store can be created while
+    /// the deployment is unavailable and sessions are served once it is back.
+    /// Call this at startup to surface connection problems, or to establish
+    /// connections, before the first request.
+    pub async fn warm_up(&self) -> mongodb::error::Result<()> {
+        self.database.run_command(doc! { "ping": 1 }, None).await?;
+        Ok(())
+    }
+
+    /// Create the session collection with the configured collation, if it
+    /// doesn't exist, and the TTL index used to expire sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string());
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> mongodb::error::Result<()> {
+        if let Err(err) = self
+            .database
+            .create_collection(
+                self.collection.name(),
+                CreateCollectionOptions::builder()
+                    .collation(self.collation())
+                    .build(),
+            )
+            .await
+        {
+            if !is_namespace_exists_error(&err) {
+                return Err(err);
+            }
+        }
+
+        let index = if self.cosmos_compat {
+            // Cosmos DB only honors per-document `ttl` fields once a TTL index exists
+            // on `_ts`; the collection-wide default is effectively "never" so that
+            // the per-document value always decides.
+            IndexModel::builder()
+                .keys(doc! { "_ts": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(StdDuration::from_secs(i32::MAX as u64))
+                        .build(),
+                )
+                .build()
+        } else {
+            IndexModel::builder()
+                .keys(doc! { "expireAt": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(StdDuration::ZERO)
+                        .collation(self.collation())
+                        .build(),
+                )
+                .build()
+        };
+        self.collection.create_index(index, None).await?;
+
+        if self.sharding {
+            self.shard_collection().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the deployment removes expired sessions on its own.
+    ///
+    /// This looks for the TTL index created by [`MongoDBStore::migrate`] and
+    /// reads the `ttlMonitorEnabled` server parameter, which requires the
+    /// `getParameter` privilege on the cluster; without it, the monitor is
+    /// reported as unknown.
+    pub async fn ttl_status(&self) -> mongodb::error::Result<TtlStatus> {
+        let ttl_field = if self.cosmos_compat {
+            "_ts"
+        } else {
+            "expireAt"
+        };
+        let index_exists = match self.collection.list_indexes(None).await {
+            Ok(mut cursor) => {
+                let mut index_exists = false;
+                while !index_exists && cursor.advance().await? {
+                    let index = cursor.deserialize_current()?;
+                    index_exists = index.keys.len() == 1
+                        && index.keys.contains_key(ttl_field)
+                        && index
+                            .options
+                            .is_some_and(|options| options.expire_after.is_some());
+                }
+                index_exists
+            }
+            Err(err) if is_namespace_not_found_error(&err) => false,
+            Err(err) => return Err(err),
+        };
+
+        let monitor_enabled = self
+            .collection
+            .client()
+            .database("admin")
+            .run_command(doc! { "getParameter": 1, "ttlMonitorEnabled": 1 }, None)
+            .await
+            .ok()
+            .and_then(|reply| reply.get_bool("ttlMonitorEnabled").ok());
+
+        Ok(TtlStatus {
+            index_exists,
+            monitor_enabled,
+        })
+    }
+
+    /// Returns `true` if a sweep should be skipped because the TTL monitor
+    /// already removes expired sessions, logging redundant sweeps as
+    /// configured.
+    async fn skip_redundant_sweep(&self) -> bool {
+        if self.redundant_sweep == RedundantSweep::Sweep {
+            return false;
+        }
+        let Ok(status) = self.ttl_status().await else {
+            return false;
+        };
+        if !status.expires_sessions() {
+            return false;
+        }
+
+        match self.redundant_sweep {
+            RedundantSweep::Skip => true,
+            _ => {
+                warn!(
+                    collection = %self.collection.namespace(),
+                    "sweeping expired sessions that the TTL monitor already removes"
+                );
+                false
+            }
+        }
+    }
+
+    async fn shard_collection(&self) -> mongodb::error::Result<()> {
+        let admin = self.collection.client().database("admin");
+
+        admin
+            .run_command(doc! { "enableSharding": self.database.name() }, None)
+            .await?;
+        admin
+            .run_command(
+                doc! {
+                    "shardCollection": self.collection.namespace().to_string(),
+                    "key": { "_id": "hashed" },
+                    // Required when the collection's default collation isn't `simple`.
+                    "collation": { "locale": "simple" },
+                },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The document a session is written as, checked against the maximum
+    /// size, if any.
+    fn session_document(&self, record: &Record) -> Result<Document, MongoDBStoreError> {
+        let doc = self.encode_document(record)?;
+        match &self.size_guard {
+            Some(size_guard) => {
+                size_guard.check(record, doc, |record| self.encode_document(record))
+            }
+            None => Ok(doc),
+        }
+    }
+
+    fn encode_document(&self, record: &Record) -> Result<Document, MongoDBStoreError> {
+        let record = &*self.ttl_policy.apply(record);
+        Ok(to_document(&MongoDBSessionRecord {
+            data: bson::Binary {
+                subtype: bson::spec::BinarySubtype::Generic,
+                bytes: encode_record(record)?,
+            },
+            expiry_date: bson::DateTime::from(record.expiry_date),
+            ttl: self.ttl_for(record.expiry_date),
+            user: self
+                .user_field
+                .as_ref()
+                .and_then(|key| record.data.get(key))
+                .map(bson::to_bson)
+                .transpose()?,
+        })?)
+    }
+
+    fn ttl_for(&self, expiry_date: OffsetDateTime) -> Option<i32> {
+        self.cosmos_compat.then(|| {
+            let seconds = (expiry_date - OffsetDateTime::now_utc()).whole_seconds();
+            seconds.clamp(1, i32::MAX as i64) as i32
+        })
+    }
+
+    /// Set the read preference used by `load`.
+    ///
+    /// By default loads follow the read preference of the client, which is
+    /// usually the primary. Read-heavy deployments may route loads to
+    /// secondaries instead; note that a secondary may lag behind the primary,
+    /// so a session saved moments ago may not be visible yet. Bounding the lag
+    /// with `max_staleness` keeps this window small.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions_mongodb_store::{
+    ///     mongodb::{
+    ///         options::{ReadPreference, ReadPreferenceOptions},
+    ///         Client,
+    ///     },
+    ///     MongoDBStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string())
+    ///     .with_load_read_preference(ReadPreference::SecondaryPreferred {
+    ///         options: ReadPreferenceOptions::builder()
+    ///             .max_staleness(Duration::from_secs(90))
+    ///             .build(),
+    ///     });
+    /// # })
+    /// ```
+    pub fn with_load_read_preference(mut self, read_preference: ReadPreference) -> Self {
+        self.load_selection_criteria = Some(SelectionCriteria::ReadPreference(read_preference));
+        self
+    }
+
+    /// Fail session operations that don't finish within the provided timeout.
+    ///
+    /// The driver waits for a suitable server for as long as the client's
+    /// server selection timeout, 30 seconds by default, so during a replica
+    /// set election every request touching its session stalls until a new
+    /// primary is elected. The timeout set here bounds `create`, `save`,
+    /// `load`, `delete`, and `touch` as a whole, including server selection,
+    /// and is also sent as `maxTimeMS` with the queries that accept it, so the
+    /// server gives up on them too. Operations that time out fail with
+    /// [`MongoDBStoreError::Timeout`], reported as a transient error for retry
+    /// and circuit breaking wrappers to handle.
+    ///
+    /// Server selection timeouts are a client option in the driver; lowering
+    /// `serverSelectionTimeoutMS` in the connection string changes it for
+    /// every user of the client instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string())
+    ///     .with_operation_timeout(Duration::from_millis(500));
+    /// # })
+    /// ```
+    pub fn with_operation_timeout(mut self, timeout: StdDuration) -> Self {
+        self.operation_timeout = Some(timeout);
+        self
+    }
+
+    /// Run a session operation within the operation timeout, if any.
+    async fn with_timeout<T>(
+        &self,
+        operation: impl Future<Output = session_store::Result<T>>,
+    ) -> session_store::Result<T> {
+        match self.operation_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, operation)
+                .await
+                .unwrap_or_else(|_| Err(MongoDBStoreError::Timeout(timeout).into())),
+            None => operation.await,
         }
     }
 }
 
+impl StoreOptions for MongoDBStore {
+    fn with_namespace(self, namespace: &str) -> Result<Self, String> {
+        Ok(self.with_collection_name(namespace))
+    }
+
+    fn with_id_collision_policy(self, id_collision_policy: IdCollisionPolicy) -> Self {
+        Self::with_id_collision_policy(self, id_collision_policy)
+    }
+
+    fn with_decode_failure_policy(self, decode_failure_policy: DecodeFailurePolicy) -> Self {
+        Self::with_decode_failure_policy(self, decode_failure_policy)
+    }
+
+    fn with_slow_op_threshold(self, threshold: StdDuration) -> Self {
+        Self::with_slow_op_threshold(self, threshold)
+    }
+
+    fn with_ttl_policy(self, ttl_policy: TtlPolicy) -> Self {
+        Self::with_ttl_policy(self, ttl_policy)
+    }
+}
+
 #[async_trait]
 impl ExpiredDeletion for MongoDBStore {
     async fn delete_expired(&self) -> session_store::Result<()> {
+        if self.skip_redundant_sweep().await {
+            return Ok(());
+        }
+
         self.collection
             .delete_many(
                 doc! { "expireAt": {"$lt": OffsetDateTime::now_utc()} },
-                None,
+                DeleteOptions::builder().collation(self.collation()).build(),
             )
             .await
             .map_err(MongoDBStoreError::MongoDB)?;
@@ -94,56 +1051,233 @@ impl ExpiredDeletion for MongoDBStore {
 
 #[async_trait]
 impl SessionStore for MongoDBStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = self
+            .with_timeout(async {
+                let collection = self.collection.clone_with_type::<Document>();
+                let mut collisions = 0;
+                loop {
+                    let mut doc = self.session_document(record)?;
+                    doc.insert("_id", record.id.to_string());
+
+                    match collection.insert_one(doc, None).await {
+                        Ok(_) => return Ok(()),
+                        Err(err) if is_duplicate_key_error(&err) => {
+                            collisions += 1;
+                            record.id = self.id_collision_policy.next_id(collisions)?;
+                        }
+                        Err(err) => return Err(MongoDBStoreError::MongoDB(err).into()),
+                    }
+                }
+            })
+            .await;
+        self.slow_ops.finish(started, "create", Some(record));
+        res
+    }
+
     async fn save(&self, record: &Record) -> session_store::Result<()> {
-        let doc = to_document(&MongoDBSessionRecord {
-            data: bson::Binary {
-                subtype: bson::spec::BinarySubtype::Generic,
-                bytes: rmp_serde::to_vec(record).map_err(MongoDBStoreError::Encode)?,
-            },
-            expiry_date: bson::DateTime::from(record.expiry_date),
-        })
-        .map_err(MongoDBStoreError::BsonSerialize)?;
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = self
+            .with_timeout(async {
+                let doc = self.session_document(record)?;
 
-        self.collection
-            .update_one(
-                doc! { "_id": record.id.to_string() },
-                doc! { "$set": doc },
-                UpdateOptions::builder().upsert(true).build(),
-            )
-            .await
-            .map_err(MongoDBStoreError::MongoDB)?;
+                // The write and the expiry check are a single atomic operation on
+                // the shard owning the ID, so a session can't expire or be
+                // removed between them.
+                let res = self
+                    .collection
+                    .clone_with_type::<Document>()
+                    .find_one_and_update(
+                        doc! {
+                            "_id": record.id.to_string(),
+                            "expireAt": {"$gt": OffsetDateTime::now_utc()}
+                        },
+                        doc! { "$set": doc },
+                        FindOneAndUpdateOptions::builder()
+                            .upsert(!self.save_guard)
+                            .projection(doc! { "_id": 1 })
+                            .collation(self.collation())
+                            .max_time(self.operation_timeout)
+                            .build(),
+                    )
+                    .await;
 
-        Ok(())
+                match res {
+                    Ok(_) => Ok(()),
+                    // The upsert collided with an expired session that hasn't
+                    // been removed yet, which is left to expire.
+                    Err(err) if !self.save_guard && is_duplicate_key_error(&err) => Ok(()),
+                    Err(err) => Err(MongoDBStoreError::MongoDB(err).into()),
+                }
+            })
+            .await;
+        self.slow_ops.finish(started, "save", Some(record));
+        res
     }
 
     async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
-        let doc = self
+        let started = self.slow_ops.start();
+        let res: session_store::Result<Option<Record>> = self
+            .with_timeout(async {
+                let doc = self
+                    .collection
+                    .find_one(
+                        doc! {
+                            "_id": session_id.to_string(),
+                            "expireAt": {"$gt": OffsetDateTime::now_utc()}
+                        },
+                        FindOneOptions::builder()
+                            .selection_criteria(self.load_selection_criteria.clone())
+                            .collation(self.collation())
+                            .max_time(self.operation_timeout)
+                            .build(),
+                    )
+                    .await
+                    .map_err(MongoDBStoreError::MongoDB)?;
+
+                if let Some(doc) = doc {
+                    let mut record: Record = decode_record(&doc.data.bytes)?;
+                    record.expiry_date = doc.expiry_date.into();
+                    Ok(Some(record))
+                } else {
+                    Ok(None)
+                }
+            })
+            .await;
+        self.slow_ops
+            .finish(started, "load", res.as_ref().ok().and_then(Option::as_ref));
+        self.decode_failure_policy
+            .apply(self, session_id, res)
+            .await
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = self
+            .with_timeout(async {
+                self.collection
+                    .delete_one(
+                        doc! { "_id": session_id.to_string() },
+                        DeleteOptions::builder().collation(self.collation()).build(),
+                    )
+                    .await
+                    .map_err(MongoDBStoreError::MongoDB)?;
+
+                Ok(())
+            })
+            .await;
+        self.slow_ops.finish(started, "delete", None);
+        res
+    }
+}
+
+#[async_trait]
+impl TouchSessionStore for MongoDBStore {
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        let expiry_date = self.ttl_policy.expiry_date(session_id, expiry_date);
+        let mut update = doc! { "expireAt": bson::DateTime::from(expiry_date) };
+        if let Some(ttl) = self.ttl_for(expiry_date) {
+            update.insert("ttl", ttl);
+        }
+
+        self.with_timeout(async {
+            let res = self
+                .collection
+                .update_one(
+                    doc! {
+                        "_id": session_id.to_string(),
+                        "expireAt": {"$gt": OffsetDateTime::now_utc()}
+                    },
+                    doc! { "$set": update },
+                    UpdateOptions::builder().collation(self.collation()).build(),
+                )
+                .await
+                .map_err(MongoDBStoreError::MongoDB)?;
+
+            Ok(res.matched_count > 0)
+        })
+        .await
+    }
+}
+
+fn is_namespace_exists_error(err: &mongodb::error::Error) -> bool {
+    matches!(err.kind.as_ref(), ErrorKind::Command(err) if err.code == 48)
+}
+
+fn is_namespace_not_found_error(err: &mongodb::error::Error) -> bool {
+    matches!(err.kind.as_ref(), ErrorKind::Command(err) if err.code == 26)
+}
+
+fn is_duplicate_key_error(err: &mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => write_error.code == 11000,
+        // Raised by commands such as `findAndModify` rather than writes.
+        ErrorKind::Command(command_error) => command_error.code == 11000,
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl ExportSessions for MongoDBStore {
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        let filter = match after {
+            Some(after) => doc! { "_id": {"$gt": after.to_string()} },
+            None => doc! {},
+        };
+
+        let mut cursor = self
             .collection
-            .find_one(
-                doc! {
-                    "_id": session_id.to_string(),
-                    "expireAt": {"$gt": OffsetDateTime::now_utc()}
-                },
-                None,
+            .find(
+                filter,
+                FindOptions::builder()
+                    .sort(doc! { "_id": 1 })
+                    .limit(i64::from(limit))
+                    .collation(self.collation())
+                    .build(),
             )
             .await
             .map_err(MongoDBStoreError::MongoDB)?;
 
-        if let Some(doc) = doc {
-            Ok(Some(
-                rmp_serde::from_slice(&doc.data.bytes).map_err(MongoDBStoreError::Decode)?,
-            ))
-        } else {
-            Ok(None)
+        let mut records = Vec::new();
+        while cursor.advance().await.map_err(MongoDBStoreError::MongoDB)? {
+            let doc = cursor
+                .deserialize_current()
+                .map_err(MongoDBStoreError::MongoDB)?;
+            let mut record: Record = decode_record(&doc.data.bytes)?;
+            record.expiry_date = doc.expiry_date.into();
+            records.push(record);
         }
+
+        Ok(records)
     }
+}
 
-    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
-        self.collection
-            .delete_one(doc! { "_id": session_id.to_string() }, None)
+#[async_trait]
+impl EraseUserSessions for MongoDBStore {
+    async fn erase_user(
+        &self,
+        user_id: &serde_json::Value,
+        key: &[u8],
+    ) -> session_store::Result<ErasureReport> {
+        if self.user_field.is_none() {
+            return Err(MongoDBStoreError::NoUserField.into());
+        }
+
+        let user = bson::to_bson(user_id).map_err(MongoDBStoreError::BsonSerialize)?;
+        let res = self
+            .collection
+            .delete_many(
+                doc! { "user": user },
+                DeleteOptions::builder().collation(self.collation()).build(),
+            )
             .await
             .map_err(MongoDBStoreError::MongoDB)?;
 
-        Ok(())
+        Ok(ErasureReport::new(user_id, res.deleted_count, key))
     }
 }
@@ -0,0 +1,132 @@
+use std::{fmt, sync::Arc};
+
+use bson::Document;
+use tower_sessions_core::{session::Record, session_store};
+use tower_sessions_ext::{warn, ErrorTag};
+
+use crate::MongoDBStoreError;
+
+type TruncateHook = Arc<dyn Fn(&mut Record) + Send + Sync>;
+
+/// The size of the `_id` field that `create` adds to the session document:
+/// its type, name, length, and the 22 characters of the session ID with
+/// their terminator.
+const ID_FIELD_SIZE: usize = 1 + 4 + 4 + 23;
+
+/// Returns `true` if the error was produced by a [`MongoDBStore`] refusing to
+/// write a session whose document exceeds the maximum size set with
+/// [`MongoDBStore::with_max_document_size`].
+///
+/// Such errors are reported as encode errors tagged with
+/// [`ErrorTag::SessionTooLarge`], which this function recognizes; see
+/// [`MongoDBStoreError::SessionTooLarge`].
+///
+/// [`MongoDBStore`]: crate::MongoDBStore
+/// [`MongoDBStore::with_max_document_size`]: crate::MongoDBStore::with_max_document_size
+pub fn is_session_too_large(err: &session_store::Error) -> bool {
+    ErrorTag::SessionTooLarge.is_tagged(err)
+}
+
+/// What `create` and `save` do with a session whose document exceeds the
+/// maximum size set with
+/// [`MongoDBStore::with_max_document_size`](crate::MongoDBStore::with_max_document_size).
+#[derive(Clone, Default)]
+pub enum OversizedSession {
+    /// Fail with [`MongoDBStoreError::SessionTooLarge`].
+    #[default]
+    Reject,
+
+    /// Let a hook shrink the record before it is written, e.g. by dropping
+    /// cached data that can be recomputed, and fail as with
+    /// [`OversizedSession::Reject`] if it is still too large.
+    ///
+    /// Only the stored copy is changed, so the request that saved the
+    /// session still sees the data the hook removed. Each truncation is
+    /// logged with `tracing::warn!`.
+    Truncate(TruncateHook),
+}
+
+impl OversizedSession {
+    /// Shrink oversized records with the provided hook; see
+    /// [`OversizedSession::Truncate`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_mongodb_store::OversizedSession;
+    ///
+    /// let oversized_session = OversizedSession::truncate(|record| {
+    ///     record.data.remove("search_results");
+    /// });
+    /// ```
+    pub fn truncate(hook: impl Fn(&mut Record) + Send + Sync + 'static) -> Self {
+        Self::Truncate(Arc::new(hook))
+    }
+}
+
+impl fmt::Debug for OversizedSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reject => f.write_str("Reject"),
+            Self::Truncate(_) => f.debug_tuple("Truncate").field(&"..").finish(),
+        }
+    }
+}
+
+/// The maximum size of session documents, and what to do with those
+/// exceeding it.
+#[derive(Clone, Debug)]
+pub(crate) struct SizeGuard {
+    pub(crate) max_size: usize,
+    pub(crate) oversized_session: OversizedSession,
+}
+
+impl SizeGuard {
+    /// Check the size of the record's document, truncating the record and
+    /// encoding it again if allowed.
+    pub(crate) fn check(
+        &self,
+        record: &Record,
+        doc: Document,
+        encode: impl Fn(&Record) -> Result<Document, MongoDBStoreError>,
+    ) -> Result<Document, MongoDBStoreError> {
+        let size = document_size(&doc)?;
+        if size <= self.max_size {
+            return Ok(doc);
+        }
+
+        let OversizedSession::Truncate(hook) = &self.oversized_session else {
+            return Err(self.too_large(size));
+        };
+
+        let mut truncated = record.clone();
+        hook(&mut truncated);
+        let doc = encode(&truncated)?;
+        let truncated_size = document_size(&doc)?;
+        warn!(
+            session.id = %record.id,
+            size,
+            truncated_size,
+            max_size = self.max_size,
+            "truncated oversized session"
+        );
+        if truncated_size > self.max_size {
+            return Err(self.too_large(truncated_size));
+        }
+        Ok(doc)
+    }
+
+    fn too_large(&self, size: usize) -> MongoDBStoreError {
+        MongoDBStoreError::SessionTooLarge {
+            size,
+            max_size: self.max_size,
+        }
+    }
+}
+
+/// The size of the document once written, including its `_id`.
+fn document_size(doc: &Document) -> Result<usize, MongoDBStoreError> {
+    let mut bytes = Vec::new();
+    doc.to_writer(&mut bytes)?;
+    Ok(bytes.len() + ID_FIELD_SIZE)
+}
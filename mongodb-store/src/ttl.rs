@@ -0,0 +1,49 @@
+use serde::Deserialize;
+
+/// What [`ExpiredDeletion::delete_expired`] does when the TTL monitor already
+/// removes expired sessions.
+///
+/// [`MongoDBStore::migrate`] creates a TTL index over the expiry date, so a
+/// deployment with the TTL monitor running deletes expired sessions on its
+/// own, about once a minute. A deletion task sweeping the collection as well
+/// then repeats that work, scanning the index of a large collection for no
+/// gain. The TTL monitor can be disabled per server, e.g. during maintenance,
+/// and the index may be missing where `migrate` isn't run, so sweeping stays
+/// the default.
+///
+/// [`ExpiredDeletion::delete_expired`]: tower_sessions_core::ExpiredDeletion::delete_expired
+/// [`MongoDBStore::migrate`]: crate::MongoDBStore::migrate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedundantSweep {
+    /// Sweep without checking the TTL monitor.
+    #[default]
+    Sweep,
+
+    /// Sweep, logging a warning with `tracing::warn!` when the TTL monitor
+    /// already removes expired sessions.
+    Warn,
+
+    /// Skip the sweep when the TTL monitor already removes expired sessions.
+    Skip,
+}
+
+/// Whether the deployment expires sessions on its own, as reported by
+/// [`MongoDBStore::ttl_status`](crate::MongoDBStore::ttl_status).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtlStatus {
+    /// Whether the session collection has a TTL index over the expiry date,
+    /// or over `_ts` with Cosmos DB compatibility.
+    pub index_exists: bool,
+
+    /// Whether the TTL monitor is enabled, or `None` if the server didn't
+    /// say, e.g. because the client may not read server parameters.
+    pub monitor_enabled: Option<bool>,
+}
+
+impl TtlStatus {
+    /// Returns `true` if the TTL monitor is known to remove expired sessions.
+    pub fn expires_sessions(&self) -> bool {
+        self.index_exists && self.monitor_enabled == Some(true)
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use fred::{prelude::KeysInterface, types::Expiration};
+use tower_sessions_core::{session::Id, session_store};
+
+use crate::{chunked::derived_key, RedisStore, RedisStoreError};
+
+/// The key holding the auxiliary data stored under `name` next to the session
+/// stored under `key`.
+fn aux_key(key: &str, name: &str) -> String {
+    derived_key(key, &format!("aux:{name}"))
+}
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// Allow storing auxiliary data under the provided names next to each
+    /// session, e.g. flash messages, with [`RedisStore::put_aux`].
+    ///
+    /// Auxiliary data is kept under keys derived from the session's key,
+    /// which Redis Cluster places in the same slot, and expires on its own
+    /// schedule rather than the session's. Deleting a session deletes its
+    /// auxiliary data under every declared name in the same `DEL`, so names
+    /// must be declared up front; data stored under a name that is no longer
+    /// declared is only removed once it expires.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    /// let session_store = RedisStore::new(pool).with_aux_keys(["flash"]);
+    /// ```
+    pub fn with_aux_keys<I, K>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.aux_names = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The keys of the auxiliary data of the session stored under `key`.
+    pub(crate) fn aux_keys(&self, key: &str) -> Vec<String> {
+        self.aux_names
+            .iter()
+            .map(|name| aux_key(key, name))
+            .collect()
+    }
+
+    /// The key of the auxiliary data stored under `name` next to the session,
+    /// if the name is declared.
+    fn declared_aux_key(&self, session_id: &Id, name: &str) -> Result<String, RedisStoreError> {
+        if !self.aux_names.iter().any(|declared| declared == name) {
+            return Err(RedisStoreError::UnknownAuxKey(name.to_string()));
+        }
+        Ok(aux_key(&self.key_for(session_id), name))
+    }
+
+    /// Store auxiliary data under the provided name next to the session,
+    /// replacing any data already stored under it, for the provided TTL.
+    ///
+    /// The name must have been declared with [`RedisStore::with_aux_keys`].
+    /// The data isn't tied to the session's expiry, so choose a TTL no longer
+    /// than the data is useful for.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::session::Id;
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    ///
+    /// let _ = pool.connect();
+    /// pool.wait_for_connect().await.unwrap();
+    ///
+    /// let session_store = RedisStore::new(pool).with_aux_keys(["flash"]);
+    /// let session_id = Id::default();
+    /// session_store
+    ///     .put_aux(&session_id, "flash", b"Saved!", Duration::from_secs(60))
+    ///     .await
+    ///     .unwrap();
+    /// let flash = session_store.take_aux(&session_id, "flash").await.unwrap();
+    /// # })
+    /// ```
+    pub async fn put_aux(
+        &self,
+        session_id: &Id,
+        name: &str,
+        data: &[u8],
+        ttl: Duration,
+    ) -> session_store::Result<()> {
+        self.ensure_connected().await?;
+        let key = self.declared_aux_key(session_id, name)?;
+        let ttl_millis = i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX).max(1);
+        let _: () = self
+            .client
+            .set(key, data, Some(Expiration::PX(ttl_millis)), None, false)
+            .await
+            .map_err(RedisStoreError::Redis)?;
+        Ok(())
+    }
+
+    /// Get the auxiliary data stored under the provided name next to the
+    /// session, if any.
+    pub async fn get_aux(
+        &self,
+        session_id: &Id,
+        name: &str,
+    ) -> session_store::Result<Option<Vec<u8>>> {
+        self.ensure_connected().await?;
+        let key = self.declared_aux_key(session_id, name)?;
+        Ok(self.client.get(key).await.map_err(RedisStoreError::Redis)?)
+    }
+
+    /// Get and delete the auxiliary data stored under the provided name next
+    /// to the session, if any, as for flash messages shown once.
+    ///
+    /// This uses `GETDEL`, which requires Redis 6.2 or later.
+    pub async fn take_aux(
+        &self,
+        session_id: &Id,
+        name: &str,
+    ) -> session_store::Result<Option<Vec<u8>>> {
+        self.ensure_connected().await?;
+        let key = self.declared_aux_key(session_id, name)?;
+        Ok(self
+            .client
+            .getdel(key)
+            .await
+            .map_err(RedisStoreError::Redis)?)
+    }
+
+    /// Delete the auxiliary data stored under the provided name next to the
+    /// session, if any.
+    pub async fn delete_aux(&self, session_id: &Id, name: &str) -> session_store::Result<()> {
+        self.ensure_connected().await?;
+        let key = self.declared_aux_key(session_id, name)?;
+        let _: () = self.client.del(key).await.map_err(RedisStoreError::Redis)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use fred::{
+    prelude::KeysInterface,
+    types::{Expiration, SetOptions},
+};
+use tower_sessions_core::session::Id;
+
+use crate::{RedisStore, RedisStoreError};
+
+/// Marks a value as a manifest of chunks rather than an encoded record, which
+/// never starts with a null byte.
+const MANIFEST_MAGIC: &[u8] = b"\0tower-sessions:chunks\0";
+
+/// The value stored under a session's key when its record is split across
+/// several keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Manifest {
+    count: u32,
+    expires_at: i64,
+    /// Tells apart the chunks of successive saves, so that a save never
+    /// overwrites chunks that another save's manifest points to.
+    generation: String,
+}
+
+impl Manifest {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = MANIFEST_MAGIC.to_vec();
+        data.extend_from_slice(&self.count.to_le_bytes());
+        data.extend_from_slice(&self.expires_at.to_le_bytes());
+        data.extend_from_slice(self.generation.as_bytes());
+        data
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        let data = data.strip_prefix(MANIFEST_MAGIC)?;
+        let (count, data) = data.split_first_chunk::<4>()?;
+        let (expires_at, generation) = data.split_first_chunk::<8>()?;
+        let manifest = Self {
+            count: u32::from_le_bytes(*count),
+            expires_at: i64::from_le_bytes(*expires_at),
+            generation: std::str::from_utf8(generation).ok()?.to_string(),
+        };
+        (manifest.count > 0).then_some(manifest)
+    }
+
+    /// The keys of the chunks of the record stored under `key`.
+    pub(crate) fn chunk_keys(&self, key: &str) -> Vec<String> {
+        (0..self.count)
+            .map(|index| chunk_key(key, &self.generation, index))
+            .collect()
+    }
+}
+
+fn chunk_key(key: &str, generation: &str, index: u32) -> String {
+    derived_key(key, &format!("{generation}:{index}"))
+}
+
+/// Keys derived from a session's key embed it as a hash tag, unless it
+/// already has one, so that Redis Cluster places them in the same slot as the
+/// session.
+pub(crate) fn derived_key(key: &str, suffix: &str) -> String {
+    if has_hash_tag(key) {
+        format!("{key}:{suffix}")
+    } else {
+        format!("{{{key}}}:{suffix}")
+    }
+}
+
+fn has_hash_tag(key: &str) -> bool {
+    key.find('{')
+        .and_then(|start| key[start + 1..].find('}'))
+        .is_some_and(|len| len > 0)
+}
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// Write an encoded record, split into chunks if it's larger than
+    /// `chunk_size`, and return whether it was stored.
+    ///
+    /// Chunks are written before the manifest pointing to them, and the
+    /// chunks of the replaced record are deleted afterwards, so that loads
+    /// always find either the previous or the new record in full.
+    pub(crate) async fn set_chunked(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        chunk_size: usize,
+        expires_at: i64,
+        options: Option<SetOptions>,
+    ) -> Result<bool, RedisStoreError> {
+        let expire = || Some(Expiration::EXAT(expires_at));
+
+        let manifest = if value.len() > chunk_size {
+            let manifest = Manifest {
+                count: value.len().div_ceil(chunk_size) as u32,
+                expires_at,
+                generation: Id::default().to_string(),
+            };
+            for (chunk_key, chunk) in manifest
+                .chunk_keys(&key)
+                .into_iter()
+                .zip(value.chunks(chunk_size))
+            {
+                let _: () = self
+                    .client
+                    .set(chunk_key, chunk, expire(), None, false)
+                    .await?;
+            }
+            Some(manifest)
+        } else {
+            None
+        };
+        let value = manifest.as_ref().map_or(value, Manifest::encode);
+
+        let (stored, replaced) = match options {
+            // A session that doesn't exist yet has no chunks to replace, and
+            // `GET` can't be combined with `NX` before Redis 7.
+            Some(SetOptions::NX) => {
+                let stored: bool = self
+                    .client
+                    .set(key.as_str(), value.as_slice(), expire(), options, false)
+                    .await?;
+                (stored, None)
+            }
+            _ => {
+                let must_exist = options.is_some();
+                let replaced: Option<Vec<u8>> = self
+                    .client
+                    .set(key.as_str(), value.as_slice(), expire(), options, true)
+                    .await?;
+                (replaced.is_some() || !must_exist, replaced)
+            }
+        };
+
+        if let Some(manifest) = manifest.filter(|_| !stored) {
+            self.delete_chunks(&key, &manifest).await?;
+        }
+        if let Some(replaced) = replaced.as_deref().and_then(Manifest::decode) {
+            self.delete_chunks(&key, &replaced).await?;
+        }
+
+        Ok(stored)
+    }
+
+    /// Reassemble the record stored under `key` if its value is a manifest.
+    ///
+    /// Returns `None` if any chunk is missing, e.g. because it was evicted.
+    pub(crate) async fn resolve_chunks(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, RedisStoreError> {
+        let Some(manifest) = Manifest::decode(&data) else {
+            return Ok(Some(data));
+        };
+
+        let chunks: Vec<Option<Vec<u8>>> = self.client.mget(manifest.chunk_keys(key)).await?;
+        Ok(chunks
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .map(|chunks| chunks.concat()))
+    }
+
+    /// Delete the record stored under `key` along with its chunks.
+    pub(crate) async fn delete_chunked(&self, key: String) -> Result<(), RedisStoreError> {
+        let data: Option<Vec<u8>> = self.client.getdel(key.as_str()).await?;
+        if let Some(manifest) = data.as_deref().and_then(Manifest::decode) {
+            self.delete_chunks(&key, &manifest).await?;
+        }
+        Ok(())
+    }
+
+    /// Set the expiry of the record stored under `key` and of its chunks.
+    pub(crate) async fn expire_chunked(
+        &self,
+        key: String,
+        expires_at: i64,
+    ) -> Result<bool, RedisStoreError> {
+        let data: Option<Vec<u8>> = self.client.get(key.as_str()).await?;
+        let Some(data) = data else {
+            return Ok(false);
+        };
+
+        if let Some(manifest) = Manifest::decode(&data) {
+            for chunk_key in manifest.chunk_keys(&key) {
+                let _: bool = self.client.expire_at(chunk_key, expires_at, None).await?;
+            }
+        }
+        Ok(self.client.expire_at(key, expires_at, None).await?)
+    }
+
+    async fn delete_chunks(&self, key: &str, manifest: &Manifest) -> Result<(), RedisStoreError> {
+        let _: () = self.client.del(manifest.chunk_keys(key)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use fred::prelude::KeysInterface;
+use serde::Deserialize;
+use tower_sessions_ext::StoreOptionsConfig;
+
+use crate::RedisStore;
+
+/// The settings of a [`RedisStore`], as read from a configuration file.
+///
+/// The options shared by every store, such as the `namespace` prefixing
+/// session keys, are written next to these settings. Settings that are left
+/// out keep the store's defaults.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_redis_store::RedisStoreConfig;
+///
+/// let config: RedisStoreConfig =
+///     serde_json::from_str(r#"{ "key_prefix": "prod:", "chunk_size": 65536 }"#).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RedisStoreConfig {
+    /// The options shared by every store.
+    #[serde(flatten)]
+    pub options: StoreOptionsConfig,
+
+    /// Passed to [`RedisStore::with_key_prefix`], replacing the prefix set by
+    /// the namespace.
+    pub key_prefix: Option<String>,
+
+    /// Passed to [`RedisStore::with_database`].
+    pub database: Option<u8>,
+
+    /// Whether to call [`RedisStore::with_lazy_connect`].
+    pub lazy_connect: bool,
+
+    /// Whether to call [`RedisStore::with_express_session_format`].
+    #[cfg(feature = "express-session")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "express-session")))]
+    pub express_session_format: bool,
+
+    /// Passed to [`RedisStore::with_chunking`].
+    pub chunk_size: Option<usize>,
+
+    /// Passed to [`RedisStore::with_metadata_side_key`].
+    pub metadata_keys: Option<Vec<String>>,
+
+    /// Passed to [`RedisStore::with_aux_keys`].
+    pub aux_keys: Option<Vec<String>>,
+
+    /// Whether to call [`RedisStore::with_wrong_type_recovery`].
+    pub wrong_type_recovery: bool,
+
+    /// Whether to call [`RedisStore::with_key_expiry_on_load`].
+    pub key_expiry_on_load: bool,
+
+    /// The maximum age, in milliseconds, passed to
+    /// [`RedisStore::with_stale_while_revalidate`] along with
+    /// `local_cache_capacity`.
+    pub stale_max_age_ms: Option<u64>,
+
+    /// The capacity passed to [`RedisStore::with_stale_while_revalidate`]
+    /// along with `stale_max_age_ms`.
+    pub local_cache_capacity: Option<usize>,
+}
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// Create a new Redis store with the provided client, configured with the
+    /// provided settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore, RedisStoreConfig};
+    ///
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    /// let config: RedisStoreConfig =
+    ///     serde_json::from_str(r#"{ "namespace": "sessions", "lazy_connect": true }"#).unwrap();
+    /// let session_store = RedisStore::try_from_config(pool, &config).unwrap();
+    /// ```
+    pub fn try_from_config(client: C, config: &RedisStoreConfig) -> Result<Self, String> {
+        let mut store = config.options.apply(Self::new(client))?;
+        if let Some(key_prefix) = &config.key_prefix {
+            store = store.with_key_prefix(key_prefix);
+        }
+        if let Some(database) = config.database {
+            store = store.with_database(database)?;
+        }
+        if config.lazy_connect {
+            store = store.with_lazy_connect();
+        }
+        #[cfg(feature = "express-session")]
+        if config.express_session_format {
+            store = store.with_express_session_format();
+        }
+        if let Some(chunk_size) = config.chunk_size {
+            store = store.with_chunking(chunk_size);
+        }
+        if let Some(metadata_keys) = &config.metadata_keys {
+            store = store.with_metadata_side_key(metadata_keys.iter().cloned());
+        }
+        if let Some(aux_keys) = &config.aux_keys {
+            store = store.with_aux_keys(aux_keys.iter().cloned());
+        }
+        if config.wrong_type_recovery {
+            store = store.with_wrong_type_recovery();
+        }
+        if config.key_expiry_on_load {
+            store = store.with_key_expiry_on_load();
+        }
+        match (config.stale_max_age_ms, config.local_cache_capacity) {
+            (Some(max_age_ms), Some(capacity)) => {
+                store =
+                    store.with_stale_while_revalidate(Duration::from_millis(max_age_ms), capacity);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(
+                    "`stale_max_age_ms` and `local_cache_capacity` must be set together"
+                        .to_string(),
+                )
+            }
+        }
+        Ok(store)
+    }
+}
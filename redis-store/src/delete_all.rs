@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use fred::{prelude::KeysInterface, types::Key};
+use tower_sessions_core::{session::Id, session_store};
+
+use crate::{escape_glob, RedisStore, RedisStoreError};
+
+/// The number of keys requested per `SCAN` page, and so the most keys removed
+/// by a single `UNLINK`, when deleting every session.
+const DELETE_ALL_PAGE_SIZE: u32 = 1_000;
+
+/// Returns `true` if the key is a session's key under the provided stem, or a
+/// key derived from one, such as a chunk or side key.
+fn is_session_key(stem: &str, key: &str) -> bool {
+    let is_id = |id: &str| Id::from_str(id).is_ok();
+
+    // Derived keys embed the session's key as a hash tag, unless the stem
+    // already has one, in which case they extend it.
+    if let Some(tagged) = key.strip_prefix('{').and_then(|key| key.strip_prefix(stem)) {
+        if tagged
+            .split_once("}:")
+            .is_some_and(|(session_id, _)| is_id(session_id))
+        {
+            return true;
+        }
+    }
+    key.strip_prefix(stem).is_some_and(|rest| {
+        let session_id = rest
+            .split_once(':')
+            .map_or(rest, |(session_id, _)| session_id);
+        is_id(session_id)
+    })
+}
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// Delete every session of the store, returning the number of keys
+    /// removed, including chunk and side keys.
+    ///
+    /// This is meant for invalidating all sessions at once, e.g. after a
+    /// security incident. Keys are found by scanning for the store's key
+    /// prefix and removed in batches with `UNLINK`, which frees their memory
+    /// in the background, so Redis keeps serving other clients in between;
+    /// keys that aren't sessions are left alone. Sessions created while the
+    /// scan runs may survive it. Redis Cluster isn't supported, as each node
+    /// would need to be scanned. Sessions cached in process by
+    /// [`RedisStore::with_stale_while_revalidate`] are dropped too, but those
+    /// cached by other processes are served until they are refreshed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    ///
+    /// let _ = pool.connect();
+    /// pool.wait_for_connect().await.unwrap();
+    ///
+    /// let session_store = RedisStore::new(pool).with_key_prefix("sessions:");
+    /// let removed = session_store.delete_all().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn delete_all(&self) -> session_store::Result<u64> {
+        self.ensure_connected().await?;
+        if self.client.is_clustered() {
+            return Err(session_store::Error::Backend(
+                "Deleting all sessions isn't supported with Redis Cluster".to_string(),
+            ));
+        }
+
+        if let Some(local_cache) = &self.local_cache {
+            local_cache.clear();
+        }
+
+        let stem = self.key_stem();
+        let mut patterns = vec![format!("{}*", escape_glob(&stem))];
+        // Without a prefix, the first pattern already matches every key.
+        if !stem.is_empty() {
+            patterns.push(format!("\\{{{}*", escape_glob(&stem)));
+        }
+
+        let mut removed = 0;
+        for pattern in patterns {
+            let mut cursor = "0".to_string();
+            loop {
+                let (next_cursor, keys): (String, Vec<Key>) = self
+                    .client
+                    .scan_page(cursor, pattern.clone(), Some(DELETE_ALL_PAGE_SIZE), None)
+                    .await
+                    .map_err(RedisStoreError::Redis)?;
+
+                let keys: Vec<Key> = keys
+                    .into_iter()
+                    .filter(|key| key.as_str().is_some_and(|key| is_session_key(&stem, key)))
+                    .collect();
+                if !keys.is_empty() {
+                    let unlinked: u64 = self
+                        .client
+                        .unlink(keys)
+                        .await
+                        .map_err(RedisStoreError::Redis)?;
+                    removed += unlinked;
+                }
+
+                if next_cursor == "0" {
+                    break;
+                }
+                cursor = next_cursor;
+            }
+        }
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, OnceLock},
+};
+
+use async_trait::async_trait;
+use fred::{prelude::KeysInterface, types::SetOptions};
+use tokio::sync::{mpsc, oneshot};
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, SessionStore,
+};
+use tower_sessions_ext::warn;
+
+use crate::RedisStore;
+
+/// A write to be applied to the durable store by the writer task.
+#[derive(Debug)]
+enum DurableWrite {
+    Save(Record),
+    Delete(Id, oneshot::Sender<session_store::Result<()>>),
+}
+
+/// A Redis store backed by a durable store that sessions are written behind
+/// to, so that they survive Redis being flushed or restarted.
+///
+/// Creates and saves are applied to Redis and then queued for the durable
+/// store, which is written to by a single background task, so requests only
+/// wait on Redis. Loads are served from Redis, falling back to the durable
+/// store on a miss and copying the session found there back into Redis.
+/// Deletes go through the same queue as saves, so that they are applied after
+/// any save still pending for the session, and are awaited before the session
+/// is removed from Redis.
+///
+/// Sessions written to Redis but still queued are lost if the application
+/// stops, and failed durable writes are only logged with `tracing::warn!`.
+/// The durable store's `save` must insert sessions that do not exist yet, as
+/// new sessions are written with `save` to keep the ID assigned by Redis.
+///
+/// The writer task is spawned on the Tokio runtime of the first write and
+/// exits once every clone of the store has been dropped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions::MemoryStore;
+/// use tower_sessions_redis_store::{fred::prelude::*, RedisDurableStore, RedisStore};
+///
+/// # tokio_test::block_on(async {
+/// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+///
+/// let _ = pool.connect();
+/// pool.wait_for_connect().await.unwrap();
+///
+/// let session_store = RedisDurableStore::new(RedisStore::new(pool), MemoryStore::default());
+/// # })
+/// ```
+#[derive(Debug)]
+pub struct RedisDurableStore<C: KeysInterface + Send + Sync, D> {
+    redis: RedisStore<C>,
+    durable: D,
+    queue_capacity: usize,
+    sender: Arc<OnceLock<mpsc::Sender<DurableWrite>>>,
+}
+
+impl<C, D> Clone for RedisDurableStore<C, D>
+where
+    C: KeysInterface + Send + Sync + Clone,
+    D: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            redis: self.redis.clone(),
+            durable: self.durable.clone(),
+            queue_capacity: self.queue_capacity,
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<C, D> RedisDurableStore<C, D>
+where
+    C: KeysInterface + Send + Sync + Debug + 'static,
+    D: SessionStore + Clone,
+{
+    /// Create a new store from the provided Redis store and durable store.
+    pub fn new(redis: RedisStore<C>, durable: D) -> Self {
+        Self {
+            redis,
+            durable,
+            queue_capacity: 1024,
+            sender: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Set the number of writes that may be queued for the durable store.
+    ///
+    /// Once the queue is full, creates and saves wait for room, so that
+    /// writes are slowed down rather than dropped when the durable store falls
+    /// behind. Defaults to 1024.
+    pub fn with_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity.max(1);
+        self
+    }
+
+    async fn enqueue(&self, write: DurableWrite) -> session_store::Result<()> {
+        let sender = self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel(self.queue_capacity);
+            tokio::spawn(run(self.durable.clone(), receiver));
+            sender
+        });
+
+        sender.send(write).await.map_err(|_| writer_stopped())
+    }
+}
+
+async fn run<D: SessionStore>(durable: D, mut receiver: mpsc::Receiver<DurableWrite>) {
+    while let Some(write) = receiver.recv().await {
+        match write {
+            DurableWrite::Save(record) => {
+                if let Err(err) = durable.save(&record).await {
+                    warn!(
+                        session.id = %record.id,
+                        error = %err,
+                        "failed to write session to durable store"
+                    );
+                }
+            }
+            DurableWrite::Delete(session_id, reply) => {
+                let _ = reply.send(durable.delete(&session_id).await);
+            }
+        }
+    }
+}
+
+fn writer_stopped() -> session_store::Error {
+    session_store::Error::Backend("Durable store writer task stopped".to_string())
+}
+
+#[async_trait]
+impl<C, D> SessionStore for RedisDurableStore<C, D>
+where
+    C: KeysInterface + Send + Sync + Debug + 'static,
+    D: SessionStore + Clone,
+{
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        self.redis.create(record).await?;
+        self.enqueue(DurableWrite::Save(record.clone())).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.redis.save(record).await?;
+        self.enqueue(DurableWrite::Save(record.clone())).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        if let Some(record) = self.redis.load(session_id).await? {
+            return Ok(Some(record));
+        }
+
+        let Some(record) = self.durable.load(session_id).await? else {
+            return Ok(None);
+        };
+
+        // Don't overwrite a session written to Redis since the miss.
+        self.redis.ensure_connected().await?;
+        self.redis
+            .save_with_options(&record, Some(SetOptions::NX))
+            .await?;
+        Ok(Some(record))
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        // Delete from the durable store first, so that a concurrent load can't
+        // copy the session back into Redis.
+        let (reply, deleted) = oneshot::channel();
+        self.enqueue(DurableWrite::Delete(*session_id, reply))
+            .await?;
+        deleted.await.map_err(|_| writer_stopped())??;
+
+        self.redis.delete(session_id).await
+    }
+}
@@ -0,0 +1,88 @@
+use std::{fmt::Debug, str::FromStr};
+
+use async_trait::async_trait;
+use fred::{prelude::KeysInterface, types::Key};
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store,
+};
+use tower_sessions_ext::ExportSessions;
+
+use crate::{escape_glob, RedisStore, RedisStoreError};
+
+/// The number of keys requested per `SCAN` page when exporting sessions.
+const EXPORT_SCAN_PAGE_SIZE: u32 = 1_000;
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// The IDs of the sessions stored under the store's key prefix, following
+    /// `after`, ordered by their string form.
+    async fn scan_session_ids(&self, after: Option<&str>) -> session_store::Result<Vec<Id>> {
+        let stem = self.key_stem();
+        let pattern = format!("{}*", escape_glob(&stem));
+
+        let mut session_ids = Vec::new();
+        let mut cursor = "0".to_string();
+        loop {
+            let (next_cursor, keys): (String, Vec<Key>) = self
+                .client
+                .scan_page(cursor, pattern.clone(), Some(EXPORT_SCAN_PAGE_SIZE), None)
+                .await
+                .map_err(RedisStoreError::Redis)?;
+
+            // Chunk, side, and auxiliary keys extend the session's key, so
+            // only keys ending in the ID are sessions' keys.
+            session_ids.extend(keys.iter().filter_map(|key| {
+                let session_id = key.as_str()?.strip_prefix(&stem)?;
+                if after.is_some_and(|after| session_id <= after) {
+                    return None;
+                }
+                let id = Id::from_str(session_id).ok()?;
+                Some((session_id.to_string(), id))
+            }));
+
+            if next_cursor == "0" {
+                break;
+            }
+            cursor = next_cursor;
+        }
+
+        // `SCAN` may return a key more than once.
+        session_ids.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        session_ids.dedup_by(|(a, _), (b, _)| a == b);
+        Ok(session_ids.into_iter().map(|(_, id)| id).collect())
+    }
+}
+
+#[async_trait]
+impl<C> ExportSessions for RedisStore<C>
+where
+    C: KeysInterface + Send + Sync + Debug + 'static,
+{
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        // Redis has no ordering of keys, so each page scans every key under
+        // the prefix, and a cluster would need each of its nodes scanned.
+        self.ensure_connected().await?;
+        if self.client.is_clustered() {
+            return Err(session_store::Error::Backend(
+                "Exporting sessions isn't supported with Redis Cluster".to_string(),
+            ));
+        }
+
+        let after = after.map(|id| id.to_string());
+        let session_ids = self.scan_session_ids(after.as_deref()).await?;
+
+        // Sessions expiring or deleted since the scan are passed over, so
+        // that a page is only short once no sessions follow it.
+        let mut records = Vec::new();
+        for session_id in session_ids {
+            if records.len() >= limit as usize {
+                break;
+            }
+            if let Some(record) = self.load_from_redis(&session_id).await? {
+                records.push(record);
+            }
+        }
+
+        Ok(records)
+    }
+}
@@ -0,0 +1,78 @@
+//! Records in the layout written by `connect-redis` for `express-session`.
+//!
+//! A session is a JSON object holding the session data at the top level
+//! alongside a `cookie` object, e.g.:
+//!
+//! ```json
+//! {
+//!   "cookie": {
+//!     "originalMaxAge": null,
+//!     "expires": "2024-01-01T00:00:00.000Z",
+//!     "httpOnly": true,
+//!     "path": "/"
+//!   },
+//!   "user_id": 42
+//! }
+//! ```
+use serde_json::{Map, Value};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tower_sessions_core::session::{Id, Record};
+
+use crate::RedisStoreError;
+
+const COOKIE_KEY: &str = "cookie";
+
+/// The expiry `connect-redis` applies to sessions without an expiring cookie.
+const DEFAULT_TTL: Duration = Duration::days(1);
+
+pub(crate) fn encode(record: &Record) -> Result<Vec<u8>, RedisStoreError> {
+    let expires = record
+        .expiry_date
+        .format(&Rfc3339)
+        .map_err(|err| RedisStoreError::ExpressSessionEncode(err.to_string()))?;
+
+    let mut object: Map<String, Value> = record
+        .data
+        .iter()
+        .filter(|(key, _)| key.as_str() != COOKIE_KEY)
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    object.insert(
+        COOKIE_KEY.to_string(),
+        serde_json::json!({
+            "originalMaxAge": null,
+            "expires": expires,
+            "httpOnly": true,
+            "path": "/",
+        }),
+    );
+
+    serde_json::to_vec(&object)
+        .map_err(|err| RedisStoreError::ExpressSessionEncode(err.to_string()))
+}
+
+pub(crate) fn decode(session_id: &Id, data: &[u8]) -> Result<Record, RedisStoreError> {
+    let mut object: Map<String, Value> = serde_json::from_slice(data)
+        .map_err(|err| RedisStoreError::ExpressSessionDecode(err.to_string()))?;
+
+    let expiry_date = match object
+        .remove(COOKIE_KEY)
+        .as_ref()
+        .and_then(|cookie| cookie.get("expires"))
+    {
+        Some(Value::String(expires)) => OffsetDateTime::parse(expires, &Rfc3339)
+            .map_err(|err| RedisStoreError::ExpressSessionDecode(err.to_string()))?,
+        Some(Value::Null) | None => OffsetDateTime::now_utc() + DEFAULT_TTL,
+        Some(other) => {
+            return Err(RedisStoreError::ExpressSessionDecode(format!(
+                "expected `cookie.expires` to be a string, found {other}"
+            )))
+        }
+    };
+
+    Ok(Record {
+        id: *session_id,
+        data: object.into_iter().collect(),
+        expiry_date,
+    })
+}
@@ -0,0 +1,70 @@
+use fred::{error::Error, prelude::KeysInterface};
+use time::OffsetDateTime;
+use tower_sessions_core::session::Record;
+
+use crate::{RedisStore, RedisStoreError};
+
+/// Deletes the sessions whose keys expire by `ARGV[1]`, in milliseconds since
+/// the Unix epoch, along with their side keys.
+///
+/// `KEYS` holds pairs of a session's key and its side key. The sessions'
+/// keys are returned along with the values they held, so that the chunks of
+/// chunked records can be deleted afterwards. Keys are checked and deleted
+/// in one atomic step, so that a session saved or touched during the sweep
+/// is never deleted.
+pub(crate) const SWEEP_SCRIPT: &str = r#"
+local swept = {}
+for i = 1, #KEYS, 2 do
+  local key = KEYS[i]
+  local expires_at = redis.call('PEXPIRETIME', key)
+  if expires_at > 0 and expires_at <= tonumber(ARGV[1])
+      and redis.call('TYPE', key).ok == 'string' then
+    table.insert(swept, key)
+    table.insert(swept, redis.call('GETDEL', key))
+    redis.call('DEL', KEYS[i + 1])
+  end
+end
+return swept
+"#;
+
+/// Take the expiry date of a loaded record from its key, which `touch`
+/// extends without rewriting the record.
+///
+/// `EXAT` has a resolution of seconds, so an expiry date stored with the
+/// record is kept if the key expires within the same second.
+pub(crate) fn reconcile_expiry_date(record: &mut Record, key_expiry_date: Option<OffsetDateTime>) {
+    if let Some(key_expiry_date) = key_expiry_date {
+        if key_expiry_date.unix_timestamp() != record.expiry_date.unix_timestamp() {
+            record.expiry_date = key_expiry_date;
+        }
+    }
+}
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// The expiry date of the key, as last set by a save or a touch, or
+    /// `None` if the key is missing or doesn't expire.
+    ///
+    /// Servers predating `PEXPIRETIME`, added in Redis 7.0, report `None` as
+    /// well, leaving touched sessions with the expiry date they were saved
+    /// with.
+    pub(crate) async fn key_expiry_date(
+        &self,
+        key: &str,
+    ) -> Result<Option<OffsetDateTime>, RedisStoreError> {
+        match self.client.pexpire_time::<i64, _>(key).await {
+            Ok(expires_at) if expires_at > 0 => Ok(OffsetDateTime::from_unix_timestamp_nanos(
+                i128::from(expires_at) * 1_000_000,
+            )
+            .ok()),
+            Ok(_) => Ok(None),
+            Err(err) if is_unknown_command(&err) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+fn is_unknown_command(err: &Error) -> bool {
+    err.details()
+        .to_ascii_lowercase()
+        .contains("unknown command")
+}
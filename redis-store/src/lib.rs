@@ -1,16 +1,48 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, str::FromStr, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 pub use fred;
 use fred::{
+    error::ErrorKind,
+    interfaces::LuaInterface,
     prelude::KeysInterface,
-    types::{Expiration, SetOptions},
+    types::{Expiration, InfoKind, Key, SetOptions},
 };
 use time::OffsetDateTime;
+use tokio::sync::OnceCell;
 use tower_sessions_core::{
     session::{Id, Record},
-    session_store, SessionStore,
+    session_store::{self, ExpiredDeletion},
+    SessionStore,
 };
+use tower_sessions_ext::{
+    transient_backend_error, DecodeFailurePolicy, ErrorTag, ExpiryEnforcement, IdCollisionPolicy,
+    SlowOpTracer, StoreOptions, TouchSessionStore, TtlPolicy,
+};
+
+use self::{
+    chunked::Manifest,
+    side_key::side_key,
+    stale::{LocalCache, Lookup},
+};
+pub use self::{
+    config::RedisStoreConfig, durable::RedisDurableStore, server::RedisServer,
+    wrong_type::is_key_collision,
+};
+
+mod auxiliary;
+mod chunked;
+mod config;
+mod delete_all;
+mod durable;
+mod export;
+#[cfg(feature = "express-session")]
+mod express_session;
+mod key_expiry;
+mod server;
+mod side_key;
+mod stale;
+mod wrong_type;
 
 #[derive(Debug, thiserror::Error)]
 pub enum RedisStoreError {
@@ -22,22 +54,132 @@ pub enum RedisStoreError {
 
     #[error(transparent)]
     Encode(#[from] rmp_serde::encode::Error),
+
+    /// A session's key holds a value of another type than the store writes,
+    /// which usually means that another application uses the same keys.
+    #[error(
+        "Session key holds a value that isn't a session: {0}. Another application may be writing \
+         to the same keys; give sessions a distinct prefix with `RedisStore::with_key_prefix`, \
+         use a separate database, or delete such values automatically with \
+         `RedisStore::with_wrong_type_recovery`"
+    )]
+    WrongType(String),
+
+    /// Auxiliary data was accessed under a name that wasn't declared with
+    /// [`RedisStore::with_aux_keys`], so it wouldn't be deleted along with
+    /// the session.
+    #[error(
+        "Auxiliary data name `{0}` isn't declared; declare it with `RedisStore::with_aux_keys`"
+    )]
+    UnknownAuxKey(String),
+
+    /// A session was created with an expiry date that has passed, so that
+    /// its key would expire as soon as it was written.
+    #[error(
+        "Session expired at {0} before it could be created; refusing to write a key that would \
+         expire immediately"
+    )]
+    AlreadyExpired(OffsetDateTime),
+
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonEncode(serde_json::Error),
+
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonDecode(serde_json::Error),
+
+    #[cfg(feature = "express-session")]
+    #[error("Failed to encode express-session record: {0}")]
+    ExpressSessionEncode(String),
+
+    #[cfg(feature = "express-session")]
+    #[error("Failed to decode express-session record: {0}")]
+    ExpressSessionDecode(String),
+}
+
+impl RedisStoreError {
+    /// Returns `true` if the operation may succeed if retried, e.g. after a
+    /// connection failure, timeout, or cluster reconfiguration.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RedisStoreError::Redis(err) => matches!(
+                err.kind(),
+                ErrorKind::IO
+                    | ErrorKind::Timeout
+                    | ErrorKind::Canceled
+                    | ErrorKind::Backpressure
+                    | ErrorKind::Cluster
+            ),
+            _ => false,
+        }
+    }
 }
 
 impl From<RedisStoreError> for session_store::Error {
     fn from(err: RedisStoreError) -> Self {
         match err {
+            RedisStoreError::Redis(_) if err.is_transient() => transient_backend_error(err),
             RedisStoreError::Redis(inner) => session_store::Error::Backend(inner.to_string()),
+            RedisStoreError::WrongType(_) => ErrorTag::KeyCollision.backend_error(err),
+            RedisStoreError::UnknownAuxKey(_) | RedisStoreError::AlreadyExpired(_) => {
+                session_store::Error::Backend(err.to_string())
+            }
             RedisStoreError::Decode(inner) => session_store::Error::Decode(inner.to_string()),
             RedisStoreError::Encode(inner) => session_store::Error::Encode(inner.to_string()),
+            #[cfg(feature = "json")]
+            RedisStoreError::JsonEncode(inner) => session_store::Error::Encode(inner.to_string()),
+            #[cfg(feature = "json")]
+            RedisStoreError::JsonDecode(inner) => session_store::Error::Decode(inner.to_string()),
+            #[cfg(feature = "express-session")]
+            RedisStoreError::ExpressSessionEncode(_) => {
+                session_store::Error::Encode(err.to_string())
+            }
+            #[cfg(feature = "express-session")]
+            RedisStoreError::ExpressSessionDecode(_) => {
+                session_store::Error::Decode(err.to_string())
+            }
         }
     }
 }
 
+/// The layout of session records stored in Redis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RecordFormat {
+    /// Records are serialized with MessagePack, or JSON with the `json`
+    /// feature, under the bare session ID.
+    #[default]
+    Native,
+
+    /// Records are stored as `connect-redis` does for `express-session`.
+    #[cfg(feature = "express-session")]
+    ExpressSession,
+}
+
 /// A Redis session store.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct RedisStore<C: KeysInterface + Send + Sync> {
     client: C,
+    format: RecordFormat,
+    id_collision_policy: IdCollisionPolicy,
+    decode_failure_policy: DecodeFailurePolicy,
+    slow_ops: SlowOpTracer,
+    expiry_enforcement: ExpiryEnforcement,
+    ttl_policy: TtlPolicy,
+    key_prefix: String,
+    chunk_size: Option<usize>,
+    metadata_keys: Option<Vec<String>>,
+    aux_names: Vec<String>,
+    wrong_type_recovery: bool,
+    key_expiry_on_load: bool,
+    lazy_connection: Option<Arc<OnceCell<()>>>,
+    local_cache: Option<LocalCache>,
+}
+
+impl<C: KeysInterface + Send + Sync + Default> Default for RedisStore<C> {
+    fn default() -> Self {
+        Self::new(C::default())
+    }
 }
 
 impl<C: KeysInterface + Send + Sync> RedisStore<C> {
@@ -58,31 +200,597 @@ impl<C: KeysInterface + Send + Sync> RedisStore<C> {
     /// })
     /// ```
     pub fn new(client: C) -> Self {
-        Self { client }
+        Self {
+            client,
+            format: RecordFormat::default(),
+            id_collision_policy: IdCollisionPolicy::default(),
+            decode_failure_policy: DecodeFailurePolicy::default(),
+            slow_ops: SlowOpTracer::new("redis"),
+            expiry_enforcement: ExpiryEnforcement::BackendOnly,
+            ttl_policy: TtlPolicy::default(),
+            key_prefix: String::new(),
+            chunk_size: None,
+            metadata_keys: None,
+            aux_names: Vec::new(),
+            wrong_type_recovery: false,
+            key_expiry_on_load: false,
+            lazy_connection: None,
+            local_cache: None,
+        }
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    /// Set how `load` responds to a session whose stored data can't be
+    /// decoded, e.g. after the serialization format has changed.
+    pub fn with_decode_failure_policy(
+        mut self,
+        decode_failure_policy: DecodeFailurePolicy,
+    ) -> Self {
+        self.decode_failure_policy = decode_failure_policy;
+        self
+    }
+
+    /// Log operations taking longer than the provided threshold with
+    /// `tracing::warn!`.
+    pub fn with_slow_op_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_ops = self.slow_ops.with_threshold(threshold);
+        self
+    }
+
+    /// Set whether `load` checks the expiry date of sessions in addition to
+    /// Redis key expiry, which has a resolution of one second.
+    ///
+    /// The expiry date checked is the one the session was saved with, so
+    /// sessions extended with `touch` are only kept past it with
+    /// [`RedisStore::with_key_expiry_on_load`].
+    ///
+    /// Defaults to [`ExpiryEnforcement::BackendOnly`].
+    pub fn with_expiry_enforcement(mut self, expiry_enforcement: ExpiryEnforcement) -> Self {
+        self.expiry_enforcement = expiry_enforcement;
+        self
+    }
+
+    /// Load sessions with the expiry date of their key rather than the one
+    /// they were saved with.
+    ///
+    /// `touch` only extends the expiry of a session's key, so this is needed
+    /// for touched sessions to load with their new expiry date, and to be
+    /// kept past the expiry date they were saved with under
+    /// [`ExpiryEnforcement::Strict`]. Each load then also reads the key's
+    /// expiry with `PEXPIRETIME`, which requires Redis 7.0 or later; older
+    /// servers report the saved expiry date.
+    pub fn with_key_expiry_on_load(mut self) -> Self {
+        self.key_expiry_on_load = true;
+        self
+    }
+
+    /// Set the policy adjusting the expiry dates sessions are stored with,
+    /// which sets the `EXAT` of their keys.
+    pub fn with_ttl_policy(mut self, ttl_policy: TtlPolicy) -> Self {
+        self.ttl_policy = ttl_policy;
+        self
+    }
+
+    /// Prefix the keys of sessions with the provided string, e.g. `prod:`.
+    ///
+    /// This separates the sessions of environments sharing a Redis instance
+    /// or cluster, so that a store configured for one environment can't load
+    /// another's sessions.
+    pub fn with_key_prefix(mut self, key_prefix: impl Into<String>) -> Self {
+        self.key_prefix = key_prefix.into();
+        self
+    }
+
+    /// Require the client to be configured to use the provided logical
+    /// database.
+    ///
+    /// Selecting a database per environment is done in the client's
+    /// configuration; this guards against a store for one environment being
+    /// given a client for another. Redis Cluster only supports database 0, so
+    /// use [`RedisStore::with_key_prefix`] to separate environments there.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// let config = Config::from_url("redis://127.0.0.1:6379/2").unwrap();
+    /// let pool = Pool::new(config, None, None, None, 6).unwrap();
+    /// let session_store = RedisStore::new(pool).with_database(2).unwrap();
+    /// ```
+    pub fn with_database(self, database: u8) -> Result<Self, String> {
+        if database != 0 && self.client.is_clustered() {
+            return Err(format!(
+                "Redis Cluster only supports database 0, not database {database}. Use a key \
+                 prefix to separate sessions instead."
+            ));
+        }
+
+        let configured = self.client.client_config().database.unwrap_or(0);
+        if configured != database {
+            return Err(format!(
+                "Expected the Redis client to use database {database}, but it is configured to \
+                 use database {configured}."
+            ));
+        }
+
+        Ok(self)
+    }
+
+    /// Detect which Redis-compatible server the client is connected to.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    /// pool.init().await.unwrap();
+    ///
+    /// let session_store = RedisStore::new(pool);
+    /// println!("Using {:?}", session_store.detect_server().await.unwrap());
+    /// # })
+    /// ```
+    pub async fn detect_server(&self) -> session_store::Result<RedisServer> {
+        self.ensure_connected().await?;
+        let info: String = self
+            .client
+            .info(Some(InfoKind::Server))
+            .await
+            .map_err(RedisStoreError::Redis)?;
+        Ok(RedisServer::from_info(&info))
+    }
+
+    /// Connect the client on the first operation rather than requiring it to
+    /// be connected beforehand.
+    ///
+    /// This lets an application start while Redis is briefly unavailable;
+    /// session operations fail until a connection succeeds, and each attempt
+    /// retries connecting. Use [`RedisStore::warm_up`] to connect ahead of the
+    /// first request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    /// let session_store = RedisStore::new(pool).with_lazy_connect();
+    /// ```
+    pub fn with_lazy_connect(mut self) -> Self {
+        self.lazy_connection = Some(Arc::new(OnceCell::new()));
+        self
+    }
+
+    /// Connect a client set up with [`RedisStore::with_lazy_connect`] now, or
+    /// otherwise wait for the client to be connected.
+    pub async fn warm_up(&self) -> session_store::Result<()> {
+        match &self.lazy_connection {
+            Some(_) => self.ensure_connected().await,
+            None => Ok(self
+                .client
+                .wait_for_connect()
+                .await
+                .map_err(RedisStoreError::Redis)?),
+        }
+    }
+
+    pub(crate) async fn ensure_connected(&self) -> session_store::Result<()> {
+        let Some(lazy_connection) = &self.lazy_connection else {
+            return Ok(());
+        };
+
+        lazy_connection
+            .get_or_try_init(|| async {
+                let _connection_task = self.client.init().await.map_err(RedisStoreError::Redis)?;
+                Ok::<_, session_store::Error>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Store sessions in the layout used by `express-session` with
+    /// `connect-redis`.
+    ///
+    /// Records are written as JSON under `sess:<id>` keys, with the session
+    /// data at the top level and the expiry date in `cookie.expires`, so that a
+    /// Node application sharing the Redis instance can read and write the same
+    /// sessions. The Node application must generate session IDs compatible
+    /// with `tower-sessions`, e.g. via the `genid` option, and the `cookie` key
+    /// of the session data is reserved.
+    #[cfg(feature = "express-session")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "express-session")))]
+    pub fn with_express_session_format(mut self) -> Self {
+        self.format = RecordFormat::ExpressSession;
+        self
+    }
+
+    /// Split records larger than `chunk_size` bytes across several keys.
+    ///
+    /// Sessions holding more data than is practical to store in a single
+    /// value, e.g. beyond a proxy's or managed service's value size limit,
+    /// are stored as chunks under keys derived from the session's key, with
+    /// the session's key holding a small manifest listing them. Chunks expire
+    /// along with the session and are deleted when it is replaced or
+    /// deleted. A session whose chunks were evicted loads as missing.
+    ///
+    /// Saves replace records with `SET`'s `GET` option, which requires Redis
+    /// 6.2 or later, and deletes and touches need an extra round trip to find
+    /// the chunks, so this is off by default. Sessions stored in chunks are loaded even with
+    /// chunking turned off, but their chunks are then only removed once they
+    /// expire. This has no effect with the `express-session` format, which
+    /// Node applications must be able to read.
+    pub fn with_chunking(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size.max(1));
+        self
+    }
+
+    /// Store the session's expiry date and the provided session data keys,
+    /// e.g. a "last seen" timestamp, under a small secondary key, and only
+    /// rewrite the rest of the session when it changes.
+    ///
+    /// Sessions whose saves mostly extend their expiry or bump a few small
+    /// values are then saved by writing the secondary key and extending the
+    /// expiry of the session's key, rather than rewriting all of its data.
+    /// Each save reads the secondary key first to tell whether the rest of
+    /// the session changed, and each load fetches both keys in one `MGET`.
+    ///
+    /// Sessions stored before this was turned on are loaded as they are, and
+    /// split on their next save. If the secondary key is evicted, the
+    /// session loads without the metadata keys and with the expiry date of
+    /// its last full write. This has no effect with the `express-session`
+    /// format, which Node applications must be able to read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    /// let session_store = RedisStore::new(pool).with_metadata_side_key(["last_seen"]);
+    /// ```
+    pub fn with_metadata_side_key<I, K>(mut self, metadata_keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.metadata_keys = Some(metadata_keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Delete values that aren't sessions found under session keys, and retry
+    /// the operation, instead of failing with
+    /// [`RedisStoreError::WrongType`].
+    ///
+    /// Such values are usually written by another application sharing the
+    /// database without a distinct key prefix, so deleting them may break
+    /// that application; each deletion is logged with `tracing::warn!`.
+    /// Prefer separating the applications' keys with
+    /// [`RedisStore::with_key_prefix`] or a separate database.
+    pub fn with_wrong_type_recovery(mut self) -> Self {
+        self.wrong_type_recovery = true;
+        self
+    }
+
+    /// Serve loads from copies of sessions cached in process, refreshing a
+    /// copy from Redis in the background once it is older than `max_age`.
+    ///
+    /// This is for latency-critical endpoints that tolerate slightly stale
+    /// session data: only the first load of a session in this process waits
+    /// on Redis, and later loads return the cached copy immediately, even
+    /// while it is being refreshed. Creates and saves update the cached copy
+    /// and deletes remove it, so a process always sees its own writes, but
+    /// changes made through other processes, including deletes, are only
+    /// seen once the copy has been refreshed, which may serve a logged out
+    /// session for up to `max_age` and one more load. Copies past their
+    /// expiry date are never served.
+    ///
+    /// At most `capacity` sessions are cached, evicting the one read from
+    /// Redis longest ago when full. Refreshes are spawned on the Tokio runtime
+    /// of the load, and failed refreshes are logged with `tracing::warn!` and
+    /// retried by the next load.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+    ///
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    /// let session_store =
+    ///     RedisStore::new(pool).with_stale_while_revalidate(Duration::from_secs(5), 10_000);
+    /// ```
+    pub fn with_stale_while_revalidate(mut self, max_age: Duration, capacity: usize) -> Self {
+        self.local_cache = Some(LocalCache::new(max_age, capacity));
+        self
+    }
+
+    /// The client the store runs its commands with.
+    pub fn client(&self) -> &C {
+        &self.client
     }
 
-    async fn save_with_options(
+    /// The session data keys stored under a side key, if side keys apply.
+    fn metadata_keys(&self) -> Option<&[String]> {
+        self.metadata_keys
+            .as_deref()
+            .filter(|_| self.format == RecordFormat::Native)
+    }
+
+    /// The chunk size to split records by, if chunking applies.
+    fn chunk_size(&self) -> Option<usize> {
+        self.chunk_size
+            .filter(|_| self.format == RecordFormat::Native)
+    }
+
+    /// The part of keys preceding the session ID.
+    fn key_stem(&self) -> String {
+        match self.format {
+            RecordFormat::Native => self.key_prefix.clone(),
+            #[cfg(feature = "express-session")]
+            RecordFormat::ExpressSession => format!("{}sess:", self.key_prefix),
+        }
+    }
+
+    /// The key the session with the provided ID is stored under, including
+    /// the key prefix, e.g. for running commands of one's own against the
+    /// session's key with [`RedisStore::client`].
+    ///
+    /// Chunks, side keys, and auxiliary keys of the session are stored under
+    /// keys derived from this one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::session::Id;
+    /// use tower_sessions_redis_store::{
+    ///     fred::{
+    ///         prelude::*,
+    ///         types::{ClusterHash, CustomCommand},
+    ///     },
+    ///     RedisStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = Pool::new(Config::default(), None, None, None, 6).unwrap();
+    /// pool.init().await.unwrap();
+    ///
+    /// let session_store = RedisStore::new(pool).with_key_prefix("prod:");
+    /// let key = session_store.key_for(&Id::default());
+    /// let object = CustomCommand::new("OBJECT", ClusterHash::Offset(1), false);
+    /// let idle_secs: Option<u64> = session_store
+    ///     .client()
+    ///     .custom(object, vec!["IDLETIME".to_string(), key])
+    ///     .await
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub fn key_for(&self, session_id: &Id) -> String {
+        match self.format {
+            RecordFormat::Native => format!("{}{session_id}", self.key_prefix),
+            #[cfg(feature = "express-session")]
+            RecordFormat::ExpressSession => format!("{}sess:{session_id}", self.key_prefix),
+        }
+    }
+
+    fn encode(&self, record: &Record) -> Result<Vec<u8>, RedisStoreError> {
+        match self.format {
+            #[cfg(feature = "json")]
+            RecordFormat::Native => serde_json::to_vec(record).map_err(RedisStoreError::JsonEncode),
+            #[cfg(not(feature = "json"))]
+            RecordFormat::Native => Ok(rmp_serde::to_vec(record)?),
+            #[cfg(feature = "express-session")]
+            RecordFormat::ExpressSession => express_session::encode(record),
+        }
+    }
+
+    #[cfg_attr(not(feature = "express-session"), allow(unused_variables))]
+    fn decode(&self, session_id: &Id, data: &[u8]) -> Result<Record, RedisStoreError> {
+        match self.format {
+            #[cfg(feature = "json")]
+            RecordFormat::Native => {
+                serde_json::from_slice(data).map_err(RedisStoreError::JsonDecode)
+            }
+            #[cfg(not(feature = "json"))]
+            RecordFormat::Native => Ok(rmp_serde::from_slice(data)?),
+            #[cfg(feature = "express-session")]
+            RecordFormat::ExpressSession => express_session::decode(session_id, data),
+        }
+    }
+
+    pub(crate) async fn save_with_options(
         &self,
         record: &Record,
         options: Option<SetOptions>,
     ) -> session_store::Result<bool> {
-        let expire = Some(Expiration::EXAT(OffsetDateTime::unix_timestamp(
-            record.expiry_date,
-        )));
+        let record = &*self.ttl_policy.apply(record);
+        let replaces = matches!(options, Some(SetOptions::XX));
+        let stored = match self.metadata_keys() {
+            Some(metadata_keys) => self.set_split(record, metadata_keys, options).await?,
+            None => self.set_record(record, options).await?,
+        };
+
+        if let Some(local_cache) = &self.local_cache {
+            if stored {
+                local_cache.insert(record);
+            } else if replaces {
+                local_cache.remove(&record.id);
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Fail if the record would be written with an expiry date that has
+    /// passed.
+    ///
+    /// `EXAT` has a resolution of seconds, so an expiry date within the
+    /// current second has passed as far as Redis is concerned.
+    fn check_not_expired(&self, record: &Record) -> Result<(), RedisStoreError> {
+        let expiry_date = self.ttl_policy.apply(record).expiry_date;
+        if expiry_date.unix_timestamp() <= OffsetDateTime::now_utc().unix_timestamp() {
+            return Err(RedisStoreError::AlreadyExpired(expiry_date));
+        }
+        Ok(())
+    }
+
+    /// Write a record under the session's key, and return whether it was
+    /// stored.
+    pub(crate) async fn set_record(
+        &self,
+        record: &Record,
+        options: Option<SetOptions>,
+    ) -> Result<bool, RedisStoreError> {
+        let expires_at = OffsetDateTime::unix_timestamp(record.expiry_date);
+        let value = self.encode(record)?;
+
+        // Without `GET`, `SET` replaces values of any type.
+        if let Some(chunk_size) = self.chunk_size() {
+            let key = self.key_for(&record.id);
+            return self
+                .recover_wrong_type(&key, || {
+                    self.set_chunked(
+                        key.clone(),
+                        value.clone(),
+                        chunk_size,
+                        expires_at,
+                        options.clone(),
+                    )
+                })
+                .await;
+        }
 
         Ok(self
             .client
             .set(
-                record.id.to_string(),
-                rmp_serde::to_vec(&record)
-                    .map_err(RedisStoreError::Encode)?
-                    .as_slice(),
-                expire,
+                self.key_for(&record.id),
+                value.as_slice(),
+                Some(Expiration::EXAT(expires_at)),
                 options,
                 false,
             )
+            .await?)
+    }
+}
+
+impl<C: KeysInterface + Send + Sync> StoreOptions for RedisStore<C> {
+    fn with_namespace(self, namespace: &str) -> Result<Self, String> {
+        Ok(self.with_key_prefix(format!("{namespace}:")))
+    }
+
+    fn with_id_collision_policy(self, id_collision_policy: IdCollisionPolicy) -> Self {
+        Self::with_id_collision_policy(self, id_collision_policy)
+    }
+
+    fn with_decode_failure_policy(self, decode_failure_policy: DecodeFailurePolicy) -> Self {
+        Self::with_decode_failure_policy(self, decode_failure_policy)
+    }
+
+    fn with_slow_op_threshold(self, threshold: Duration) -> Self {
+        Self::with_slow_op_threshold(self, threshold)
+    }
+
+    fn with_expiry_enforcement(self, expiry_enforcement: ExpiryEnforcement) -> Self {
+        Self::with_expiry_enforcement(self, expiry_enforcement)
+    }
+
+    fn with_ttl_policy(self, ttl_policy: TtlPolicy) -> Self {
+        Self::with_ttl_policy(self, ttl_policy)
+    }
+}
+
+impl<C> RedisStore<C>
+where
+    C: KeysInterface + Send + Sync + Debug + 'static,
+{
+    /// Load the session from Redis, bypassing the local cache.
+    pub(crate) async fn load_from_redis(
+        &self,
+        session_id: &Id,
+    ) -> session_store::Result<Option<Record>> {
+        let started = self.slow_ops.start();
+        let res: session_store::Result<Option<Record>> = async {
+            self.ensure_connected().await?;
+            let key = self.key_for(session_id);
+            let get = async {
+                if self.metadata_keys().is_some() {
+                    Ok(self.get_split(&key).await?)
+                } else {
+                    let data = self
+                        .recover_wrong_type(&key, || async {
+                            Ok(self.client.get::<Option<Vec<u8>>, _>(key.as_str()).await?)
+                        })
+                        .await?;
+                    Ok::<_, session_store::Error>((data, None))
+                }
+            };
+            let key_expiry_date = async {
+                if self.key_expiry_on_load {
+                    self.key_expiry_date(&key).await
+                } else {
+                    Ok(None)
+                }
+            };
+            let (got, key_expiry_date) = tokio::join!(get, key_expiry_date);
+            let (data, metadata) = got?;
+            let data = match data {
+                Some(data) => self.resolve_chunks(&key, data).await?,
+                None => None,
+            };
+
+            let mut record = data
+                .map(|data| self.decode(session_id, &data))
+                .transpose()?;
+            if let (Some(record), Some(metadata)) = (record.as_mut(), metadata) {
+                side_key::merge_metadata(record, &metadata)?;
+            }
+            if let Some(record) = record.as_mut() {
+                key_expiry::reconcile_expiry_date(record, key_expiry_date?);
+            }
+            Ok(self.expiry_enforcement.apply(record))
+        }
+        .await;
+        self.slow_ops
+            .finish(started, "load", res.as_ref().ok().and_then(Option::as_ref));
+        self.decode_failure_policy
+            .apply(self, session_id, res)
             .await
-            .map_err(RedisStoreError::Redis)?)
+    }
+
+    /// Delete the session and its related keys from Redis, leaving the local
+    /// cache as it is.
+    async fn delete_from_redis(&self, session_id: &Id) -> session_store::Result<()> {
+        let key = self.key_for(session_id);
+        let mut keys = self.aux_keys(&key);
+        if self.metadata_keys().is_some() {
+            keys.push(side_key(&key));
+        }
+        if self.chunk_size().is_some() {
+            if !keys.is_empty() {
+                let _: () = self
+                    .client
+                    .del(keys)
+                    .await
+                    .map_err(RedisStoreError::Redis)?;
+            }
+            self.delete_chunked(key).await?;
+            return Ok(());
+        }
+
+        keys.push(key);
+        let _: () = self
+            .client
+            .del(keys)
+            .await
+            .map_err(RedisStoreError::Redis)?;
+        Ok(())
     }
 }
 
@@ -92,43 +800,223 @@ where
     C: KeysInterface + Send + Sync + Debug + 'static,
 {
     async fn create(&self, record: &mut Record) -> session_store::Result<()> {
-        loop {
-            if !self.save_with_options(record, Some(SetOptions::NX)).await? {
-                record.id = Id::default();
-                continue;
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            self.ensure_connected().await?;
+            let mut collisions = 0;
+            loop {
+                // Check each attempt, as the ID, and so the expiry date chosen
+                // by the TTL policy, changes on collisions.
+                self.check_not_expired(record)?;
+                if !self.save_with_options(record, Some(SetOptions::NX)).await? {
+                    collisions += 1;
+                    record.id = self.id_collision_policy.next_id(collisions)?;
+                    continue;
+                }
+                break;
             }
-            break;
+            Ok(())
         }
-        Ok(())
+        .await;
+        self.slow_ops.finish(started, "create", Some(record));
+        res
     }
 
     async fn save(&self, record: &Record) -> session_store::Result<()> {
-        self.save_with_options(record, Some(SetOptions::XX)).await?;
-        Ok(())
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            self.ensure_connected().await?;
+            self.save_with_options(record, Some(SetOptions::XX)).await?;
+            Ok(())
+        }
+        .await;
+        self.slow_ops.finish(started, "save", Some(record));
+        res
     }
 
     async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
-        let data = self
-            .client
-            .get::<Option<Vec<u8>>, _>(session_id.to_string())
-            .await
-            .map_err(RedisStoreError::Redis)?;
+        let Some(local_cache) = &self.local_cache else {
+            return self.load_from_redis(session_id).await;
+        };
 
-        if let Some(data) = data {
-            Ok(Some(
-                rmp_serde::from_slice(&data).map_err(RedisStoreError::Decode)?,
-            ))
-        } else {
-            Ok(None)
+        match local_cache.get(session_id) {
+            Lookup::Fresh(record) => Ok(Some(record)),
+            Lookup::Stale(record) => {
+                self.revalidate(local_cache, *session_id);
+                Ok(Some(record))
+            }
+            Lookup::Miss => {
+                let generation = local_cache.generation();
+                let record = self.load_from_redis(session_id).await?;
+                if let Some(record) = &record {
+                    local_cache.insert_read(record, generation);
+                }
+                Ok(record)
+            }
         }
     }
 
     async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
-        let _: () = self
-            .client
-            .del(session_id.to_string())
-            .await
-            .map_err(RedisStoreError::Redis)?;
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            self.ensure_connected().await?;
+            let deleted = self.delete_from_redis(session_id).await;
+            // Evict the cached copy only once the session is gone, so that a
+            // load racing the delete can't cache it again.
+            if let Some(local_cache) = &self.local_cache {
+                local_cache.remove(session_id);
+            }
+            deleted
+        }
+        .await;
+        self.slow_ops.finish(started, "delete", None);
+        res
+    }
+}
+
+#[async_trait]
+impl<C> TouchSessionStore for RedisStore<C>
+where
+    C: KeysInterface + Send + Sync + Debug + 'static,
+{
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        self.ensure_connected().await?;
+        let expiry_date = self.ttl_policy.expiry_date(session_id, expiry_date);
+        if self.metadata_keys().is_some() {
+            let _: bool = self
+                .client
+                .expire_at(
+                    side_key(&self.key_for(session_id)),
+                    expiry_date.unix_timestamp(),
+                    None,
+                )
+                .await
+                .map_err(RedisStoreError::Redis)?;
+        }
+        let touched = if self.chunk_size().is_some() {
+            self.expire_chunked(self.key_for(session_id), expiry_date.unix_timestamp())
+                .await?
+        } else {
+            self.client
+                .expire_at(self.key_for(session_id), expiry_date.unix_timestamp(), None)
+                .await
+                .map_err(RedisStoreError::Redis)?
+        };
+
+        if let Some(local_cache) = &self.local_cache {
+            if touched {
+                local_cache.touch(session_id, expiry_date);
+            } else {
+                local_cache.remove(session_id);
+            }
+        }
+        Ok(touched)
+    }
+}
+
+/// The number of keys requested per `SCAN` page when sweeping expired
+/// sessions.
+const SWEEP_PAGE_SIZE: u32 = 100;
+
+#[async_trait]
+impl<C> ExpiredDeletion for RedisStore<C>
+where
+    C: KeysInterface + LuaInterface + Send + Sync + Debug + 'static,
+{
+    /// Delete sessions whose keys should have expired but haven't, e.g.
+    /// because of clock skew between the application and Redis.
+    ///
+    /// Redis expires keys by itself, so this is only a safety net. Keys are
+    /// found by scanning for the store's key prefix and checked against the
+    /// expiry set by the last save or touch, as read with `PEXPIRETIME`, in
+    /// a Lua script that deletes them in the same atomic step, so sessions
+    /// saved or touched meanwhile are kept. This requires Redis 7.0 or later.
+    /// Keys that aren't sessions are left alone. Redis Cluster isn't
+    /// supported, as each node would need to be scanned.
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.ensure_connected().await?;
+        if self.client.is_clustered() {
+            return Err(session_store::Error::Backend(
+                "Sweeping expired sessions isn't supported with Redis Cluster".to_string(),
+            ));
+        }
+
+        let stem = self.key_stem();
+        let pattern = format!("{}*", escape_glob(&stem));
+        let mut cursor = "0".to_string();
+        loop {
+            let (next_cursor, keys): (String, Vec<Key>) = self
+                .client
+                .scan_page(cursor, pattern.clone(), Some(SWEEP_PAGE_SIZE), None)
+                .await
+                .map_err(RedisStoreError::Redis)?;
+
+            // Pair each session's key with its side key, which the script
+            // deletes along with it.
+            let script_keys: Vec<String> = keys
+                .iter()
+                .filter_map(Key::as_str)
+                .filter(|key| {
+                    key.strip_prefix(&stem)
+                        .is_some_and(|id| Id::from_str(id).is_ok())
+                })
+                .flat_map(|key| [key.to_string(), side_key(key)])
+                .collect();
+
+            if !script_keys.is_empty() {
+                let now = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+                let swept: Vec<Vec<u8>> = self
+                    .client
+                    .eval(key_expiry::SWEEP_SCRIPT, script_keys, now as i64)
+                    .await
+                    .map_err(RedisStoreError::Redis)?;
+
+                let mut chunk_keys = Vec::new();
+                for pair in swept.chunks_exact(2) {
+                    let key = String::from_utf8_lossy(&pair[0]);
+                    if let Some(local_cache) = &self.local_cache {
+                        if let Some(session_id) =
+                            key.strip_prefix(&stem).and_then(|id| Id::from_str(id).ok())
+                        {
+                            local_cache.remove(&session_id);
+                        }
+                    }
+                    if let Some(manifest) = Manifest::decode(&pair[1]) {
+                        chunk_keys.extend(manifest.chunk_keys(&key));
+                    }
+                }
+
+                if !chunk_keys.is_empty() {
+                    let _: () = self
+                        .client
+                        .del(chunk_keys)
+                        .await
+                        .map_err(RedisStoreError::Redis)?;
+                }
+            }
+
+            if next_cursor == "0" {
+                break;
+            }
+            cursor = next_cursor;
+        }
+
         Ok(())
     }
 }
+
+/// Escape the characters `SCAN` patterns treat specially.
+fn escape_glob(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,40 @@
+/// The Redis-compatible server a store is connected to.
+///
+/// `RedisStore` only uses `GET`, `SET` with the `NX`, `XX`, and `EXAT`
+/// options, `DEL`, `EXPIREAT`, and `PEXPIRETIME`, plus `SCAN` and a Lua
+/// script when sweeping expired sessions and `SET`'s `GET` option, `GETDEL`,
+/// and `MGET` with chunking, which behave the same on each of these servers,
+/// so no server-specific configuration is needed. Detection is provided for
+/// diagnostics, e.g. to log which server an application is running against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RedisServer {
+    /// Redis, or a server not otherwise recognized.
+    Redis,
+
+    /// Valkey.
+    Valkey,
+
+    /// Dragonfly.
+    Dragonfly,
+}
+
+impl RedisServer {
+    /// Identify the server from the output of `INFO server`.
+    pub(crate) fn from_info(info: &str) -> Self {
+        let fields = info.lines().filter_map(|line| line.trim().split_once(':'));
+        let mut server = RedisServer::Redis;
+        for (name, value) in fields {
+            match name {
+                // Dragonfly also reports a `redis_version` for compatibility.
+                "dragonfly_version" => return RedisServer::Dragonfly,
+                "valkey_version" => server = RedisServer::Valkey,
+                "server_name" if value.eq_ignore_ascii_case("valkey") => {
+                    server = RedisServer::Valkey
+                }
+                _ => {}
+            }
+        }
+        server
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use fred::{
+    prelude::KeysInterface,
+    types::{Expiration, SetOptions},
+};
+use sha2::{Digest, Sha256};
+use tower_sessions_core::session::Record;
+
+use crate::{chunked::derived_key, RedisStore, RedisStoreError};
+
+/// A digest of a record's payload, telling whether the payload stored under
+/// the session's key is the one being saved.
+type PayloadDigest = [u8; 32];
+
+/// The key holding the metadata of the session stored under `key`.
+pub(crate) fn side_key(key: &str) -> String {
+    derived_key(key, "meta")
+}
+
+/// Split a record into its payload and a record holding only the metadata
+/// keys.
+fn split(record: &Record, metadata_keys: &[String]) -> (Record, Record) {
+    let mut payload = record.clone();
+    let mut metadata = Record {
+        data: Default::default(),
+        ..record.clone()
+    };
+    for metadata_key in metadata_keys {
+        if let Some(value) = payload.data.remove(metadata_key) {
+            metadata.data.insert(metadata_key.clone(), value);
+        }
+    }
+    (payload, metadata)
+}
+
+/// Digest the payload's data, which doesn't include the expiry date, so that
+/// extending a session's expiry doesn't count as a change.
+fn digest(payload: &Record) -> Result<PayloadDigest, RedisStoreError> {
+    let sorted: BTreeMap<_, _> = payload.data.iter().collect();
+    Ok(Sha256::digest(rmp_serde::to_vec(&sorted)?).into())
+}
+
+/// Decode the value of a side key into the digest of the payload it was
+/// written with and the metadata.
+fn decode(data: &[u8]) -> Result<(PayloadDigest, Record), RedisStoreError> {
+    Ok(rmp_serde::from_slice(data)?)
+}
+
+/// Merge the metadata stored under a side key into the record loaded from the
+/// session's key.
+pub(crate) fn merge_metadata(record: &mut Record, data: &[u8]) -> Result<(), RedisStoreError> {
+    let (_, metadata) = decode(data)?;
+    record.data.extend(metadata.data);
+    record.expiry_date = metadata.expiry_date;
+    Ok(())
+}
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// Write a record's metadata to its side key, and its payload to the
+    /// session's key only if it differs from the stored one, and return
+    /// whether it was stored.
+    pub(crate) async fn set_split(
+        &self,
+        record: &Record,
+        metadata_keys: &[String],
+        options: Option<SetOptions>,
+    ) -> Result<bool, RedisStoreError> {
+        let key = self.key_for(&record.id);
+        let side_key = side_key(&key);
+        let expires_at = record.expiry_date.unix_timestamp();
+        let (payload, metadata) = split(record, metadata_keys);
+        let digest = digest(&payload)?;
+
+        // A session that doesn't exist yet has no payload to compare against.
+        let unchanged = if matches!(options, Some(SetOptions::NX)) {
+            false
+        } else {
+            let stored: Option<Vec<u8>> = self
+                .recover_wrong_type(&side_key, || async {
+                    Ok(self.client.get(side_key.as_str()).await?)
+                })
+                .await?;
+            stored
+                .and_then(|data| decode(&data).ok())
+                .is_some_and(|(stored_digest, _)| stored_digest == digest)
+        };
+
+        let stored = match (unchanged, self.chunk_size()) {
+            (true, Some(_)) => self.expire_chunked(key, expires_at).await?,
+            (true, None) => self.client.expire_at(key, expires_at, None).await?,
+            (false, _) => self.set_record(&payload, options).await?,
+        };
+
+        if stored {
+            let value = rmp_serde::to_vec(&(digest, &metadata))?;
+            let _: () = self
+                .client
+                .set(
+                    side_key,
+                    value.as_slice(),
+                    Some(Expiration::EXAT(expires_at)),
+                    None,
+                    false,
+                )
+                .await?;
+        }
+
+        Ok(stored)
+    }
+
+    /// Get the values of the session's key and of its side key.
+    pub(crate) async fn get_split(
+        &self,
+        key: &str,
+    ) -> Result<(Option<Vec<u8>>, Option<Vec<u8>>), RedisStoreError> {
+        let mut values: Vec<Option<Vec<u8>>> = self
+            .client
+            .mget(vec![key.to_string(), side_key(key)])
+            .await?;
+        let metadata = values.pop().flatten();
+        let data = values.pop().flatten();
+        Ok((data, metadata))
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use fred::prelude::KeysInterface;
+use time::OffsetDateTime;
+use tower_sessions_core::session::{Id, Record};
+use tower_sessions_ext::warn;
+
+use crate::RedisStore;
+
+/// A session cached in process, along with when it was read from Redis.
+#[derive(Debug)]
+struct Entry {
+    record: Record,
+    fetched_at: Instant,
+    refreshing: bool,
+}
+
+/// The cached sessions, indexed by when they were fetched so that the least
+/// recently fetched is found without scanning them all.
+#[derive(Debug, Default)]
+struct Entries {
+    by_id: HashMap<Id, Entry>,
+    by_fetched_at: BTreeSet<(Instant, i128)>,
+}
+
+impl Entries {
+    fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    fn contains_key(&self, session_id: &Id) -> bool {
+        self.by_id.contains_key(session_id)
+    }
+
+    fn get_mut(&mut self, session_id: &Id) -> Option<&mut Entry> {
+        self.by_id.get_mut(session_id)
+    }
+
+    fn insert(&mut self, session_id: Id, entry: Entry) {
+        let fetched_at = entry.fetched_at;
+        if let Some(previous) = self.by_id.insert(session_id, entry) {
+            self.by_fetched_at
+                .remove(&(previous.fetched_at, session_id.0));
+        }
+        self.by_fetched_at.insert((fetched_at, session_id.0));
+    }
+
+    fn remove(&mut self, session_id: &Id) {
+        if let Some(entry) = self.by_id.remove(session_id) {
+            self.by_fetched_at.remove(&(entry.fetched_at, session_id.0));
+        }
+    }
+
+    fn remove_least_recently_fetched(&mut self) {
+        if let Some((_, session_id)) = self.by_fetched_at.pop_first() {
+            self.by_id.remove(&Id(session_id));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.by_id.clear();
+        self.by_fetched_at.clear();
+    }
+}
+
+/// What the local cache holds for a session.
+pub(crate) enum Lookup {
+    /// A copy read from Redis within the maximum age.
+    Fresh(Record),
+
+    /// A copy older than the maximum age, which the caller must refresh.
+    Stale(Record),
+
+    /// No copy, or only one that has expired.
+    Miss,
+}
+
+/// Sessions cached in process by
+/// [`RedisStore::with_stale_while_revalidate`].
+#[derive(Debug, Clone)]
+pub(crate) struct LocalCache {
+    max_age: Duration,
+    capacity: usize,
+    entries: Arc<Mutex<Entries>>,
+    /// Bumped, with the entries locked, whenever a session is written or
+    /// removed.
+    generation: Arc<AtomicU64>,
+}
+
+impl LocalCache {
+    pub(crate) fn new(max_age: Duration, capacity: usize) -> Self {
+        Self {
+            max_age,
+            capacity: capacity.max(1),
+            entries: Arc::default(),
+            generation: Arc::default(),
+        }
+    }
+
+    /// Look up the session, claiming its refresh if the copy is stale. A
+    /// stale copy already being refreshed is served as it is.
+    pub(crate) fn get(&self, session_id: &Id) -> Lookup {
+        let mut entries = self.lock();
+        let Some(entry) = entries.get_mut(session_id) else {
+            return Lookup::Miss;
+        };
+
+        if entry.record.expiry_date <= OffsetDateTime::now_utc() {
+            entries.remove(session_id);
+            return Lookup::Miss;
+        }
+
+        if entry.refreshing || entry.fetched_at.elapsed() < self.max_age {
+            return Lookup::Fresh(entry.record.clone());
+        }
+
+        entry.refreshing = true;
+        Lookup::Stale(entry.record.clone())
+    }
+
+    /// The current write generation, to be passed to
+    /// [`LocalCache::insert_read`] when reading a session from Redis.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Cache the record as read from Redis, unless a session was written or
+    /// removed since the read started at the given generation, in which case
+    /// the record read may be outdated already.
+    pub(crate) fn insert_read(&self, record: &Record, generation: u64) {
+        let mut entries = self.lock();
+        if self.generation() == generation {
+            self.insert_locked(&mut entries, record);
+        }
+    }
+
+    /// Cache the record as just written to Redis, evicting the least recently
+    /// fetched session when the cache is full.
+    pub(crate) fn insert(&self, record: &Record) {
+        let mut entries = self.lock();
+        self.bump();
+        self.insert_locked(&mut entries, record);
+    }
+
+    fn insert_locked(&self, entries: &mut Entries, record: &Record) {
+        if entries.len() >= self.capacity && !entries.contains_key(&record.id) {
+            entries.remove_least_recently_fetched();
+        }
+
+        entries.insert(
+            record.id,
+            Entry {
+                record: record.clone(),
+                fetched_at: Instant::now(),
+                refreshing: false,
+            },
+        );
+    }
+
+    /// Replace the copy being refreshed with the session as read from Redis,
+    /// or remove it if the session is gone.
+    ///
+    /// A copy written since the refresh started is newer than the one read,
+    /// and is kept.
+    fn revalidated(&self, session_id: &Id, record: Option<Record>) {
+        let mut entries = self.lock();
+        if !entries
+            .get_mut(session_id)
+            .is_some_and(|entry| entry.refreshing)
+        {
+            return;
+        }
+        match record {
+            Some(record) => entries.insert(
+                *session_id,
+                Entry {
+                    record,
+                    fetched_at: Instant::now(),
+                    refreshing: false,
+                },
+            ),
+            None => entries.remove(session_id),
+        }
+    }
+
+    /// Let the next load retry refreshing the session, e.g. after Redis
+    /// failed to respond.
+    fn release(&self, session_id: &Id) {
+        if let Some(entry) = self.lock().get_mut(session_id) {
+            entry.refreshing = false;
+        }
+    }
+
+    /// Update the expiry date of the cached copy, if any.
+    pub(crate) fn touch(&self, session_id: &Id, expiry_date: OffsetDateTime) {
+        let mut entries = self.lock();
+        self.bump();
+        if let Some(entry) = entries.get_mut(session_id) {
+            entry.record.expiry_date = expiry_date;
+        }
+    }
+
+    pub(crate) fn remove(&self, session_id: &Id) {
+        let mut entries = self.lock();
+        self.bump();
+        entries.remove(session_id);
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut entries = self.lock();
+        self.bump();
+        entries.clear();
+    }
+
+    fn bump(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<C> RedisStore<C>
+where
+    C: KeysInterface + Send + Sync + Debug + 'static,
+{
+    /// Read the session from Redis in the background, replacing the cached
+    /// copy, or removing it if the session is gone.
+    pub(crate) fn revalidate(&self, local_cache: &LocalCache, session_id: Id) {
+        let store = self.clone();
+        let local_cache = local_cache.clone();
+        tokio::spawn(async move {
+            match store.load_from_redis(&session_id).await {
+                Ok(record) => local_cache.revalidated(&session_id, record),
+                Err(err) => {
+                    warn!(
+                        session.id = %session_id,
+                        error = %err,
+                        "failed to revalidate cached session"
+                    );
+                    local_cache.release(&session_id);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,59 @@
+use std::future::Future;
+
+use fred::{error::ErrorKind, prelude::KeysInterface};
+use tower_sessions_core::session_store;
+use tower_sessions_ext::{warn, ErrorTag};
+
+use crate::{RedisStore, RedisStoreError};
+
+/// Returns `true` if the error was produced by a [`RedisStore`] finding a
+/// value that isn't a session under a session's key, e.g. one written by
+/// another application sharing the database.
+///
+/// Such errors are reported as backend errors tagged with
+/// [`ErrorTag::KeyCollision`], which this function recognizes; see
+/// [`RedisStoreError::WrongType`].
+pub fn is_key_collision(err: &session_store::Error) -> bool {
+    ErrorTag::KeyCollision.is_tagged(err)
+}
+
+/// Returns `true` if Redis rejected a command because the key holds a value
+/// of a type the command doesn't apply to, e.g. a hash rather than a string.
+fn is_wrong_type(err: &fred::error::Error) -> bool {
+    *err.kind() == ErrorKind::InvalidArgument && err.details().starts_with("WRONGTYPE")
+}
+
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
+    /// Run a command against the session's key, reporting a value of another
+    /// type under the key as [`RedisStoreError::WrongType`], or deleting it
+    /// and running the command again if recovery is enabled.
+    pub(crate) async fn recover_wrong_type<T, F, Fut>(
+        &self,
+        key: &str,
+        command: F,
+    ) -> Result<T, RedisStoreError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, RedisStoreError>>,
+    {
+        match command().await {
+            Err(RedisStoreError::Redis(err)) if is_wrong_type(&err) => {}
+            res => return res,
+        }
+        if !self.wrong_type_recovery {
+            return Err(RedisStoreError::WrongType(key.to_string()));
+        }
+
+        warn!(
+            key,
+            "deleting a value that isn't a session from a session key"
+        );
+        let _: () = self.client.del(key).await?;
+        match command().await {
+            Err(RedisStoreError::Redis(err)) if is_wrong_type(&err) => {
+                Err(RedisStoreError::WrongType(key.to_string()))
+            }
+            res => res,
+        }
+    }
+}
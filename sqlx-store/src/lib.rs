@@ -1,28 +1,115 @@
 pub use sqlx;
 use tower_sessions_core::session_store;
+use tower_sessions_ext::transient_backend_error;
 
 #[cfg(feature = "mysql")]
 #[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
-pub use self::mysql_store::MySqlStore;
+pub use self::mysql_audit_sink::MySqlAuditSink;
+#[cfg(feature = "mysql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
+pub use self::mysql_store::{MySqlQueryHints, MySqlStore, MySqlStoreConfig, MySqlVerifyReport};
+#[cfg(feature = "mysql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
+pub use self::mysql_store_builder::MySqlStoreBuilder;
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "mysql"))]
+pub use self::pool_status::PoolStatus;
 #[cfg(feature = "postgres")]
 #[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
-pub use self::postgres_store::PostgresStore;
+pub use self::postgres_audit_sink::PostgresAuditSink;
+#[cfg(feature = "postgres-moka")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres-moka")))]
+pub use self::postgres_cached_store::PostgresCachedStore;
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+pub use self::postgres_storage::PostgresStorageParameters;
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+pub use self::postgres_store::{PostgresSaveStatus, PostgresStore, PostgresStoreConfig};
 #[cfg(feature = "sqlite")]
 #[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
-pub use self::sqlite_store::SqliteStore;
+pub use self::sqlite_store::{SqliteStore, SqliteStoreConfig};
 
 #[cfg(feature = "sqlite")]
 #[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
 mod sqlite_store;
 
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "mysql"))]
+mod pool_status;
+
+#[cfg(feature = "sqlite")]
+mod sqlite_busy_retry;
+
+#[cfg(feature = "sqlite")]
+mod sqlite_recent_writes;
+
+#[cfg(feature = "sqlite")]
+mod sqlite_write_queue;
+
 #[cfg(feature = "postgres")]
 #[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
 mod postgres_store;
 
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+mod postgres_storage;
+
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+mod postgres_audit_sink;
+
+#[cfg(feature = "postgres-moka")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres-moka")))]
+mod postgres_cached_store;
+
 #[cfg(feature = "mysql")]
 #[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
 mod mysql_store;
 
+#[cfg(feature = "mysql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
+mod mysql_store_builder;
+
+#[cfg(feature = "mysql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
+mod mysql_audit_sink;
+
+/// Column names of a session table.
+///
+/// The defaults match the schema created by each store's `migrate`. Custom
+/// names allow a store to use a pre-existing session table, such as one
+/// created by another framework's migrations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnNames {
+    /// The session ID column, `id` by default.
+    pub id: String,
+
+    /// The serialized session data column, `data` by default.
+    pub data: String,
+
+    /// The session expiry date column, `expiry_date` by default.
+    pub expiry_date: String,
+}
+
+impl Default for ColumnNames {
+    fn default() -> Self {
+        Self {
+            id: "id".to_string(),
+            data: "data".to_string(),
+            expiry_date: "expiry_date".to_string(),
+        }
+    }
+}
+
+impl ColumnNames {
+    #[cfg(any(feature = "sqlite", feature = "postgres", feature = "mysql"))]
+    fn find_invalid(&self, is_valid: impl Fn(&str) -> bool) -> Option<&str> {
+        [&self.id, &self.data, &self.expiry_date]
+            .into_iter()
+            .map(String::as_str)
+            .find(|name| !is_valid(name))
+    }
+}
+
 /// An error type for SQLx stores.
 #[derive(thiserror::Error, Debug)]
 pub enum SqlxStoreError {
@@ -37,14 +124,119 @@ pub enum SqlxStoreError {
     /// A variant to map `rmp_serde` decode errors.
     #[error(transparent)]
     Decode(#[from] rmp_serde::decode::Error),
+
+    /// A variant to map `serde_json` encode errors.
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonEncode(serde_json::Error),
+
+    /// A variant to map `serde_json` decode errors.
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonDecode(serde_json::Error),
+}
+
+impl SqlxStoreError {
+    /// Returns `true` if the operation may succeed if retried, e.g. after a
+    /// connection failure, pool timeout, deadlock, or serialization failure.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SqlxStoreError::Sqlx(
+                sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed,
+            ) => true,
+            SqlxStoreError::Sqlx(sqlx::Error::Database(err)) => is_transient_database_error(&**err),
+            _ => false,
+        }
+    }
+}
+
+fn is_transient_database_error(err: &dyn sqlx::error::DatabaseError) -> bool {
+    #[cfg(feature = "sqlite")]
+    if err
+        .try_downcast_ref::<sqlx::sqlite::SqliteError>()
+        .is_some()
+    {
+        // `SQLITE_BUSY` and `SQLITE_LOCKED`, including their extended codes.
+        return err
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6));
+    }
+
+    #[cfg(feature = "mysql")]
+    if let Some(err) = err.try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>() {
+        // `ER_LOCK_WAIT_TIMEOUT` and `ER_LOCK_DEADLOCK`.
+        if matches!(err.number(), 1205 | 1213) {
+            return true;
+        }
+    }
+
+    // SQLSTATE classes 08 (connection exception) and 40 (transaction rollback).
+    err.code()
+        .is_some_and(|code| code.starts_with("08") || code.starts_with("40"))
 }
 
 impl From<SqlxStoreError> for session_store::Error {
     fn from(err: SqlxStoreError) -> Self {
         match err {
+            SqlxStoreError::Sqlx(_) if err.is_transient() => transient_backend_error(err),
             SqlxStoreError::Sqlx(inner) => session_store::Error::Backend(inner.to_string()),
             SqlxStoreError::Decode(inner) => session_store::Error::Decode(inner.to_string()),
             SqlxStoreError::Encode(inner) => session_store::Error::Encode(inner.to_string()),
+            #[cfg(feature = "json")]
+            SqlxStoreError::JsonEncode(inner) => session_store::Error::Encode(inner.to_string()),
+            #[cfg(feature = "json")]
+            SqlxStoreError::JsonDecode(inner) => session_store::Error::Decode(inner.to_string()),
         }
     }
 }
+
+/// Serialize a record for the data column, with MessagePack or, with the
+/// `json` feature, JSON.
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "mysql"))]
+pub(crate) fn encode_record(
+    record: &tower_sessions_core::session::Record,
+) -> Result<Vec<u8>, SqlxStoreError> {
+    #[cfg(feature = "json")]
+    return serde_json::to_vec(record).map_err(SqlxStoreError::JsonEncode);
+
+    #[cfg(not(feature = "json"))]
+    return rmp_serde::to_vec(record).map_err(SqlxStoreError::Encode);
+}
+
+/// Serialize a record like [`encode_record`], but with its data in key order
+/// and without its expiry date, so that the encoding only changes along with
+/// the session data. The result is decoded by [`decode_record`].
+#[cfg(feature = "postgres")]
+pub(crate) fn encode_record_data(
+    record: &tower_sessions_core::session::Record,
+) -> Result<Vec<u8>, SqlxStoreError> {
+    // Serialized in the order of the fields of `Record`, which deserializes
+    // from sequences as well as maps.
+    let stable = (
+        &record.id,
+        record
+            .data
+            .iter()
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        time::OffsetDateTime::UNIX_EPOCH,
+    );
+
+    #[cfg(feature = "json")]
+    return serde_json::to_vec(&stable).map_err(SqlxStoreError::JsonEncode);
+
+    #[cfg(not(feature = "json"))]
+    return rmp_serde::to_vec(&stable).map_err(SqlxStoreError::Encode);
+}
+
+/// Deserialize a record read from the data column.
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "mysql"))]
+pub(crate) fn decode_record(
+    data: &[u8],
+) -> Result<tower_sessions_core::session::Record, SqlxStoreError> {
+    #[cfg(feature = "json")]
+    return serde_json::from_slice(data).map_err(SqlxStoreError::JsonDecode);
+
+    #[cfg(not(feature = "json"))]
+    return rmp_serde::from_slice(data).map_err(SqlxStoreError::Decode);
+}
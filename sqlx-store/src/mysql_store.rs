@@ -1,12 +1,167 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
-use sqlx::{MySqlConnection, MySqlPool};
+use serde::Deserialize;
+use sqlx::{Connection, Executor, MySqlConnection, MySqlPool, Row};
 use time::OffsetDateTime;
 use tower_sessions_core::{
     session::{Id, Record},
     session_store, ExpiredDeletion, SessionStore,
 };
+use tower_sessions_ext::{
+    warn, DecodeFailurePolicy, ExportSessions, IdCollisionPolicy, SlowOpTracer, StoreOptions,
+    StoreOptionsConfig, TouchSessionStore, TtlPolicy,
+};
+
+use crate::{
+    decode_record, encode_record, ColumnNames, MySqlStoreBuilder, PoolStatus, SqlxStoreError,
+};
+
+/// The settings of a [`MySqlStore`], as read from a configuration file.
+///
+/// The options shared by every store, such as the `namespace` naming the
+/// session table, are written next to these settings. Settings that are left
+/// out keep the store's defaults.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_sqlx_store::MySqlStoreConfig;
+///
+/// let config: MySqlStoreConfig =
+///     serde_json::from_str(r#"{ "namespace": "sessions", "vitess_compat": true }"#).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MySqlStoreConfig {
+    /// The options shared by every store.
+    #[serde(flatten)]
+    pub options: StoreOptionsConfig,
+
+    /// Passed to [`MySqlStore::with_schema_name`].
+    pub schema_name: Option<String>,
+
+    /// Passed to [`MySqlStore::with_soft_delete`].
+    pub soft_delete_column: Option<String>,
+
+    /// Passed to [`MySqlStore::with_expiry_partitions`].
+    pub expiry_partitions_days_ahead: Option<u32>,
+
+    /// Whether to call [`MySqlStore::with_vitess_compat`].
+    pub vitess_compat: bool,
+
+    /// Passed to [`MySqlStore::with_load_retry`].
+    pub load_retry: Option<bool>,
+
+    /// Passed to [`MySqlStore::with_query_hints`].
+    pub query_hints: Option<MySqlQueryHints>,
+}
+
+/// Optimizer hints added to the store's statements, e.g. to cap how long a
+/// query may run on an overloaded server.
+///
+/// Each hint is written into a `/*+ ... */` comment right after the
+/// statement's first keyword, so it must be a hint the server understands,
+/// e.g. `SET_VAR(optimizer_switch='index_merge=off')`; MySQL ignores hints it
+/// can't parse, with a warning. Hints left unset add nothing.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_sqlx_store::MySqlQueryHints;
+///
+/// let hints = MySqlQueryHints {
+///     max_execution_time_ms: Some(200),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct MySqlQueryHints {
+    /// Abort `load` and `export` queries running for longer than this many
+    /// milliseconds, via `MAX_EXECUTION_TIME`, which MySQL only applies to
+    /// `SELECT` statements.
+    pub max_execution_time_ms: Option<u64>,
+
+    /// Hints for the `SELECT` of `load`.
+    pub load: Option<String>,
+
+    /// Hints for the `SELECT` of `export`.
+    pub export: Option<String>,
+
+    /// Hints for the statements of `create`, `save`, `delete`, and `touch`.
+    pub write: Option<String>,
+
+    /// Hints for the `DELETE` of [`ExpiredDeletion::delete_expired`] and
+    /// [`MySqlStore::purge_deleted`].
+    pub delete_expired: Option<String>,
+}
+
+impl MySqlQueryHints {
+    /// Returns an error if a hint would end its comment early, letting the
+    /// rest of it run as SQL.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let hints = [&self.load, &self.export, &self.write, &self.delete_expired];
+        match hints.into_iter().flatten().find(|hint| hint.contains("*/")) {
+            Some(hint) => Err(format!(
+                "Invalid optimizer hint '{hint}'. Hints can't contain '*/'."
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The hint comment for a `SELECT` with the provided hints, including
+    /// the execution time limit.
+    fn select_comment(&self, hint: &Option<String>) -> String {
+        let max_execution_time = self
+            .max_execution_time_ms
+            .map(|ms| format!("MAX_EXECUTION_TIME({ms})"));
+        let hints: Vec<&str> = max_execution_time
+            .as_deref()
+            .into_iter()
+            .chain(hint.as_deref())
+            .collect();
+        hint_comment(&hints.join(" "))
+    }
+}
+
+/// A `/*+ ... */` comment holding the hints, preceded by a space, or nothing
+/// without hints.
+fn hint_comment(hints: &str) -> String {
+    if hints.trim().is_empty() {
+        String::new()
+    } else {
+        format!(" /*+ {hints} */")
+    }
+}
+
+/// The problems found by [`MySqlStore::verify`] in the session table.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MySqlVerifyReport {
+    /// The number of rows checked.
+    pub rows_checked: u64,
 
-use crate::SqlxStoreError;
+    /// The IDs of rows whose data can't be decoded as a session record.
+    pub undecodable_ids: Vec<String>,
+
+    /// The IDs of rows whose expiry date isn't a valid date after the Unix
+    /// epoch, such as the zero date MySQL may store in place of invalid
+    /// values.
+    pub out_of_range_expiry_ids: Vec<String>,
+
+    /// The errors and warnings reported by `check table`, e.g. for index
+    /// entries without a matching row.
+    pub table_check_messages: Vec<String>,
+}
+
+impl MySqlVerifyReport {
+    /// Returns `true` if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.undecodable_ids.is_empty()
+            && self.out_of_range_expiry_ids.is_empty()
+            && self.table_check_messages.is_empty()
+    }
+}
 
 /// A MySQL session store.
 #[derive(Clone, Debug)]
@@ -14,6 +169,19 @@ pub struct MySqlStore {
     pool: MySqlPool,
     schema_name: String,
     table_name: String,
+    columns: ColumnNames,
+    fixed_columns: Vec<(String, String)>,
+    soft_delete_column: Option<String>,
+    audit_columns: Option<(String, String)>,
+    expiry_partitions: Option<u32>,
+    vitess_compat: bool,
+    load_retry: bool,
+    query_hints: MySqlQueryHints,
+    ttl_policy: TtlPolicy,
+    id_collision_policy: IdCollisionPolicy,
+    decode_failure_policy: DecodeFailurePolicy,
+    slow_ops: SlowOpTracer,
+    queries: Queries,
 }
 
 impl MySqlStore {
@@ -31,13 +199,87 @@ impl MySqlStore {
     /// # })
     /// ```
     pub fn new(pool: MySqlPool) -> Self {
+        let schema_name = "tower_sessions".to_string();
+        let table_name = "session".to_string();
+        let columns = ColumnNames::default();
         Self {
             pool,
-            schema_name: "tower_sessions".to_string(),
-            table_name: "session".to_string(),
+            queries: Queries::new(
+                &schema_name,
+                &table_name,
+                &columns,
+                &[],
+                None,
+                None,
+                false,
+                false,
+                &MySqlQueryHints::default(),
+            ),
+            schema_name,
+            table_name,
+            columns,
+            fixed_columns: Vec::new(),
+            soft_delete_column: None,
+            audit_columns: None,
+            expiry_partitions: None,
+            vitess_compat: false,
+            load_retry: true,
+            query_hints: MySqlQueryHints::default(),
+            ttl_policy: TtlPolicy::default(),
+            id_collision_policy: IdCollisionPolicy::default(),
+            decode_failure_policy: DecodeFailurePolicy::default(),
+            slow_ops: SlowOpTracer::new("mysql"),
         }
     }
 
+    /// Create a new MySqlStore store with the provided connection pool,
+    /// configured with the provided settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore, MySqlStoreConfig};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let config: MySqlStoreConfig =
+    ///     serde_json::from_str(r#"{ "namespace": "sessions" }"#).unwrap();
+    /// let session_store = MySqlStore::try_from_config(pool, &config).unwrap();
+    /// # })
+    /// ```
+    pub fn try_from_config(pool: MySqlPool, config: &MySqlStoreConfig) -> Result<Self, String> {
+        let mut store = config.options.apply(Self::new(pool))?;
+        if let Some(schema_name) = &config.schema_name {
+            store = store.with_schema_name(schema_name)?;
+        }
+        if let Some(column_name) = &config.soft_delete_column {
+            store = store.with_soft_delete(column_name)?;
+        }
+        if let Some(days_ahead) = config.expiry_partitions_days_ahead {
+            store = store.with_expiry_partitions(days_ahead)?;
+        }
+        if config.vitess_compat {
+            store = store.with_vitess_compat()?;
+        }
+        if let Some(load_retry) = config.load_retry {
+            store = store.with_load_retry(load_retry);
+        }
+        if let Some(query_hints) = &config.query_hints {
+            store = store.with_query_hints(query_hints.clone())?;
+        }
+        Ok(store)
+    }
+
+    /// Create a builder for a MySqlStore that connects to the provided URL
+    /// with its own connection pool.
+    ///
+    /// See [`MySqlStoreBuilder`] for the TLS and pool options and their
+    /// defaults.
+    pub fn builder(url: impl Into<String>) -> MySqlStoreBuilder {
+        MySqlStoreBuilder::new(url)
+    }
+
     /// Set the session table schema name with the provided name.
     pub fn with_schema_name(mut self, schema_name: impl AsRef<str>) -> Result<Self, String> {
         let schema_name = schema_name.as_ref();
@@ -51,6 +293,7 @@ impl MySqlStore {
         }
 
         schema_name.clone_into(&mut self.schema_name);
+        self.render_queries();
         Ok(self)
     }
 
@@ -67,9 +310,356 @@ impl MySqlStore {
         }
 
         table_name.clone_into(&mut self.table_name);
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Set the session table column names with the provided names.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, ColumnNames, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool)
+    ///     .with_schema_name("public")
+    ///     .unwrap()
+    ///     .with_table_name("django_session")
+    ///     .unwrap()
+    ///     .with_column_names(ColumnNames {
+    ///         id: "session_key".to_string(),
+    ///         data: "session_data".to_string(),
+    ///         expiry_date: "expire_date".to_string(),
+    ///     })
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub fn with_column_names(mut self, columns: ColumnNames) -> Result<Self, String> {
+        if let Some(column_name) = columns.find_invalid(is_valid_identifier) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.columns = columns;
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Add a column that is populated with the provided value whenever a
+    /// session is inserted.
+    ///
+    /// This is useful for pre-existing session tables with additional
+    /// non-null columns the store does not otherwise know about.
+    pub fn with_fixed_column(
+        mut self,
+        column_name: impl AsRef<str>,
+        value: impl Into<String>,
+    ) -> Result<Self, String> {
+        let column_name = column_name.as_ref();
+        if !is_valid_identifier(column_name) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.fixed_columns
+            .push((column_name.to_string(), value.into()));
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Mark deleted sessions with a deletion timestamp in the provided column
+    /// instead of removing their rows.
+    ///
+    /// Soft-deleted sessions can no longer be loaded, saved, or touched, and
+    /// are kept by [`ExpiredDeletion::delete_expired`] so that they remain
+    /// available for later investigation. Use [`MySqlStore::purge_deleted`]
+    /// to remove them for good.
+    ///
+    /// The column is created by [`MySqlStore::migrate`]; existing tables must
+    /// be altered to add a nullable column with this name.
+    ///
+    /// Soft deletion can't be combined with expiry partitions.
+    pub fn with_soft_delete(mut self, column_name: impl AsRef<str>) -> Result<Self, String> {
+        let column_name = column_name.as_ref();
+        if !is_valid_identifier(column_name) {
+            return Err(invalid_column_name(column_name));
+        }
+        if self.expiry_partitions.is_some() {
+            return Err(SOFT_DELETE_WITH_PARTITIONS.to_string());
+        }
+
+        self.soft_delete_column = Some(column_name.to_string());
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Record when each session was created and last written in the provided
+    /// columns.
+    ///
+    /// The creation column is set when a session is first saved, and the
+    /// update column whenever the session is saved or touched, both using the
+    /// database clock. This suits session analytics in SQL, e.g. counting
+    /// sessions created per day, without decoding session data.
+    ///
+    /// The columns are created by [`MySqlStore::migrate`]; existing tables
+    /// must be altered to add them, e.g. as `timestamp(6) not null default
+    /// current_timestamp(6)`.
+    ///
+    /// Audit columns can't be combined with expiry partitions, under which
+    /// `save` replaces a session's row.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool)
+    ///     .with_audit_columns("created_at", "updated_at")
+    ///     .unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_audit_columns(
+        mut self,
+        created_at_column: impl AsRef<str>,
+        updated_at_column: impl AsRef<str>,
+    ) -> Result<Self, String> {
+        let created_at_column = created_at_column.as_ref();
+        let updated_at_column = updated_at_column.as_ref();
+        for column_name in [created_at_column, updated_at_column] {
+            if !is_valid_identifier(column_name) {
+                return Err(invalid_column_name(column_name));
+            }
+        }
+        if self.expiry_partitions.is_some() {
+            return Err(AUDIT_WITH_PARTITIONS.to_string());
+        }
+
+        self.audit_columns = Some((created_at_column.to_string(), updated_at_column.to_string()));
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Partition the session table by the day sessions expire on, so that
+    /// [`ExpiredDeletion::delete_expired`] can drop whole days of expired
+    /// sessions with `ALTER TABLE ... DROP PARTITION` instead of deleting
+    /// them row by row.
+    ///
+    /// Each call to `delete_expired` drops the partitions of days that have
+    /// passed, adds partitions for the provided number of days ahead, and
+    /// deletes the expired sessions of the current day. Sessions expiring
+    /// further ahead are kept in a catch-all partition until their day gets
+    /// its own.
+    ///
+    /// MySQL requires the primary key of a partitioned table to include the
+    /// expiry date, so session IDs are no longer enforced to be unique by the
+    /// primary key: `create` only inserts sessions whose ID isn't taken, and
+    /// `save` replaces a session's row in a transaction. As MySQL can't
+    /// partition by day on fractional `timestamp` columns, the expiry date is
+    /// stored in UTC as `datetime(6)`.
+    ///
+    /// The partitioned table is created by [`MySqlStore::migrate`]; tables
+    /// that aren't partitioned are swept with `DELETE` as usual. Expiry
+    /// partitions can't be combined with soft deletion or audit columns.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool).with_expiry_partitions(7).unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_expiry_partitions(mut self, days_ahead: u32) -> Result<Self, String> {
+        if self.soft_delete_column.is_some() {
+            return Err(SOFT_DELETE_WITH_PARTITIONS.to_string());
+        }
+        if self.audit_columns.is_some() {
+            return Err(AUDIT_WITH_PARTITIONS.to_string());
+        }
+        if self.vitess_compat {
+            return Err(VITESS_WITH_PARTITIONS.to_string());
+        }
+
+        self.expiry_partitions = Some(days_ahead);
+        self.render_queries();
         Ok(self)
     }
 
+    /// Keep the store's statements compatible with Vitess-based databases
+    /// such as PlanetScale.
+    ///
+    /// With this enabled, [`MySqlStore::migrate`] doesn't run `CREATE SCHEMA`,
+    /// as keyspaces are managed by Vitess rather than through SQL, and tables
+    /// are referred to without a schema name, so that they resolve to the
+    /// keyspace of the connection. [`ExpiredDeletion::delete_expired`] deletes
+    /// expired sessions in batches, staying below the number of rows Vitess
+    /// lets a single statement affect.
+    ///
+    /// Vitess doesn't support partitioned tables, so this can't be combined
+    /// with expiry partitions. Where schema changes must go through deploy
+    /// requests, run `migrate` against a development branch.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool).with_vitess_compat().unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_vitess_compat(mut self) -> Result<Self, String> {
+        if self.expiry_partitions.is_some() {
+            return Err(VITESS_WITH_PARTITIONS.to_string());
+        }
+
+        self.vitess_compat = true;
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Set whether `load` is retried once on another connection if its
+    /// connection was reset, e.g. by a server restart or failover.
+    ///
+    /// Loads don't change the session data, so retrying them is always safe,
+    /// and a connection dropped during maintenance then doesn't fail the
+    /// request. This is enabled by default; disable it to return such errors
+    /// immediately, e.g. when a retry wrapper already handles transient
+    /// errors.
+    pub fn with_load_retry(mut self, load_retry: bool) -> Self {
+        self.load_retry = load_retry;
+        self
+    }
+
+    /// Add the provided optimizer hints to the store's statements.
+    ///
+    /// This lets operators bound the worst-case cost of session queries on
+    /// an overloaded server, e.g. aborting loads that run for longer than a
+    /// request may wait with [`MySqlQueryHints::max_execution_time_ms`],
+    /// which then fail with an error rather than holding a connection.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlQueryHints, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool)
+    ///     .with_query_hints(MySqlQueryHints {
+    ///         max_execution_time_ms: Some(200),
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub fn with_query_hints(mut self, query_hints: MySqlQueryHints) -> Result<Self, String> {
+        query_hints.validate()?;
+
+        self.query_hints = query_hints;
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    /// Set how `load` responds to a session whose stored data can't be
+    /// decoded, e.g. after the serialization format has changed.
+    pub fn with_decode_failure_policy(
+        mut self,
+        decode_failure_policy: DecodeFailurePolicy,
+    ) -> Self {
+        self.decode_failure_policy = decode_failure_policy;
+        self
+    }
+
+    /// Log operations taking longer than the provided threshold with
+    /// `tracing::warn!`.
+    pub fn with_slow_op_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_ops = self.slow_ops.with_threshold(threshold);
+        self
+    }
+
+    /// Set the policy adjusting the expiry dates written to the expiry
+    /// column.
+    pub fn with_ttl_policy(mut self, ttl_policy: TtlPolicy) -> Self {
+        self.ttl_policy = ttl_policy;
+        self
+    }
+
+    /// Open a pooled connection ahead of the first operation.
+    ///
+    /// A store created with a lazily connecting pool, e.g. via
+    /// [`MySqlPool::connect_lazy`](sqlx::MySqlPool::connect_lazy), lets an
+    /// application start while the database is unavailable, with each session
+    /// operation connecting as needed. Call this at startup to connect early
+    /// and surface connection problems.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect_lazy(database_url).unwrap();
+    /// let session_store = MySqlStore::new(pool);
+    /// session_store.warm_up().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn warm_up(&self) -> sqlx::Result<()> {
+        self.pool.acquire().await?;
+        Ok(())
+    }
+
+    /// Take a snapshot of the connection pool's health, including how long
+    /// it currently takes to acquire a connection.
+    ///
+    /// Measuring the wait acquires a connection, opening one if none are idle
+    /// and the pool isn't full.
+    pub async fn pool_status(&self) -> sqlx::Result<PoolStatus> {
+        PoolStatus::probe(&self.pool).await
+    }
+
+    /// Log a snapshot of the connection pool's health with `tracing::info!`,
+    /// or with `tracing::warn!` if the pool is saturated, e.g. periodically.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub async fn log_pool_status(&self) {
+        PoolStatus::log(self.pool_status().await, "mysql");
+    }
+
+    /// The statements [`MySqlStore::migrate`] runs to create the session
+    /// schema, e.g. for reviewing them or applying them with other migration
+    /// tooling.
+    ///
+    /// The partitions of upcoming days, with
+    /// [`MySqlStore::with_expiry_partitions`], are added by `migrate`
+    /// afterwards and aren't included.
+    pub fn migration_sql(&self) -> Vec<String> {
+        let mut statements: Vec<String> = self.queries.create_schema.iter().cloned().collect();
+        statements.push(self.queries.create_table.clone());
+        statements
+    }
+
     /// Migrate the session schema.
     ///
     /// # Examples
@@ -87,45 +677,233 @@ impl MySqlStore {
     pub async fn migrate(&self) -> sqlx::Result<()> {
         let mut tx = self.pool.begin().await?;
 
-        let create_schema_query = format!(
-            "create schema if not exists {schema_name}",
-            schema_name = self.schema_name,
-        );
-        sqlx::query(&create_schema_query).execute(&mut *tx).await?;
-
-        let create_table_query = format!(
-            r#"
-            create table if not exists `{schema_name}`.`{table_name}`
-            (
-                id char(22) primary key not null,
-                data blob not null,
-                expiry_date timestamp(6) not null
-            )
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&create_table_query).execute(&mut *tx).await?;
+        for statement in self.migration_sql() {
+            sqlx::query(&statement).execute(&mut *tx).await?;
+        }
 
         tx.commit().await?;
 
+        self.rotate_partitions().await?;
+
         Ok(())
     }
 
-    async fn id_exists(&self, conn: &mut MySqlConnection, id: &Id) -> session_store::Result<bool> {
-        let query = format!(
+    /// Drop the partitions of days that have passed and add those of the
+    /// upcoming days, if the table is partitioned by expiry.
+    async fn rotate_partitions(&self) -> sqlx::Result<()> {
+        let Some(days_ahead) = self.expiry_partitions else {
+            return Ok(());
+        };
+
+        let partitions: Vec<(String, String)> = sqlx::query_as(
             r#"
-            select exists(select 1 from `{schema_name}`.`{table_name}` where id = ?)
+            select cast(partition_name as char), cast(partition_description as char)
+            from information_schema.partitions
+            where table_schema = ? and table_name = ? and partition_name is not null
+            order by partition_ordinal_position
             "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
+        )
+        .bind(&self.schema_name)
+        .bind(&self.table_name)
+        .fetch_all(&self.pool)
+        .await?;
+        if partitions.is_empty() {
+            return Ok(());
+        }
+
+        // Partitions are bounded by `to_days` of the day after the one they
+        // hold.
+        let today = OffsetDateTime::now_utc()
+            .unix_timestamp()
+            .div_euclid(SECONDS_PER_DAY)
+            + UNIX_EPOCH_TO_DAYS;
+        let mut expired = Vec::new();
+        let mut last_bound = None;
+        let mut catch_all = None;
+        for (name, description) in partitions {
+            match description.parse::<i64>() {
+                // Every session in the partition expired on an earlier day.
+                Ok(bound) if bound <= today => expired.push(name),
+                Ok(bound) => last_bound = Some(bound),
+                Err(_) => catch_all = Some(name),
+            }
+        }
+
+        let first_bound = last_bound.map_or(today + 1, |bound| bound + 1);
+        let upcoming: Vec<String> = (first_bound..=today + 1 + i64::from(days_ahead))
+            .map(|bound| {
+                format!(
+                    "partition `{}` values less than ({bound})",
+                    partition_name(bound)
+                )
+            })
+            .collect();
+
+        let (schema_name, table_name) = (&self.schema_name, &self.table_name);
+        if !upcoming.is_empty() {
+            let upcoming = upcoming.join(", ");
+            let alter = match &catch_all {
+                Some(catch_all) => format!(
+                    "alter table `{schema_name}`.`{table_name}` reorganize partition `{catch_all}` \
+                     into ({upcoming}, partition `{catch_all}` values less than maxvalue)"
+                ),
+                None => {
+                    format!("alter table `{schema_name}`.`{table_name}` add partition ({upcoming})")
+                }
+            };
+            ignore_concurrent_rotation(sqlx::query(&alter).execute(&self.pool).await)?;
+        }
+
+        if !expired.is_empty() {
+            let expired = expired
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            ignore_concurrent_rotation(
+                sqlx::query(&format!(
+                    "alter table `{schema_name}`.`{table_name}` drop partition {expired}"
+                ))
+                .execute(&self.pool)
+                .await,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn render_queries(&mut self) {
+        self.queries = Queries::new(
+            &self.schema_name,
+            &self.table_name,
+            &self.columns,
+            &self.fixed_columns,
+            self.soft_delete_column.as_deref(),
+            self.audit_columns
+                .as_ref()
+                .map(|(created_at, updated_at)| (created_at.as_str(), updated_at.as_str())),
+            self.expiry_partitions.is_some(),
+            self.vitess_compat,
+            &self.query_hints,
         );
+    }
+
+    /// Permanently remove sessions soft-deleted before the provided date,
+    /// returning the number of sessions removed.
+    ///
+    /// This does nothing unless soft deletion is enabled via
+    /// [`MySqlStore::with_soft_delete`].
+    pub async fn purge_deleted(
+        &self,
+        deleted_before: OffsetDateTime,
+    ) -> session_store::Result<u64> {
+        let Some(purge_deleted) = &self.queries.purge_deleted else {
+            return Ok(0);
+        };
 
-        Ok(sqlx::query_scalar(&query)
-            .bind(id.to_string())
-            .fetch_one(conn)
+        let res = sqlx::query(purge_deleted)
+            .bind(deleted_before)
+            .execute(&self.pool)
             .await
-            .map_err(SqlxStoreError::Sqlx)?)
+            .map_err(SqlxStoreError::Sqlx)?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Check the session table for rows that can't be loaded and for
+    /// inconsistent indexes, e.g. after changing the record encoding or
+    /// migrating the schema.
+    ///
+    /// Every row is checked, including expired and soft-deleted ones, a page
+    /// at a time so that the table is never held in memory. Indexes are
+    /// checked with `check table`, which Vitess doesn't support, so it's
+    /// skipped with [`MySqlStore::with_vitess_compat`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool);
+    /// let report = session_store.verify().await.unwrap();
+    /// if !report.is_ok() {
+    ///     eprintln!("session table needs attention: {report:?}");
+    /// }
+    /// # })
+    /// ```
+    pub async fn verify(&self) -> sqlx::Result<MySqlVerifyReport> {
+        let mut report = MySqlVerifyReport::default();
+
+        let mut after = String::new();
+        loop {
+            let rows: Vec<(String, Vec<u8>, i64)> = sqlx::query_as(&self.queries.verify)
+                .bind(&after)
+                .bind(i64::from(VERIFY_PAGE_SIZE))
+                .fetch_all(&self.pool)
+                .await?;
+            let Some((last_id, _, _)) = rows.last() else {
+                break;
+            };
+            after = last_id.clone();
+            let page_len = rows.len();
+
+            for (id, data, out_of_range_expiry) in rows {
+                report.rows_checked += 1;
+                if decode_record(&data).is_err() {
+                    report.undecodable_ids.push(id.clone());
+                }
+                if out_of_range_expiry != 0 {
+                    report.out_of_range_expiry_ids.push(id);
+                }
+            }
+            if page_len < VERIFY_PAGE_SIZE as usize {
+                break;
+            }
+        }
+
+        if !self.vitess_compat {
+            // Sent as a text query, as not every server accepts `check table`
+            // as a prepared statement.
+            let rows = self
+                .pool
+                .fetch_all(self.queries.check_table.as_str())
+                .await?;
+            for row in rows {
+                let (msg_type, msg_text): (String, String) = (row.try_get(2)?, row.try_get(3)?);
+                if msg_type.eq_ignore_ascii_case("error")
+                    || msg_type.eq_ignore_ascii_case("warning")
+                {
+                    report.table_check_messages.push(msg_text);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Insert the record, returning `false` if its ID is already taken.
+    async fn try_create(&self, record: &Record) -> session_store::Result<bool> {
+        let record = &*self.ttl_policy.apply(record);
+        let mut query = sqlx::query(&self.queries.insert)
+            .bind(record.id.to_string())
+            .bind(encode_record(record)?)
+            .bind(record.expiry_date);
+        for (_, value) in &self.fixed_columns {
+            query = query.bind(value.as_str());
+        }
+        if self.expiry_partitions.is_some() {
+            query = query.bind(record.id.to_string());
+        }
+        let res = query.execute(&self.pool).await;
+
+        match res {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(false),
+            Err(e) => Err(SqlxStoreError::Sqlx(e).into()),
+        }
     }
 
     async fn save_with_conn(
@@ -133,114 +911,514 @@ impl MySqlStore {
         conn: &mut MySqlConnection,
         record: &Record,
     ) -> session_store::Result<()> {
-        let query = format!(
-            r#"
-            insert into `{schema_name}`.`{table_name}`
-              (id, data, expiry_date) values (?, ?, ?)
-            on duplicate key update
-              data = values(data),
-              expiry_date = values(expiry_date)
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
+        let record = &*self.ttl_policy.apply(record);
+        let mut query = sqlx::query(&self.queries.upsert)
+            .bind(record.id.to_string())
+            .bind(encode_record(record)?)
+            .bind(record.expiry_date);
+        for (_, value) in &self.fixed_columns {
+            query = query.bind(value.as_str());
+        }
+
+        if self.expiry_partitions.is_none() {
+            query.execute(conn).await.map_err(SqlxStoreError::Sqlx)?;
+            return Ok(());
+        }
+
+        // The primary key includes the expiry date, so the session's row is
+        // replaced rather than upserted.
+        let mut tx = conn.begin().await.map_err(SqlxStoreError::Sqlx)?;
+        sqlx::query(&self.queries.delete)
             .bind(record.id.to_string())
-            .bind(rmp_serde::to_vec(&record).map_err(SqlxStoreError::Encode)?)
-            .bind(record.expiry_date)
-            .execute(conn)
+            .execute(&mut *tx)
             .await
             .map_err(SqlxStoreError::Sqlx)?;
+        query
+            .execute(&mut *tx)
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+        tx.commit().await.map_err(SqlxStoreError::Sqlx)?;
         Ok(())
     }
 }
 
+impl StoreOptions for MySqlStore {
+    fn with_namespace(self, namespace: &str) -> Result<Self, String> {
+        self.with_table_name(namespace)
+    }
+
+    fn with_id_collision_policy(self, id_collision_policy: IdCollisionPolicy) -> Self {
+        Self::with_id_collision_policy(self, id_collision_policy)
+    }
+
+    fn with_decode_failure_policy(self, decode_failure_policy: DecodeFailurePolicy) -> Self {
+        Self::with_decode_failure_policy(self, decode_failure_policy)
+    }
+
+    fn with_slow_op_threshold(self, threshold: Duration) -> Self {
+        Self::with_slow_op_threshold(self, threshold)
+    }
+
+    fn with_ttl_policy(self, ttl_policy: TtlPolicy) -> Self {
+        Self::with_ttl_policy(self, ttl_policy)
+    }
+}
+
 #[async_trait]
 impl ExpiredDeletion for MySqlStore {
     async fn delete_expired(&self) -> session_store::Result<()> {
-        let query = format!(
-            r#"
-            delete from `{schema_name}`.`{table_name}`
-            where expiry_date < utc_timestamp()
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
-            .execute(&self.pool)
+        self.rotate_partitions()
             .await
             .map_err(SqlxStoreError::Sqlx)?;
-        Ok(())
+
+        // With expiry partitions, this only scans the current day's partition.
+        loop {
+            let res = sqlx::query(&self.queries.delete_expired)
+                .execute(&self.pool)
+                .await
+                .map_err(SqlxStoreError::Sqlx)?;
+
+            // Under Vitess, expired sessions are deleted in batches.
+            if !self.vitess_compat || res.rows_affected() < VITESS_DELETE_BATCH_SIZE {
+                return Ok(());
+            }
+        }
     }
 }
 
 #[async_trait]
 impl SessionStore for MySqlStore {
     async fn create(&self, record: &mut Record) -> session_store::Result<()> {
-        let mut tx = self.pool.begin().await.map_err(SqlxStoreError::Sqlx)?;
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            let mut collisions = 0;
+            while !self.try_create(record).await? {
+                collisions += 1;
+                record.id = self.id_collision_policy.next_id(collisions)?;
+            }
 
-        while self.id_exists(&mut tx, &record.id).await? {
-            record.id = Id::default();
+            Ok(())
         }
-        self.save_with_conn(&mut tx, record).await?;
-
-        tx.commit().await.map_err(SqlxStoreError::Sqlx)?;
-
-        Ok(())
+        .await;
+        self.slow_ops.finish(started, "create", Some(record));
+        res
     }
 
     async fn save(&self, record: &Record) -> session_store::Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(SqlxStoreError::Sqlx)?;
-        self.save_with_conn(&mut conn, record).await
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            let mut conn = self.pool.acquire().await.map_err(SqlxStoreError::Sqlx)?;
+            self.save_with_conn(&mut conn, record).await
+        }
+        .await;
+        self.slow_ops.finish(started, "save", Some(record));
+        res
     }
 
     async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
-        let query = format!(
-            r#"
-            select data from `{schema_name}`.`{table_name}`
-            where id = ? and expiry_date > ?
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        let data: Option<(Vec<u8>,)> = sqlx::query_as(&query)
-            .bind(session_id.to_string())
-            .bind(OffsetDateTime::now_utc())
-            .fetch_optional(&self.pool)
-            .await
+        let started = self.slow_ops.start();
+        let res: session_store::Result<Option<Record>> = async {
+            let load = || {
+                sqlx::query_as(&self.queries.load)
+                    .bind(session_id.to_string())
+                    .bind(OffsetDateTime::now_utc())
+                    .fetch_optional(&self.pool)
+            };
+            let data: Option<(Vec<u8>, OffsetDateTime)> = match load().await {
+                Err(err) if self.load_retry && is_connection_reset(&err) => {
+                    warn!(error = %err, "retrying session load after a lost connection");
+                    load().await
+                }
+                res => res,
+            }
             .map_err(SqlxStoreError::Sqlx)?;
 
-        if let Some((data,)) = data {
-            Ok(Some(
-                rmp_serde::from_slice(&data).map_err(SqlxStoreError::Decode)?,
-            ))
-        } else {
-            Ok(None)
+            if let Some((data, expiry_date)) = data {
+                let mut record: Record = decode_record(&data)?;
+                record.expiry_date = expiry_date;
+                Ok(Some(record))
+            } else {
+                Ok(None)
+            }
         }
+        .await;
+        self.slow_ops
+            .finish(started, "load", res.as_ref().ok().and_then(Option::as_ref));
+        self.decode_failure_policy
+            .apply(self, session_id, res)
+            .await
     }
 
     async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
-        let query = format!(
-            r#"delete from `{schema_name}`.`{table_name}` where id = ?"#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            let mut query = sqlx::query(&self.queries.delete);
+            if self.soft_delete_column.is_some() {
+                query = query.bind(OffsetDateTime::now_utc());
+            }
+            query
+                .bind(session_id.to_string())
+                .execute(&self.pool)
+                .await
+                .map_err(SqlxStoreError::Sqlx)?;
+
+            Ok(())
+        }
+        .await;
+        self.slow_ops.finish(started, "delete", None);
+        res
+    }
+}
+
+#[async_trait]
+impl TouchSessionStore for MySqlStore {
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        let res = sqlx::query(&self.queries.touch)
+            .bind(self.ttl_policy.expiry_date(session_id, expiry_date))
             .bind(session_id.to_string())
+            .bind(OffsetDateTime::now_utc())
             .execute(&self.pool)
             .await
             .map_err(SqlxStoreError::Sqlx)?;
 
-        Ok(())
+        Ok(res.rows_affected() > 0)
     }
 }
 
+#[async_trait]
+impl ExportSessions for MySqlStore {
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        let rows: Vec<(Vec<u8>, OffsetDateTime)> = sqlx::query_as(&self.queries.export)
+            .bind(after.map(|id| id.to_string()).unwrap_or_default())
+            .bind(i64::from(limit))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+
+        rows.into_iter()
+            .map(|(data, expiry_date)| {
+                let mut record: Record = decode_record(&data)?;
+                record.expiry_date = expiry_date;
+                Ok(record)
+            })
+            .collect()
+    }
+}
+
+/// Queries rendered once per schema and table name rather than on every
+/// operation.
+#[derive(Clone, Debug)]
+struct Queries {
+    create_schema: Option<String>,
+    create_table: String,
+    insert: String,
+    upsert: String,
+    load: String,
+    delete: String,
+    delete_expired: String,
+    touch: String,
+    export: String,
+    verify: String,
+    check_table: String,
+    purge_deleted: Option<String>,
+}
+
+impl Queries {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        schema_name: &str,
+        table_name: &str,
+        columns: &ColumnNames,
+        fixed_columns: &[(String, String)],
+        soft_delete_column: Option<&str>,
+        audit_columns: Option<(&str, &str)>,
+        partitioned: bool,
+        vitess_compat: bool,
+        hints: &MySqlQueryHints,
+    ) -> Self {
+        let ColumnNames {
+            id,
+            data,
+            expiry_date,
+        } = columns;
+        // MySQL has no conditional upsert, so soft-deleted rows keep their
+        // values instead.
+        let (new_data, new_expiry_date) = match soft_delete_column {
+            Some(deleted_at) => (
+                format!("if(`{deleted_at}` is null, values(`{data}`), `{data}`)"),
+                format!("if(`{deleted_at}` is null, values(`{expiry_date}`), `{expiry_date}`)"),
+            ),
+            None => (
+                format!("values(`{data}`)"),
+                format!("values(`{expiry_date}`)"),
+            ),
+        };
+        let (soft_delete_definition, live) = match soft_delete_column {
+            Some(deleted_at) => (
+                format!(", `{deleted_at}` timestamp(6) null"),
+                format!(" and `{deleted_at}` is null"),
+            ),
+            None => Default::default(),
+        };
+        let (audit_definitions, upsert_updated_now, updated_now) = match audit_columns {
+            Some((created_at, updated_at)) => (
+                format!(
+                    ", `{created_at}` timestamp(6) not null default current_timestamp(6), \
+                     `{updated_at}` timestamp(6) not null default current_timestamp(6)"
+                ),
+                match soft_delete_column {
+                    Some(deleted_at) => format!(
+                        ", `{updated_at}` = if(`{deleted_at}` is null, current_timestamp(6), \
+                         `{updated_at}`)"
+                    ),
+                    None => format!(", `{updated_at}` = current_timestamp(6)"),
+                },
+                format!(", `{updated_at}` = current_timestamp(6)"),
+            ),
+            None => Default::default(),
+        };
+        // Vitess routes unqualified tables to the connection's keyspace, which
+        // the schema name needn't match.
+        let table = if vitess_compat {
+            format!("`{table_name}`")
+        } else {
+            format!("`{schema_name}`.`{table_name}`")
+        };
+        let delete_expired_limit = if vitess_compat {
+            format!(" limit {VITESS_DELETE_BATCH_SIZE}")
+        } else {
+            String::new()
+        };
+        let load_hints = hints.select_comment(&hints.load);
+        let export_hints = hints.select_comment(&hints.export);
+        let write_hints = hint_comment(hints.write.as_deref().unwrap_or_default());
+        let delete_expired_hints =
+            hint_comment(hints.delete_expired.as_deref().unwrap_or_default());
+        let fixed_definitions: String = fixed_columns
+            .iter()
+            .map(|(name, _)| format!(", `{name}` varchar(255) not null"))
+            .collect();
+        let fixed_names: String = fixed_columns
+            .iter()
+            .map(|(name, _)| format!(", `{name}`"))
+            .collect();
+        let fixed_values = ", ?".repeat(fixed_columns.len());
+        let (expiry_date_type, primary_key, partitions) = if partitioned {
+            (
+                "datetime(6)",
+                format!("primary key (`{id}`, `{expiry_date}`)"),
+                format!(
+                    " partition by range (to_days(`{expiry_date}`)) \
+                     (partition `{CATCH_ALL_PARTITION}` values less than maxvalue)"
+                ),
+            )
+        } else {
+            (
+                "timestamp(6)",
+                format!("primary key (`{id}`)"),
+                String::new(),
+            )
+        };
+
+        Self {
+            create_schema: (!vitess_compat)
+                .then(|| format!("create schema if not exists {schema_name}")),
+            create_table: format!(
+                r#"
+                create table if not exists {table}
+                (
+                    `{id}` char(22) not null,
+                    `{data}` blob not null,
+                    `{expiry_date}` {expiry_date_type} not null{fixed_definitions}{soft_delete_definition}{audit_definitions},
+                    {primary_key}
+                ){partitions}
+                "#
+            ),
+            insert: if partitioned {
+                format!(
+                    r#"
+                    insert{write_hints} into {table}
+                      (`{id}`, `{data}`, `{expiry_date}`{fixed_names})
+                      select ?, ?, ?{fixed_values} from dual
+                      where not exists (
+                        select 1 from {table} where `{id}` = ?
+                      )
+                    "#
+                )
+            } else {
+                format!(
+                    r#"
+                    insert{write_hints} into {table}
+                      (`{id}`, `{data}`, `{expiry_date}`{fixed_names})
+                      values (?, ?, ?{fixed_values})
+                    "#
+                )
+            },
+            upsert: if partitioned {
+                format!(
+                    r#"
+                    insert{write_hints} into {table}
+                      (`{id}`, `{data}`, `{expiry_date}`{fixed_names})
+                      values (?, ?, ?{fixed_values})
+                    "#
+                )
+            } else {
+                format!(
+                    r#"
+                insert{write_hints} into {table}
+                  (`{id}`, `{data}`, `{expiry_date}`{fixed_names})
+                  values (?, ?, ?{fixed_values})
+                on duplicate key update
+                  `{data}` = {new_data},
+                  `{expiry_date}` = {new_expiry_date}{upsert_updated_now}
+                "#
+                )
+            },
+            load: format!(
+                r#"
+                select{load_hints} `{data}`, `{expiry_date}` from {table}
+                where `{id}` = ? and `{expiry_date}` > ?{live}
+                "#
+            ),
+            delete: match soft_delete_column {
+                Some(deleted_at) => format!(
+                    r#"
+                    update{write_hints} {table} set `{deleted_at}` = ?
+                    where `{id}` = ?{live}
+                    "#
+                ),
+                None => format!("delete{write_hints} from {table} where `{id}` = ?"),
+            },
+            delete_expired: format!(
+                r#"
+                delete{delete_expired_hints} from {table}
+                where `{expiry_date}` < utc_timestamp(){live}{delete_expired_limit}
+                "#
+            ),
+            touch: format!(
+                r#"
+                update{write_hints} {table} set `{expiry_date}` = ?{updated_now}
+                where `{id}` = ? and `{expiry_date}` > ?{live}
+                "#
+            ),
+            export: format!(
+                r#"
+                select{export_hints} `{data}`, `{expiry_date}` from {table}
+                where `{id}` > ?{live}
+                order by `{id}`
+                limit ?
+                "#
+            ),
+            verify: format!(
+                r#"
+                select `{id}`, `{data}`, coalesce(year(`{expiry_date}`) < 1970, 1) from {table}
+                where `{id}` > ?
+                order by `{id}`
+                limit ?
+                "#
+            ),
+            check_table: format!("check table {table}"),
+            purge_deleted: soft_delete_column.map(|deleted_at| {
+                format!("delete{delete_expired_hints} from {table} where `{deleted_at}` < ?")
+            }),
+        }
+    }
+}
+
+/// The number of rows [`MySqlStore::verify`] reads per query.
+const VERIFY_PAGE_SIZE: u32 = 1_000;
+
+const SOFT_DELETE_WITH_PARTITIONS: &str =
+    "Soft deletion can't be combined with expiry partitions, which drop expired sessions outright.";
+
+const AUDIT_WITH_PARTITIONS: &str =
+    "Audit columns can't be combined with expiry partitions, which replace a session's row when \
+     it is saved.";
+
+const VITESS_WITH_PARTITIONS: &str =
+    "Vitess compatibility can't be combined with expiry partitions, as Vitess doesn't support \
+     partitioned tables.";
+
+/// The number of expired sessions deleted per statement under Vitess, well
+/// below its default limit on the rows a statement may affect.
+const VITESS_DELETE_BATCH_SIZE: u64 = 10_000;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// MySQL's `to_days('1970-01-01')`.
+const UNIX_EPOCH_TO_DAYS: i64 = 719_528;
+
+/// The partition holding sessions that expire beyond the partitioned days.
+const CATCH_ALL_PARTITION: &str = "pmax";
+
+/// The name of the partition bounded by the provided `to_days` value, after
+/// the day its sessions expire on, e.g. `p20240131`.
+fn partition_name(bound: i64) -> String {
+    let date =
+        OffsetDateTime::from_unix_timestamp((bound - 1 - UNIX_EPOCH_TO_DAYS) * SECONDS_PER_DAY)
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+            .date();
+    format!(
+        "p{:04}{:02}{:02}",
+        date.year(),
+        u8::from(date.month()),
+        date.day()
+    )
+}
+
+/// Treat errors from partitions having been added or dropped by another
+/// instance rotating them at the same time as success.
+fn ignore_concurrent_rotation(
+    res: sqlx::Result<sqlx::mysql::MySqlQueryResult>,
+) -> sqlx::Result<()> {
+    match res {
+        Ok(_) => Ok(()),
+        // `ER_RANGE_NOT_INCREASING_ERROR`, `ER_DROP_PARTITION_NON_EXISTENT`, and
+        // `ER_SAME_NAME_PARTITION`.
+        Err(sqlx::Error::Database(err))
+            if err
+                .try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+                .is_some_and(|err| matches!(err.number(), 1493 | 1507 | 1517)) =>
+        {
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns `true` if the statement failed because its connection was lost or
+/// closed by the server, so that it may succeed on another connection.
+fn is_connection_reset(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_) => true,
+        // `ER_SERVER_SHUTDOWN`, `ER_CONNECTION_KILLED`, and
+        // `ER_CLIENT_INTERACTION_TIMEOUT`.
+        sqlx::Error::Database(err) => err
+            .try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+            .is_some_and(|err| matches!(err.number(), 1053 | 1927 | 4031)),
+        _ => false,
+    }
+}
+
+fn invalid_column_name(column_name: &str) -> String {
+    format!(
+        "Invalid column name '{}'. Column names must start with a letter or underscore (including \
+         letters with diacritical marks and non-Latin letters). Subsequent characters can be \
+         letters, underscores, digits (0-9), or dollar signs ($).",
+        column_name
+    )
+}
+
 /// A valid MySQL identifier must start with a letter or underscore
 /// (including letters with diacritical marks and non-Latin letters). Subsequent
 /// characters in an identifier or keyword can be letters, underscores, digits
 /// (0-9), or dollar signs ($).
 /// See https://dev.mysql.com/doc/refman/8.4/en/identifiers.html for details.
-fn is_valid_identifier(name: &str) -> bool {
+pub(crate) fn is_valid_identifier(name: &str) -> bool {
     !name.is_empty()
         && name
             .chars()
@@ -0,0 +1,201 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions, MySqlSslMode};
+
+use crate::{MySqlQueryHints, MySqlStore};
+
+/// A builder for a [`MySqlStore`] that owns its connection pool.
+///
+/// The pool is configured with defaults suited to serving sessions in
+/// production: a bounded pool, a short acquire timeout so requests fail fast
+/// when the database is unavailable, and idle and lifetime limits so that
+/// connections are recycled before the server or a proxy drops them.
+///
+/// Options set here take precedence over those in the connection URL.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use tower_sessions_sqlx_store::{sqlx::mysql::MySqlSslMode, MySqlStore};
+///
+/// # tokio_test::block_on(async {
+/// let database_url = std::option_env!("DATABASE_URL").unwrap();
+/// let session_store = MySqlStore::builder(database_url)
+///     .with_ssl_mode(MySqlSslMode::VerifyIdentity)
+///     .with_max_connections(20)
+///     .with_acquire_timeout(Duration::from_secs(2))
+///     .with_max_execution_time(Duration::from_millis(500))
+///     .build()
+///     .await
+///     .unwrap();
+/// session_store.migrate().await.unwrap();
+/// # })
+/// ```
+#[derive(Clone, Debug)]
+pub struct MySqlStoreBuilder {
+    url: String,
+    ssl_mode: Option<MySqlSslMode>,
+    ssl_ca: Option<PathBuf>,
+    statement_cache_capacity: usize,
+    max_connections: u32,
+    min_connections: u32,
+    acquire_timeout: Duration,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    query_hints: MySqlQueryHints,
+}
+
+impl MySqlStoreBuilder {
+    pub(crate) fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ssl_mode: None,
+            ssl_ca: None,
+            statement_cache_capacity: 100,
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(5),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+            query_hints: MySqlQueryHints::default(),
+        }
+    }
+
+    /// Set whether and how TLS is used for connections.
+    ///
+    /// Defaults to the mode given in the URL, or [`MySqlSslMode::Preferred`].
+    /// TLS requires enabling the `tls-rustls` or `tls-native-tls` feature.
+    pub fn with_ssl_mode(mut self, ssl_mode: MySqlSslMode) -> Self {
+        self.ssl_mode = Some(ssl_mode);
+        self
+    }
+
+    /// Set the PEM file of the certificate authority used to verify the
+    /// server certificate.
+    pub fn with_ssl_ca(mut self, ssl_ca: impl Into<PathBuf>) -> Self {
+        self.ssl_ca = Some(ssl_ca.into());
+        self
+    }
+
+    /// Set the number of prepared statements cached per connection, 100 by
+    /// default.
+    pub fn with_statement_cache_capacity(mut self, capacity: usize) -> Self {
+        self.statement_cache_capacity = capacity;
+        self
+    }
+
+    /// Set the maximum number of pooled connections, 10 by default.
+    pub fn with_max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Set the number of connections the pool keeps open when idle, 0 by
+    /// default.
+    pub fn with_min_connections(mut self, min_connections: u32) -> Self {
+        self.min_connections = min_connections;
+        self
+    }
+
+    /// Set how long an operation waits for a pooled connection before
+    /// failing, 5 seconds by default.
+    pub fn with_acquire_timeout(mut self, acquire_timeout: Duration) -> Self {
+        self.acquire_timeout = acquire_timeout;
+        self
+    }
+
+    /// Set how long a connection may sit idle before it is closed, 10 minutes
+    /// by default.
+    pub fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Set how long a connection is kept before it is replaced, 30 minutes by
+    /// default.
+    pub fn with_max_lifetime(mut self, max_lifetime: Option<Duration>) -> Self {
+        self.max_lifetime = max_lifetime;
+        self
+    }
+
+    /// Set the optimizer hints added to the store's statements; see
+    /// [`MySqlStore::with_query_hints`].
+    pub fn with_query_hints(mut self, query_hints: MySqlQueryHints) -> Self {
+        self.query_hints = query_hints;
+        self
+    }
+
+    /// Abort loads and exports running for longer than the provided
+    /// duration, rounded down to whole milliseconds, via the
+    /// `MAX_EXECUTION_TIME` optimizer hint.
+    ///
+    /// This caps the worst-case time a request spends loading its session
+    /// from an overloaded server; writes aren't limited, as MySQL only
+    /// applies the hint to `SELECT` statements.
+    pub fn with_max_execution_time(mut self, max_execution_time: Duration) -> Self {
+        self.query_hints.max_execution_time_ms =
+            Some(u64::try_from(max_execution_time.as_millis()).unwrap_or(u64::MAX));
+        self
+    }
+
+    /// Connect to the database and create the store.
+    ///
+    /// This fails if the URL or the optimizer hints are invalid, or the first
+    /// connection can't be established.
+    pub async fn build(self) -> sqlx::Result<MySqlStore> {
+        let connect_options = self.connect_options()?;
+        let pool = self.pool_options().connect_with(connect_options).await?;
+
+        self.store(pool)
+    }
+
+    /// Create the store without connecting to the database.
+    ///
+    /// Connections are opened by the first operations that need them, so an
+    /// application can start while the database is unavailable. Use
+    /// [`MySqlStore::warm_up`] to connect early. This fails only if the URL or
+    /// the optimizer hints are invalid.
+    pub fn build_lazy(self) -> sqlx::Result<MySqlStore> {
+        let connect_options = self.connect_options()?;
+        let pool = self.pool_options().connect_lazy_with(connect_options);
+
+        self.store(pool)
+    }
+
+    fn store(self, pool: sqlx::MySqlPool) -> sqlx::Result<MySqlStore> {
+        MySqlStore::new(pool)
+            .with_query_hints(self.query_hints)
+            .map_err(config_error)
+    }
+
+    fn connect_options(&self) -> sqlx::Result<MySqlConnectOptions> {
+        // Checked up front, so that invalid hints fail before connecting.
+        self.query_hints.validate().map_err(config_error)?;
+
+        let mut connect_options = MySqlConnectOptions::from_str(&self.url)?
+            .statement_cache_capacity(self.statement_cache_capacity);
+        if let Some(ssl_mode) = self.ssl_mode {
+            connect_options = connect_options.ssl_mode(ssl_mode);
+        }
+        if let Some(ssl_ca) = &self.ssl_ca {
+            connect_options = connect_options.ssl_ca(ssl_ca);
+        }
+
+        Ok(connect_options)
+    }
+
+    fn pool_options(&self) -> MySqlPoolOptions {
+        MySqlPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+    }
+}
+
+fn config_error(message: String) -> sqlx::Error {
+    sqlx::Error::Configuration(message.into())
+}
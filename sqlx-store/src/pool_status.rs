@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use sqlx::{Database, Pool};
+
+/// A snapshot of the health of a store's connection pool.
+///
+/// Session stores are queried on most requests, so they are often where
+/// pool exhaustion shows up first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// The number of open connections, idle or in use.
+    pub size: u32,
+
+    /// The number of open connections that are idle.
+    pub idle: usize,
+
+    /// The most connections the pool may open.
+    pub max_connections: u32,
+
+    /// How long it took to acquire a connection when the status was taken.
+    pub acquire_wait: Duration,
+}
+
+impl PoolStatus {
+    pub(crate) async fn probe<DB: Database>(pool: &Pool<DB>) -> sqlx::Result<Self> {
+        // Count connections before acquiring one, which would take an idle
+        // connection or open a new one.
+        let size = pool.size();
+        let idle = pool.num_idle();
+
+        let started = Instant::now();
+        drop(pool.acquire().await?);
+        let acquire_wait = started.elapsed();
+
+        Ok(Self {
+            size,
+            idle,
+            max_connections: pool.options().get_max_connections(),
+            acquire_wait,
+        })
+    }
+
+    /// The number of connections in use.
+    pub fn in_use(&self) -> u32 {
+        self.size.saturating_sub(self.idle as u32)
+    }
+
+    /// Returns `true` if every connection the pool may open is in use, so
+    /// that operations must wait for one to be released.
+    pub fn is_saturated(&self) -> bool {
+        self.idle == 0 && self.size >= self.max_connections
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn log(status: sqlx::Result<Self>, store: &'static str) {
+        match status {
+            Ok(status) if status.is_saturated() => tracing::warn!(
+                store,
+                size = status.size,
+                idle = status.idle,
+                max_connections = status.max_connections,
+                acquire_wait = ?status.acquire_wait,
+                "session store connection pool is saturated"
+            ),
+            Ok(status) => tracing::info!(
+                store,
+                size = status.size,
+                idle = status.idle,
+                max_connections = status.max_connections,
+                acquire_wait = ?status.acquire_wait,
+                "session store connection pool status"
+            ),
+            Err(err) => tracing::warn!(
+                store,
+                error = %err,
+                "failed to acquire a session store connection"
+            ),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+use tower_sessions_core::session_store;
+use tower_sessions_ext::{AuditEvent, AuditSink};
+
+use crate::{postgres_store::is_valid_identifier, SqlxStoreError};
+
+/// An [`AuditSink`] appending audit events to a PostgreSQL table.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions_ext::AuditedStore;
+/// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresAuditSink, PostgresStore};
+///
+/// # tokio_test::block_on(async {
+/// let database_url = std::option_env!("DATABASE_URL").unwrap();
+/// let pool = PgPool::connect(database_url).await.unwrap();
+///
+/// let session_store = PostgresStore::new(pool.clone());
+/// session_store.migrate().await.unwrap();
+///
+/// let audit_sink = PostgresAuditSink::new(pool);
+/// audit_sink.migrate().await.unwrap();
+///
+/// let session_store = AuditedStore::new(session_store, audit_sink);
+/// # })
+/// ```
+#[derive(Clone, Debug)]
+pub struct PostgresAuditSink {
+    pool: PgPool,
+    schema_name: String,
+    table_name: String,
+    queries: Queries,
+}
+
+impl PostgresAuditSink {
+    /// Create a new PostgreSQL audit sink with the provided connection pool.
+    pub fn new(pool: PgPool) -> Self {
+        let schema_name = "tower_sessions".to_string();
+        let table_name = "session_audit".to_string();
+        Self {
+            pool,
+            queries: Queries::new(&schema_name, &table_name),
+            schema_name,
+            table_name,
+        }
+    }
+
+    /// Set the audit table schema name with the provided name.
+    pub fn with_schema_name(mut self, schema_name: impl AsRef<str>) -> Result<Self, String> {
+        let schema_name = schema_name.as_ref();
+        if !is_valid_identifier(schema_name) {
+            return Err(format!(
+                "Invalid schema name '{}'. Schema names must start with a letter or underscore \
+                 (including letters with diacritical marks and non-Latin letters).Subsequent \
+                 characters can be letters, underscores, digits (0-9), or dollar signs ($).",
+                schema_name
+            ));
+        }
+
+        schema_name.clone_into(&mut self.schema_name);
+        self.queries = Queries::new(&self.schema_name, &self.table_name);
+        Ok(self)
+    }
+
+    /// Set the audit table name with the provided name.
+    pub fn with_table_name(mut self, table_name: impl AsRef<str>) -> Result<Self, String> {
+        let table_name = table_name.as_ref();
+        if !is_valid_identifier(table_name) {
+            return Err(format!(
+                "Invalid table name '{}'. Table names must start with a letter or underscore \
+                 (including letters with diacritical marks and non-Latin letters).Subsequent \
+                 characters can be letters, underscores, digits (0-9), or dollar signs ($).",
+                table_name
+            ));
+        }
+
+        table_name.clone_into(&mut self.table_name);
+        self.queries = Queries::new(&self.schema_name, &self.table_name);
+        Ok(self)
+    }
+
+    /// Migrate the audit schema.
+    pub async fn migrate(&self) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // Concurrent create schema may fail due to duplicate key violations.
+        //
+        // This works around that by assuming the schema must exist on such an error.
+        if let Err(err) = sqlx::query(&self.queries.create_schema)
+            .execute(&mut *tx)
+            .await
+        {
+            if !err
+                .to_string()
+                .contains("duplicate key value violates unique constraint")
+            {
+                return Err(err);
+            }
+
+            return Ok(());
+        }
+
+        sqlx::query(&self.queries.create_table)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuditSink for PostgresAuditSink {
+    async fn record(&self, event: AuditEvent) -> session_store::Result<()> {
+        sqlx::query(&self.queries.insert)
+            .bind(event.timestamp)
+            .bind(event.operation.as_str())
+            .bind(&event.session_id_hash)
+            .bind(event.outcome.is_success())
+            .bind(event.outcome.error())
+            .execute(&self.pool)
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Queries {
+    create_schema: String,
+    create_table: String,
+    insert: String,
+}
+
+impl Queries {
+    fn new(schema_name: &str, table_name: &str) -> Self {
+        Self {
+            create_schema: format!(r#"create schema if not exists "{schema_name}""#),
+            create_table: format!(
+                r#"
+                create table if not exists "{schema_name}"."{table_name}"
+                (
+                    id bigserial primary key,
+                    occurred_at timestamptz not null,
+                    operation text not null,
+                    session_id_hash char(64) not null,
+                    success boolean not null,
+                    error text
+                )
+                "#
+            ),
+            insert: format!(
+                r#"
+                insert into "{schema_name}"."{table_name}"
+                  (occurred_at, operation, session_id_hash, success, error)
+                values ($1, $2, $3, $4, $5)
+                "#
+            ),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use sqlx::postgres::PgListener;
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+use tower_sessions_moka_store::MokaStore;
+
+use crate::{postgres_store::is_valid_identifier, PostgresStore, SqlxStoreError};
+
+/// A PostgreSQL store fronted by a local Moka cache, with cached sessions
+/// invalidated across instances via `LISTEN`/`NOTIFY`.
+///
+/// Loads are served from the cache when possible. Every save and delete is
+/// written through to PostgreSQL and announced on a notification channel, and
+/// each instance running [`PostgresCachedStore::listen`] evicts the announced
+/// session from its cache. This keeps multiple application instances from
+/// serving stale sessions, e.g. after a logout on another node.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions_moka_store::MokaStore;
+/// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresCachedStore, PostgresStore};
+///
+/// # tokio_test::block_on(async {
+/// let database_url = std::option_env!("DATABASE_URL").unwrap();
+/// let pool = PgPool::connect(database_url).await.unwrap();
+/// let postgres_store = PostgresStore::new(pool);
+/// postgres_store.migrate().await.unwrap();
+///
+/// let session_store = PostgresCachedStore::new(postgres_store, MokaStore::new(Some(2_000)));
+/// tokio::spawn({
+///     let session_store = session_store.clone();
+///     async move { session_store.listen().await }
+/// });
+/// # })
+/// ```
+#[derive(Clone, Debug)]
+pub struct PostgresCachedStore {
+    store: PostgresStore,
+    cache: MokaStore,
+    channel: String,
+    instance_id: String,
+    /// Bumped whenever a session has been written and is about to be evicted,
+    /// so that loads can tell whether the record they cached may be outdated
+    /// already.
+    invalidations: Arc<AtomicU64>,
+}
+
+impl PostgresCachedStore {
+    /// Create a new cached store from the provided PostgreSQL store and cache.
+    pub fn new(store: PostgresStore, cache: MokaStore) -> Self {
+        Self {
+            store,
+            cache,
+            channel: "tower_sessions_invalidate".to_string(),
+            instance_id: Id::default().to_string(),
+            invalidations: Arc::default(),
+        }
+    }
+
+    /// Set the notification channel with the provided name.
+    ///
+    /// All instances sharing sessions must use the same channel.
+    pub fn with_channel(mut self, channel: impl AsRef<str>) -> Result<Self, String> {
+        let channel = channel.as_ref();
+        if !is_valid_identifier(channel) {
+            return Err(format!(
+                "Invalid channel name '{}'. Channel names must start with a letter or underscore \
+                 (including letters with diacritical marks and non-Latin letters). Subsequent \
+                 characters can be letters, underscores, digits (0-9), or dollar signs ($).",
+                channel
+            ));
+        }
+
+        channel.clone_into(&mut self.channel);
+        Ok(self)
+    }
+
+    /// Evict sessions from the cache as other instances save or delete them.
+    ///
+    /// This runs until the connection pool is closed and should be spawned as
+    /// a background task. Notifications sent while the listener is
+    /// reconnecting are lost, so the whole cache is cleared whenever the
+    /// connection drops.
+    pub async fn listen(&self) -> session_store::Result<()> {
+        let mut listener = PgListener::connect_with(self.store.pool())
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+        listener
+            .listen(&self.channel)
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+
+        loop {
+            match listener.try_recv().await.map_err(SqlxStoreError::Sqlx)? {
+                Some(notification) => {
+                    let Some((instance_id, session_id)) = notification.payload().split_once(':')
+                    else {
+                        continue;
+                    };
+                    if instance_id == self.instance_id {
+                        continue;
+                    }
+                    if let Ok(session_id) = session_id.parse::<Id>() {
+                        self.invalidate();
+                        self.cache.delete(&session_id).await?;
+                    }
+                }
+                None => {
+                    self.invalidate();
+                    self.cache.clear();
+                }
+            }
+        }
+    }
+
+    fn invalidate(&self) {
+        self.invalidations.fetch_add(1, Ordering::SeqCst);
+    }
+
+    async fn notify(&self, session_id: &Id) -> session_store::Result<()> {
+        sqlx::query("select pg_notify($1, $2)")
+            .bind(&self.channel)
+            .bind(format!("{}:{}", self.instance_id, session_id))
+            .execute(self.store.pool())
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for PostgresCachedStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        // The cache expires its own entries.
+        self.store.delete_expired().await
+    }
+}
+
+#[async_trait]
+impl SessionStore for PostgresCachedStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        self.store.create(record).await?;
+        self.cache.save(record).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.store.save(record).await?;
+        self.invalidate();
+        self.cache.save(record).await?;
+        self.notify(&record.id).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        if let Some(record) = self.cache.load(session_id).await? {
+            return Ok(Some(record));
+        }
+
+        let invalidations = self.invalidations.load(Ordering::SeqCst);
+        let record = self.store.load(session_id).await?;
+        if let Some(record) = &record {
+            self.cache.save(record).await?;
+            // A session written or evicted since it was read may have been
+            // cached in its outdated state, so evict it again.
+            if self.invalidations.load(Ordering::SeqCst) != invalidations {
+                self.cache.delete(session_id).await?;
+            }
+        }
+        Ok(record)
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.store.delete(session_id).await?;
+        self.invalidate();
+        self.cache.delete(session_id).await?;
+        self.notify(session_id).await
+    }
+}
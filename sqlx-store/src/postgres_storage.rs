@@ -0,0 +1,131 @@
+/// Storage parameters applied to the PostgreSQL session table by `migrate`.
+///
+/// Session tables see a constant stream of inserts, updates, and deletes,
+/// which PostgreSQL's defaults are tuned for far less churn than. Leaving
+/// free space in each page lets updates stay on the same page, and lower
+/// autovacuum thresholds keep dead rows from piling up between vacuums.
+///
+/// Unset parameters are left as they are. See
+/// <https://www.postgresql.org/docs/current/sql-createtable.html#SQL-CREATETABLE-STORAGE-PARAMETERS>
+/// for details.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_sqlx_store::PostgresStorageParameters;
+///
+/// let storage_parameters = PostgresStorageParameters {
+///     fillfactor: Some(70),
+///     autovacuum_vacuum_scale_factor: Some(0.01),
+///     lz4_compression: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostgresStorageParameters {
+    /// The percentage of each table page filled by inserts, between 10 and
+    /// 100.
+    pub fillfactor: Option<u8>,
+
+    /// The percentage of each page of the primary key index filled by
+    /// inserts, between 10 and 100.
+    pub index_fillfactor: Option<u8>,
+
+    /// The share of the table's rows that must be dead before it is vacuumed.
+    pub autovacuum_vacuum_scale_factor: Option<f64>,
+
+    /// The number of dead rows needed, in addition to the scale factor,
+    /// before the table is vacuumed.
+    pub autovacuum_vacuum_threshold: Option<u32>,
+
+    /// The share of the table's rows that must change before it is analyzed.
+    pub autovacuum_analyze_scale_factor: Option<f64>,
+
+    /// The number of changed rows needed, in addition to the scale factor,
+    /// before the table is analyzed.
+    pub autovacuum_analyze_threshold: Option<u32>,
+
+    /// Compress large session data with LZ4 rather than PostgreSQL's default
+    /// `pglz`, which is both faster and usually compresses better.
+    ///
+    /// Requires PostgreSQL 14 or later built with LZ4 support. Only data
+    /// written afterwards is compressed with LZ4.
+    pub lz4_compression: bool,
+}
+
+impl PostgresStorageParameters {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for (name, fillfactor) in [
+            ("fillfactor", self.fillfactor),
+            ("index_fillfactor", self.index_fillfactor),
+        ] {
+            if fillfactor.is_some_and(|fillfactor| !(10..=100).contains(&fillfactor)) {
+                return Err(format!("Invalid {name}. It must be between 10 and 100."));
+            }
+        }
+
+        for (name, scale_factor) in [
+            (
+                "autovacuum_vacuum_scale_factor",
+                self.autovacuum_vacuum_scale_factor,
+            ),
+            (
+                "autovacuum_analyze_scale_factor",
+                self.autovacuum_analyze_scale_factor,
+            ),
+        ] {
+            if scale_factor.is_some_and(|scale_factor| !(0.0..=100.0).contains(&scale_factor)) {
+                return Err(format!("Invalid {name}. It must be between 0 and 100."));
+            }
+        }
+
+        for (name, threshold) in [
+            (
+                "autovacuum_vacuum_threshold",
+                self.autovacuum_vacuum_threshold,
+            ),
+            (
+                "autovacuum_analyze_threshold",
+                self.autovacuum_analyze_threshold,
+            ),
+        ] {
+            if threshold.is_some_and(|threshold| threshold > i32::MAX as u32) {
+                return Err(format!("Invalid {name}. It must be at most {}.", i32::MAX));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The table's storage parameters as a `SET` list, if any are set.
+    pub(crate) fn table_options(&self) -> Option<String> {
+        let options: Vec<String> = [
+            ("fillfactor", self.fillfactor.map(|value| value.to_string())),
+            (
+                "autovacuum_vacuum_scale_factor",
+                self.autovacuum_vacuum_scale_factor
+                    .map(|value| value.to_string()),
+            ),
+            (
+                "autovacuum_vacuum_threshold",
+                self.autovacuum_vacuum_threshold
+                    .map(|value| value.to_string()),
+            ),
+            (
+                "autovacuum_analyze_scale_factor",
+                self.autovacuum_analyze_scale_factor
+                    .map(|value| value.to_string()),
+            ),
+            (
+                "autovacuum_analyze_threshold",
+                self.autovacuum_analyze_threshold
+                    .map(|value| value.to_string()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some(format!("{name} = {}", value?)))
+        .collect();
+
+        (!options.is_empty()).then(|| options.join(", "))
+    }
+}
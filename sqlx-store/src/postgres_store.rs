@@ -1,19 +1,109 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
 use async_trait::async_trait;
-use sqlx::{PgConnection, PgPool};
+use serde::Deserialize;
+use sqlx::{pool::PoolConnection, Executor, PgConnection, PgPool, Postgres};
 use time::OffsetDateTime;
 use tower_sessions_core::{
     session::{Id, Record},
     session_store, ExpiredDeletion, SessionStore,
 };
+use tower_sessions_ext::{
+    warn, DecodeFailurePolicy, ExportSessions, IdCollisionPolicy, SlowOpTracer, StoreOptions,
+    StoreOptionsConfig, TouchSessionStore, TtlPolicy,
+};
+
+use crate::{
+    decode_record, encode_record, encode_record_data, ColumnNames, PoolStatus,
+    PostgresStorageParameters, SqlxStoreError,
+};
+
+/// Whether [`PostgresStore::save_returning_status`] inserted or updated a
+/// session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostgresSaveStatus {
+    /// The session didn't exist and was inserted.
+    Created,
+
+    /// An existing session was updated.
+    Updated,
+
+    /// The session was soft deleted and was left as it is.
+    SoftDeleted,
+}
+
+/// The settings of a [`PostgresStore`], as read from a configuration file.
+///
+/// The options shared by every store, such as the `namespace` naming the
+/// session table, are written next to these settings. Settings that are left
+/// out keep the store's defaults.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_sqlx_store::PostgresStoreConfig;
+///
+/// let config: PostgresStoreConfig = serde_json::from_str(
+///     r#"{ "schema_name": "auth", "namespace": "sessions", "soft_delete_column": "deleted_at" }"#,
+/// )
+/// .unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PostgresStoreConfig {
+    /// The options shared by every store.
+    #[serde(flatten)]
+    pub options: StoreOptionsConfig,
+
+    /// Passed to [`PostgresStore::with_schema_name`].
+    pub schema_name: Option<String>,
 
-use crate::SqlxStoreError;
+    /// Passed to [`PostgresStore::with_soft_delete`].
+    pub soft_delete_column: Option<String>,
+
+    /// Passed to [`PostgresStore::with_data_table`].
+    pub data_table: Option<String>,
+
+    /// Passed to [`PostgresStore::with_load_retry`].
+    pub load_retry: Option<bool>,
+
+    /// Passed to [`PostgresStore::with_pgbouncer_compat`].
+    pub pgbouncer_compat: bool,
+
+    /// Passed to [`PostgresStore::with_cooperative_deletion`].
+    pub cooperative_deletion_batch_size: Option<u32>,
+}
 
 /// A PostgreSQL session store.
+///
+/// Operations that fail because the primary failed over, i.e. with a lost
+/// connection or one of the SQLSTATEs sent by a server shutting down or
+/// demoted to a replica, are retried once on a fresh connection. The failed
+/// connection is closed rather than returned to the pool.
 #[derive(Clone, Debug)]
 pub struct PostgresStore {
     pool: PgPool,
     schema_name: String,
     table_name: String,
+    columns: ColumnNames,
+    fixed_columns: Vec<(String, String)>,
+    soft_delete_column: Option<String>,
+    activity_columns: Option<(String, String)>,
+    audit_columns: Option<(String, String)>,
+    data_table: Option<String>,
+    storage_parameters: PostgresStorageParameters,
+    load_retry: bool,
+    pgbouncer_compat: bool,
+    cooperative_deletion_batch_size: Option<u32>,
+    ttl_policy: TtlPolicy,
+    id_collision_policy: IdCollisionPolicy,
+    decode_failure_policy: DecodeFailurePolicy,
+    slow_ops: SlowOpTracer,
+    queries: Queries,
 }
 
 impl PostgresStore {
@@ -31,11 +121,77 @@ impl PostgresStore {
     /// # })
     /// ```
     pub fn new(pool: PgPool) -> Self {
+        let schema_name = "tower_sessions".to_string();
+        let table_name = "session".to_string();
+        let columns = ColumnNames::default();
         Self {
             pool,
-            schema_name: "tower_sessions".to_string(),
-            table_name: "session".to_string(),
+            queries: Queries::new(
+                &schema_name,
+                &table_name,
+                &columns,
+                &[],
+                None,
+                None,
+                None,
+                None,
+            ),
+            schema_name,
+            table_name,
+            columns,
+            fixed_columns: Vec::new(),
+            soft_delete_column: None,
+            activity_columns: None,
+            audit_columns: None,
+            data_table: None,
+            storage_parameters: PostgresStorageParameters::default(),
+            load_retry: true,
+            pgbouncer_compat: false,
+            cooperative_deletion_batch_size: None,
+            ttl_policy: TtlPolicy::default(),
+            id_collision_policy: IdCollisionPolicy::default(),
+            decode_failure_policy: DecodeFailurePolicy::default(),
+            slow_ops: SlowOpTracer::new("postgres"),
+        }
+    }
+
+    /// Create a new PostgreSQL store with the provided connection pool,
+    /// configured with the provided settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore, PostgresStoreConfig};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let config: PostgresStoreConfig =
+    ///     serde_json::from_str(r#"{ "namespace": "sessions" }"#).unwrap();
+    /// let session_store = PostgresStore::try_from_config(pool, &config).unwrap();
+    /// # })
+    /// ```
+    pub fn try_from_config(pool: PgPool, config: &PostgresStoreConfig) -> Result<Self, String> {
+        let mut store = config
+            .options
+            .apply(Self::new(pool))?
+            .with_pgbouncer_compat(config.pgbouncer_compat);
+        if let Some(schema_name) = &config.schema_name {
+            store = store.with_schema_name(schema_name)?;
+        }
+        if let Some(column_name) = &config.soft_delete_column {
+            store = store.with_soft_delete(column_name)?;
+        }
+        if let Some(table_name) = &config.data_table {
+            store = store.with_data_table(table_name)?;
+        }
+        if let Some(batch_size) = config.cooperative_deletion_batch_size {
+            store = store.with_cooperative_deletion(batch_size);
+        }
+        if let Some(load_retry) = config.load_retry {
+            store = store.with_load_retry(load_retry);
         }
+        Ok(store)
     }
 
     /// Set the session table schema name with the provided name.
@@ -51,6 +207,7 @@ impl PostgresStore {
         }
 
         schema_name.clone_into(&mut self.schema_name);
+        self.render_queries();
         Ok(self)
     }
 
@@ -67,9 +224,417 @@ impl PostgresStore {
         }
 
         table_name.clone_into(&mut self.table_name);
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Set the session table column names with the provided names.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, ColumnNames, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let session_store = PostgresStore::new(pool)
+    ///     .with_schema_name("public")
+    ///     .unwrap()
+    ///     .with_table_name("django_session")
+    ///     .unwrap()
+    ///     .with_column_names(ColumnNames {
+    ///         id: "session_key".to_string(),
+    ///         data: "session_data".to_string(),
+    ///         expiry_date: "expire_date".to_string(),
+    ///     })
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub fn with_column_names(mut self, columns: ColumnNames) -> Result<Self, String> {
+        if let Some(column_name) = columns.find_invalid(is_valid_identifier) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.columns = columns;
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Add a column that is populated with the provided value whenever a
+    /// session is inserted.
+    ///
+    /// This is useful for pre-existing session tables with additional
+    /// non-null columns the store does not otherwise know about.
+    pub fn with_fixed_column(
+        mut self,
+        column_name: impl AsRef<str>,
+        value: impl Into<String>,
+    ) -> Result<Self, String> {
+        let column_name = column_name.as_ref();
+        if !is_valid_identifier(column_name) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.fixed_columns
+            .push((column_name.to_string(), value.into()));
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Mark deleted sessions with a deletion timestamp in the provided column
+    /// instead of removing their rows.
+    ///
+    /// Soft-deleted sessions can no longer be loaded, saved, or touched, and
+    /// are kept by [`ExpiredDeletion::delete_expired`] so that they remain
+    /// available for later investigation. Use [`PostgresStore::purge_deleted`]
+    /// to remove them for good.
+    ///
+    /// The column is created by [`PostgresStore::migrate`]; existing tables must
+    /// be altered to add a nullable column with this name.
+    pub fn with_soft_delete(mut self, column_name: impl AsRef<str>) -> Result<Self, String> {
+        let column_name = column_name.as_ref();
+        if !is_valid_identifier(column_name) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.soft_delete_column = Some(column_name.to_string());
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Record when each session was created and last used in the provided
+    /// columns.
+    ///
+    /// The creation column is set when a session is first saved, and the
+    /// last-seen column whenever the session is saved, loaded, or touched, both
+    /// using the database clock. This allows reviewing idle or long-lived
+    /// sessions from the database without decoding session data, at the cost of
+    /// turning each load into an update.
+    ///
+    /// The columns are created by [`PostgresStore::migrate`]; existing tables
+    /// must be altered to add them, e.g. as `timestamptz not null default
+    /// now()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let session_store = PostgresStore::new(pool)
+    ///     .with_activity_columns("created_at", "last_seen")
+    ///     .unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_activity_columns(
+        mut self,
+        created_at_column: impl AsRef<str>,
+        last_seen_column: impl AsRef<str>,
+    ) -> Result<Self, String> {
+        let created_at_column = created_at_column.as_ref();
+        let last_seen_column = last_seen_column.as_ref();
+        for column_name in [created_at_column, last_seen_column] {
+            if !is_valid_identifier(column_name) {
+                return Err(invalid_column_name(column_name));
+            }
+        }
+        if self.audit_columns.is_some() {
+            return Err(ACTIVITY_WITH_AUDIT.to_string());
+        }
+
+        self.activity_columns = Some((created_at_column.to_string(), last_seen_column.to_string()));
+        self.render_queries();
         Ok(self)
     }
 
+    /// Record when each session was created and last written in the provided
+    /// columns.
+    ///
+    /// The creation column is set when a session is first saved, and the
+    /// update column whenever the session is saved or touched, both using the
+    /// database clock. Unlike [`PostgresStore::with_activity_columns`], loads
+    /// are left as plain reads, so this suits session analytics in SQL, e.g.
+    /// counting sessions created per day, without decoding session data.
+    ///
+    /// The columns are created by [`PostgresStore::migrate`]; existing tables
+    /// must be altered to add them, e.g. as `timestamptz not null default
+    /// now()`. This can't be combined with
+    /// [`PostgresStore::with_activity_columns`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let session_store = PostgresStore::new(pool)
+    ///     .with_audit_columns("created_at", "updated_at")
+    ///     .unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_audit_columns(
+        mut self,
+        created_at_column: impl AsRef<str>,
+        updated_at_column: impl AsRef<str>,
+    ) -> Result<Self, String> {
+        let created_at_column = created_at_column.as_ref();
+        let updated_at_column = updated_at_column.as_ref();
+        for column_name in [created_at_column, updated_at_column] {
+            if !is_valid_identifier(column_name) {
+                return Err(invalid_column_name(column_name));
+            }
+        }
+        if self.activity_columns.is_some() {
+            return Err(ACTIVITY_WITH_AUDIT.to_string());
+        }
+
+        self.audit_columns = Some((created_at_column.to_string(), updated_at_column.to_string()));
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Keep session data in a separate table with the provided name, written
+    /// only when the data changes, so that the session table only holds IDs,
+    /// expiry dates, and other narrow columns.
+    ///
+    /// Sessions are mostly saved to extend their expiry, which otherwise
+    /// rewrites their whole data, including any out-of-line TOAST storage.
+    /// With logical replication or change data capture, every such write is
+    /// sent downstream in full. With a data table, extending a session only
+    /// updates its narrow row, and the data table is upserted by primary key
+    /// with a condition that skips unchanged data, so that only actual
+    /// changes to session data are replicated.
+    ///
+    /// The data table references the session table and its rows are deleted
+    /// along with their sessions. Both tables are created by
+    /// [`PostgresStore::migrate`]; existing session tables keep their data
+    /// column, so point this store at new tables and copy sessions over, e.g.
+    /// with `migrate_sessions`. LZ4 compression set with
+    /// [`PostgresStore::with_storage_parameters`] applies to the data table.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let session_store = PostgresStore::new(pool)
+    ///     .with_table_name("session_expiry")
+    ///     .unwrap()
+    ///     .with_data_table("session_data")
+    ///     .unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_data_table(mut self, table_name: impl AsRef<str>) -> Result<Self, String> {
+        let table_name = table_name.as_ref();
+        if !is_valid_identifier(table_name) {
+            return Err(format!(
+                "Invalid table name '{}'. Table names must start with a letter or underscore \
+                 (including letters with diacritical marks and non-Latin letters).Subsequent \
+                 characters can be letters, underscores, digits (0-9), or dollar signs ($).",
+                table_name
+            ));
+        }
+
+        self.data_table = Some(table_name.to_string());
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Set storage parameters of the session table and its primary key index,
+    /// such as fillfactor, autovacuum thresholds, and TOAST compression.
+    ///
+    /// The parameters are applied by [`PostgresStore::migrate`], to new and
+    /// existing tables alike.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStorageParameters, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let session_store = PostgresStore::new(pool)
+    ///     .with_storage_parameters(PostgresStorageParameters {
+    ///         fillfactor: Some(70),
+    ///         autovacuum_vacuum_scale_factor: Some(0.01),
+    ///         autovacuum_vacuum_threshold: Some(1_000),
+    ///         lz4_compression: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_storage_parameters(
+        mut self,
+        storage_parameters: PostgresStorageParameters,
+    ) -> Result<Self, String> {
+        storage_parameters.validate()?;
+        self.storage_parameters = storage_parameters;
+        Ok(self)
+    }
+
+    /// Set whether `load` is retried once on a fresh connection if its
+    /// connection was reset or the primary failed over.
+    ///
+    /// Loads don't change the session data, so retrying them is always safe,
+    /// and a connection dropped by a restart or failover then doesn't fail the
+    /// request. This is enabled by default; disable it to return such errors
+    /// immediately, e.g. when a retry wrapper already handles transient
+    /// errors. Writes are retried after a failover either way.
+    pub fn with_load_retry(mut self, load_retry: bool) -> Self {
+        self.load_retry = load_retry;
+        self
+    }
+
+    /// Set whether the store works behind pgBouncer in transaction pooling
+    /// mode.
+    ///
+    /// In that mode, pgBouncer may hand each transaction, or each statement
+    /// outside of one, to a different server connection, while sqlx prepares
+    /// named statements once per connection and reuses them. This fails with
+    /// errors such as `prepared statement "sqlx_s_1" does not exist`. With
+    /// compatibility enabled, statements are prepared unnamed for each use,
+    /// and each operation runs in a transaction so that its statement is
+    /// prepared and executed on the same server connection. This costs extra
+    /// round trips, so it's disabled by default; it isn't needed with session
+    /// pooling, or with pgBouncer 1.21 or later with `max_prepared_statements`
+    /// set.
+    ///
+    /// `PostgresCachedStore` listens for notifications, which transaction
+    /// pooling doesn't support.
+    pub fn with_pgbouncer_compat(mut self, pgbouncer_compat: bool) -> Self {
+        self.pgbouncer_compat = pgbouncer_compat;
+        self
+    }
+
+    /// Delete expired sessions in batches of the provided size, skipping rows
+    /// locked by other instances, so that instances running
+    /// [`ExpiredDeletion::delete_expired`] at the same time share the sweep.
+    ///
+    /// By default a single statement deletes every expired session, so
+    /// instances sweeping at once wait on each other's row locks and then
+    /// find the rows already gone, or deadlock when they lock rows in
+    /// different orders. With this enabled, each batch selects its rows with
+    /// `FOR UPDATE SKIP LOCKED` and commits on its own, so instances delete
+    /// disjoint batches and locks are held only briefly.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let session_store = PostgresStore::new(pool).with_cooperative_deletion(1_000);
+    /// # })
+    /// ```
+    pub fn with_cooperative_deletion(mut self, batch_size: u32) -> Self {
+        self.cooperative_deletion_batch_size = Some(batch_size.max(1));
+        self
+    }
+
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    /// Set how `load` responds to a session whose stored data can't be
+    /// decoded, e.g. after the serialization format has changed.
+    pub fn with_decode_failure_policy(
+        mut self,
+        decode_failure_policy: DecodeFailurePolicy,
+    ) -> Self {
+        self.decode_failure_policy = decode_failure_policy;
+        self
+    }
+
+    /// Log operations taking longer than the provided threshold with
+    /// `tracing::warn!`.
+    pub fn with_slow_op_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_ops = self.slow_ops.with_threshold(threshold);
+        self
+    }
+
+    /// Set the policy adjusting the expiry dates written to the expiry
+    /// column.
+    pub fn with_ttl_policy(mut self, ttl_policy: TtlPolicy) -> Self {
+        self.ttl_policy = ttl_policy;
+        self
+    }
+
+    /// Open a pooled connection ahead of the first operation.
+    ///
+    /// A store created with a lazily connecting pool, e.g. via
+    /// [`PgPool::connect_lazy`](sqlx::PgPool::connect_lazy), lets an
+    /// application start while the database is unavailable, with each session
+    /// operation connecting as needed. Call this at startup to connect early
+    /// and surface connection problems.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect_lazy(database_url).unwrap();
+    /// let session_store = PostgresStore::new(pool);
+    /// session_store.warm_up().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn warm_up(&self) -> sqlx::Result<()> {
+        self.pool.acquire().await?;
+        Ok(())
+    }
+
+    /// Take a snapshot of the connection pool's health, including how long
+    /// it currently takes to acquire a connection.
+    ///
+    /// Measuring the wait acquires a connection, opening one if none are idle
+    /// and the pool isn't full.
+    pub async fn pool_status(&self) -> sqlx::Result<PoolStatus> {
+        PoolStatus::probe(&self.pool).await
+    }
+
+    /// Log a snapshot of the connection pool's health with `tracing::info!`,
+    /// or with `tracing::warn!` if the pool is saturated, e.g. periodically.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub async fn log_pool_status(&self) {
+        PoolStatus::log(self.pool_status().await, "postgres");
+    }
+
+    /// The statements [`PostgresStore::migrate`] runs to create the session
+    /// schema, e.g. for reviewing them or applying them with other migration
+    /// tooling.
+    ///
+    /// Storage parameters set via [`PostgresStore::with_storage_parameters`]
+    /// are applied by `migrate` afterwards and aren't included.
+    pub fn migration_sql(&self) -> Vec<String> {
+        let mut statements = vec![
+            self.queries.create_schema.clone(),
+            self.queries.create_table.clone(),
+        ];
+        statements.extend(self.queries.create_data_table.clone());
+        statements
+    }
+
     /// Migrate the session schema.
     ///
     /// # Examples
@@ -87,14 +652,14 @@ impl PostgresStore {
     pub async fn migrate(&self) -> sqlx::Result<()> {
         let mut tx = self.pool.begin().await?;
 
-        let create_schema_query = format!(
-            r#"create schema if not exists "{schema_name}""#,
-            schema_name = self.schema_name,
-        );
         // Concurrent create schema may fail due to duplicate key violations.
         //
         // This works around that by assuming the schema must exist on such an error.
-        if let Err(err) = sqlx::query(&create_schema_query).execute(&mut *tx).await {
+        if let Err(err) = sqlx::query(&self.queries.create_schema)
+            .persistent(!self.pgbouncer_compat)
+            .execute(&mut *tx)
+            .await
+        {
             if !err
                 .to_string()
                 .contains("duplicate key value violates unique constraint")
@@ -105,155 +670,803 @@ impl PostgresStore {
             return Ok(());
         }
 
-        let create_table_query = format!(
-            r#"
-            create table if not exists "{schema_name}"."{table_name}"
-            (
-                id text primary key not null,
-                data bytea not null,
-                expiry_date timestamptz not null
-            )
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&create_table_query).execute(&mut *tx).await?;
+        sqlx::query(&self.queries.create_table)
+            .persistent(!self.pgbouncer_compat)
+            .execute(&mut *tx)
+            .await?;
+        if let Some(create_data_table) = &self.queries.create_data_table {
+            sqlx::query(create_data_table)
+                .persistent(!self.pgbouncer_compat)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        self.apply_storage_parameters(&mut tx).await?;
 
         tx.commit().await?;
 
         Ok(())
     }
 
-    async fn id_exists(&self, conn: &mut PgConnection, id: &Id) -> session_store::Result<bool> {
-        let query = format!(
-            r#"
-            select exists(select 1 from "{schema_name}"."{table_name}" where id = $1)
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
+    async fn apply_storage_parameters(&self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        let (schema_name, table_name) = (&self.schema_name, &self.table_name);
+        let parameters = &self.storage_parameters;
+
+        if let Some(options) = parameters.table_options() {
+            sqlx::query(&format!(
+                r#"alter table "{schema_name}"."{table_name}" set ({options})"#
+            ))
+            .persistent(!self.pgbouncer_compat)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        if let Some(fillfactor) = parameters.index_fillfactor {
+            // The index name is looked up, as it may not be the default one
+            // for tables created elsewhere.
+            let index_name: String = sqlx::query_scalar(
+                r#"
+                select indexrelid::regclass::text from pg_index
+                where indrelid = $1::regclass and indisprimary
+                "#,
+            )
+            .bind(format!(r#""{schema_name}"."{table_name}""#))
+            .persistent(!self.pgbouncer_compat)
+            .fetch_one(&mut *conn)
+            .await?;
+            sqlx::query(&format!(
+                "alter index {index_name} set (fillfactor = {fillfactor})"
+            ))
+            .persistent(!self.pgbouncer_compat)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        if parameters.lz4_compression {
+            let data = &self.columns.data;
+            let table_name = self.data_table.as_ref().unwrap_or(table_name);
+            sqlx::query(&format!(
+                r#"alter table "{schema_name}"."{table_name}" alter column "{data}" set compression lz4"#
+            ))
+            .persistent(!self.pgbouncer_compat)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "postgres-moka")]
+    pub(crate) fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    fn render_queries(&mut self) {
+        self.queries = Queries::new(
+            &self.schema_name,
+            &self.table_name,
+            &self.columns,
+            &self.fixed_columns,
+            self.soft_delete_column.as_deref(),
+            self.activity_columns
+                .as_ref()
+                .map(|(created_at, last_seen)| (created_at.as_str(), last_seen.as_str())),
+            self.audit_columns
+                .as_ref()
+                .map(|(created_at, updated_at)| (created_at.as_str(), updated_at.as_str())),
+            self.data_table.as_deref(),
         );
+    }
 
-        Ok(sqlx::query_scalar(&query)
-            .bind(id.to_string())
-            .fetch_one(conn)
+    /// Save the record, returning whether it was inserted or an existing
+    /// session was updated.
+    ///
+    /// This lets applications tell new sessions apart from refreshes of
+    /// existing ones, e.g. in metrics, without another query: the upsert
+    /// reports which it did with `RETURNING`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::session::Record;
+    /// use tower_sessions_sqlx_store::{PostgresSaveStatus, PostgresStore};
+    ///
+    /// # async fn save(session_store: &PostgresStore, record: &Record) {
+    /// match session_store.save_returning_status(record).await.unwrap() {
+    ///     PostgresSaveStatus::Created => println!("new session {}", record.id),
+    ///     PostgresSaveStatus::Updated => println!("refreshed session {}", record.id),
+    ///     PostgresSaveStatus::SoftDeleted => println!("session {} was deleted", record.id),
+    /// }
+    /// # }
+    /// ```
+    pub async fn save_returning_status(
+        &self,
+        record: &Record,
+    ) -> session_store::Result<PostgresSaveStatus> {
+        let started = self.slow_ops.start();
+        let res = self
+            .with_failover_retry(|mut conn| async move {
+                let res = self.save_with_conn(&mut conn, record).await;
+                (conn, res)
+            })
             .await
-            .map_err(SqlxStoreError::Sqlx)?)
+            .map_err(Into::into);
+        self.slow_ops.finish(started, "save", Some(record));
+        res
+    }
+
+    /// Permanently remove sessions soft-deleted before the provided date,
+    /// returning the number of sessions removed.
+    ///
+    /// This does nothing unless soft deletion is enabled via
+    /// [`PostgresStore::with_soft_delete`].
+    pub async fn purge_deleted(
+        &self,
+        deleted_before: OffsetDateTime,
+    ) -> session_store::Result<u64> {
+        let Some(purge_deleted) = &self.queries.purge_deleted else {
+            return Ok(0);
+        };
+
+        let res = self
+            .with_conn(|mut conn| async move {
+                let res = sqlx::query(purge_deleted)
+                    .bind(deleted_before)
+                    .persistent(!self.pgbouncer_compat)
+                    .execute(&mut *conn)
+                    .await;
+                (conn, res.map_err(SqlxStoreError::Sqlx))
+            })
+            .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Run the operation on a pooled connection, retrying it once on a fresh
+    /// connection if it failed because the primary failed over.
+    ///
+    /// The operation is handed the connection and gives it back, so that a
+    /// connection to a former primary can be closed rather than returned to
+    /// the pool.
+    async fn with_failover_retry<T, F, Fut>(&self, op: F) -> Result<T, SqlxStoreError>
+    where
+        F: Fn(PoolConnection<Postgres>) -> Fut,
+        Fut: Future<Output = (PoolConnection<Postgres>, Result<T, SqlxStoreError>)>,
+    {
+        let conn = self.pool.acquire().await?;
+        match self.pinned(conn, &op).await {
+            (mut conn, Err(err)) if is_failover_error(&err) => {
+                warn!(error = %err, "retrying session operation after database failover");
+                conn.close_on_drop();
+                drop(conn);
+
+                let conn = self.pool.acquire().await?;
+                self.pinned(conn, &op).await.1
+            }
+            (_, res) => res,
+        }
+    }
+
+    /// Run the operation on a pooled connection.
+    async fn with_conn<T, F, Fut>(&self, op: F) -> Result<T, SqlxStoreError>
+    where
+        F: FnOnce(PoolConnection<Postgres>) -> Fut,
+        Fut: Future<Output = (PoolConnection<Postgres>, Result<T, SqlxStoreError>)>,
+    {
+        let conn = self.pool.acquire().await?;
+        self.pinned(conn, op).await.1
+    }
+
+    /// Run the operation on the connection, in a transaction if pgBouncer
+    /// compatibility is enabled, so that pgBouncer executes each statement on
+    /// the server connection it was prepared on.
+    async fn pinned<T, F, Fut>(
+        &self,
+        mut conn: PoolConnection<Postgres>,
+        op: F,
+    ) -> (PoolConnection<Postgres>, Result<T, SqlxStoreError>)
+    where
+        F: FnOnce(PoolConnection<Postgres>) -> Fut,
+        Fut: Future<Output = (PoolConnection<Postgres>, Result<T, SqlxStoreError>)>,
+    {
+        if !self.pgbouncer_compat {
+            return op(conn).await;
+        }
+
+        // Sent as simple queries, which are never prepared.
+        if let Err(err) = conn.execute("begin").await {
+            return (conn, Err(err.into()));
+        }
+        let (mut conn, res) = op(conn).await;
+        let end = if res.is_ok() { "commit" } else { "rollback" };
+        match conn.execute(end).await {
+            Err(err) if res.is_ok() => (conn, Err(err.into())),
+            _ => (conn, res),
+        }
+    }
+
+    /// Serialize the record for the data column, in a stable encoding when
+    /// unchanged data must be recognized.
+    fn encode(&self, record: &Record) -> Result<Vec<u8>, SqlxStoreError> {
+        match self.data_table {
+            Some(_) => encode_record_data(record),
+            None => encode_record(record),
+        }
+    }
+
+    /// Insert the record unless its ID is taken, in a single round trip,
+    /// returning whether it was inserted.
+    async fn try_create_with_conn(
+        &self,
+        conn: &mut PgConnection,
+        record: &Record,
+    ) -> Result<bool, SqlxStoreError> {
+        let record = &*self.ttl_policy.apply(record);
+        let mut query = sqlx::query_scalar::<_, String>(&self.queries.insert)
+            .bind(record.id.to_string())
+            .bind(self.encode(record)?)
+            .bind(record.expiry_date)
+            .persistent(!self.pgbouncer_compat);
+        for (_, value) in &self.fixed_columns {
+            query = query.bind(value.as_str());
+        }
+        let inserted = query.fetch_optional(conn).await?;
+
+        Ok(inserted.is_some())
+    }
+
+    /// Returns `true` if the stored session with the record's ID holds the
+    /// record's data, i.e. it was inserted by an earlier attempt rather than
+    /// by another session given the same ID.
+    async fn was_created_with_conn(
+        &self,
+        conn: &mut PgConnection,
+        record: &Record,
+    ) -> Result<bool, SqlxStoreError> {
+        let stored = self.load_with_conn(conn, &record.id).await?;
+        Ok(stored.is_some_and(|stored| stored.data == record.data))
     }
 
     async fn save_with_conn(
         &self,
         conn: &mut PgConnection,
         record: &Record,
-    ) -> session_store::Result<()> {
-        let query = format!(
-            r#"
-            insert into "{schema_name}"."{table_name}" (id, data, expiry_date)
-            values ($1, $2, $3)
-            on conflict (id) do update
-            set
-              data = excluded.data,
-              expiry_date = excluded.expiry_date
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
+    ) -> Result<PostgresSaveStatus, SqlxStoreError> {
+        let record = &*self.ttl_policy.apply(record);
+        let mut query = sqlx::query_scalar::<_, bool>(&self.queries.upsert)
             .bind(record.id.to_string())
-            .bind(rmp_serde::to_vec(&record).map_err(SqlxStoreError::Encode)?)
+            .bind(self.encode(record)?)
             .bind(record.expiry_date)
-            .execute(conn)
-            .await
-            .map_err(SqlxStoreError::Sqlx)?;
+            .persistent(!self.pgbouncer_compat);
+        for (_, value) in &self.fixed_columns {
+            query = query.bind(value.as_str());
+        }
+        let inserted = query.fetch_optional(conn).await?;
+
+        Ok(match inserted {
+            Some(true) => PostgresSaveStatus::Created,
+            Some(false) => PostgresSaveStatus::Updated,
+            None => PostgresSaveStatus::SoftDeleted,
+        })
+    }
+
+    async fn load_with_conn(
+        &self,
+        conn: &mut PgConnection,
+        session_id: &Id,
+    ) -> Result<Option<Record>, SqlxStoreError> {
+        let record_value: Option<(Vec<u8>, OffsetDateTime)> = sqlx::query_as(&self.queries.load)
+            .bind(session_id.to_string())
+            .bind(OffsetDateTime::now_utc())
+            .persistent(!self.pgbouncer_compat)
+            .fetch_optional(conn)
+            .await?;
+
+        if let Some((data, expiry_date)) = record_value {
+            let mut record: Record = decode_record(&data)?;
+            record.expiry_date = expiry_date;
+            Ok(Some(record))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete_with_conn(
+        &self,
+        conn: &mut PgConnection,
+        session_id: &Id,
+    ) -> Result<(), SqlxStoreError> {
+        let mut query = sqlx::query(&self.queries.delete).persistent(!self.pgbouncer_compat);
+        if self.soft_delete_column.is_some() {
+            query = query.bind(OffsetDateTime::now_utc());
+        }
+        query.bind(session_id.to_string()).execute(conn).await?;
 
         Ok(())
     }
 }
 
+/// SQLSTATEs failing statements while the primary fails over or restarts:
+/// `admin_shutdown`, `crash_shutdown`, and `cannot_connect_now`, as well as
+/// `read_only_sql_transaction` from a former primary that has been demoted.
+const FAILOVER_SQLSTATES: [&str; 4] = ["57P01", "57P02", "57P03", "25006"];
+
+/// Returns `true` if the error is likely caused by a failover, i.e. the
+/// connection was lost or the server is no longer the primary.
+fn is_failover_error(err: &SqlxStoreError) -> bool {
+    match err {
+        SqlxStoreError::Sqlx(sqlx::Error::Io(_)) => true,
+        SqlxStoreError::Sqlx(sqlx::Error::Database(err)) => err.code().is_some_and(|code| {
+            // SQLSTATE class 08 (connection exception).
+            code.starts_with("08") || FAILOVER_SQLSTATES.contains(&&*code)
+        }),
+        _ => false,
+    }
+}
+
+impl StoreOptions for PostgresStore {
+    fn with_namespace(self, namespace: &str) -> Result<Self, String> {
+        self.with_table_name(namespace)
+    }
+
+    fn with_id_collision_policy(self, id_collision_policy: IdCollisionPolicy) -> Self {
+        Self::with_id_collision_policy(self, id_collision_policy)
+    }
+
+    fn with_decode_failure_policy(self, decode_failure_policy: DecodeFailurePolicy) -> Self {
+        Self::with_decode_failure_policy(self, decode_failure_policy)
+    }
+
+    fn with_slow_op_threshold(self, threshold: Duration) -> Self {
+        Self::with_slow_op_threshold(self, threshold)
+    }
+
+    fn with_ttl_policy(self, ttl_policy: TtlPolicy) -> Self {
+        Self::with_ttl_policy(self, ttl_policy)
+    }
+}
+
 #[async_trait]
 impl ExpiredDeletion for PostgresStore {
     async fn delete_expired(&self) -> session_store::Result<()> {
-        let query = format!(
-            r#"
-            delete from "{schema_name}"."{table_name}"
-            where expiry_date < (now() at time zone 'utc')
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
-            .execute(&self.pool)
-            .await
-            .map_err(SqlxStoreError::Sqlx)?;
-        Ok(())
+        let Some(batch_size) = self.cooperative_deletion_batch_size else {
+            self.with_conn(|mut conn| async move {
+                let res = sqlx::query(&self.queries.delete_expired)
+                    .persistent(!self.pgbouncer_compat)
+                    .execute(&mut *conn)
+                    .await;
+                (conn, res.map_err(SqlxStoreError::Sqlx))
+            })
+            .await?;
+            return Ok(());
+        };
+
+        loop {
+            let deleted = self
+                .with_conn(|mut conn| async move {
+                    let res = sqlx::query(&self.queries.delete_expired_batch)
+                        .bind(i64::from(batch_size))
+                        .persistent(!self.pgbouncer_compat)
+                        .execute(&mut *conn)
+                        .await;
+                    (conn, res.map_err(SqlxStoreError::Sqlx))
+                })
+                .await?
+                .rows_affected();
+
+            // A short batch means the remaining expired sessions are gone or
+            // locked by another instance deleting them.
+            if deleted < u64::from(batch_size) {
+                return Ok(());
+            }
+        }
     }
 }
 
 #[async_trait]
 impl SessionStore for PostgresStore {
     async fn create(&self, record: &mut Record) -> session_store::Result<()> {
-        let mut tx = self.pool.begin().await.map_err(SqlxStoreError::Sqlx)?;
-
-        while self.id_exists(&mut tx, &record.id).await? {
-            record.id = Id::default();
-        }
-        self.save_with_conn(&mut tx, record).await?;
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            let mut collisions = 0;
+            loop {
+                let candidate = &*record;
+                let attempted = AtomicBool::new(false);
+                let inserted = self
+                    .with_failover_retry(|mut conn| {
+                        let retried = attempted.swap(true, Ordering::Relaxed);
+                        async move {
+                            let res = match self.try_create_with_conn(&mut conn, candidate).await {
+                                // The first attempt may have inserted the
+                                // record before its connection was lost.
+                                Ok(false) if retried => {
+                                    self.was_created_with_conn(&mut conn, candidate).await
+                                }
+                                res => res,
+                            };
+                            (conn, res)
+                        }
+                    })
+                    .await?;
+                if inserted {
+                    break;
+                }
 
-        tx.commit().await.map_err(SqlxStoreError::Sqlx)?;
+                collisions += 1;
+                record.id = self.id_collision_policy.next_id(collisions)?;
+            }
 
-        Ok(())
+            Ok(())
+        }
+        .await;
+        self.slow_ops.finish(started, "create", Some(record));
+        res
     }
 
     async fn save(&self, record: &Record) -> session_store::Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(SqlxStoreError::Sqlx)?;
-        self.save_with_conn(&mut conn, record).await
+        self.save_returning_status(record).await?;
+        Ok(())
     }
 
     async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
-        let query = format!(
-            r#"
-            select data from "{schema_name}"."{table_name}"
-            where id = $1 and expiry_date > $2
-            "#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        let record_value: Option<(Vec<u8>,)> = sqlx::query_as(&query)
-            .bind(session_id.to_string())
-            .bind(OffsetDateTime::now_utc())
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(SqlxStoreError::Sqlx)?;
-
-        if let Some((data,)) = record_value {
-            Ok(Some(
-                rmp_serde::from_slice(&data).map_err(SqlxStoreError::Decode)?,
-            ))
+        let started = self.slow_ops.start();
+        let load = |mut conn: PoolConnection<Postgres>| async move {
+            let res = self.load_with_conn(&mut conn, session_id).await;
+            (conn, res)
+        };
+        let res: Result<_, SqlxStoreError> = if self.load_retry {
+            self.with_failover_retry(load).await
         } else {
-            Ok(None)
-        }
+            self.with_conn(load).await
+        };
+        let res: session_store::Result<Option<Record>> = res.map_err(Into::into);
+        self.slow_ops
+            .finish(started, "load", res.as_ref().ok().and_then(Option::as_ref));
+        self.decode_failure_policy
+            .apply(self, session_id, res)
+            .await
     }
 
     async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
-        let query = format!(
-            r#"delete from "{schema_name}"."{table_name}" where id = $1"#,
-            schema_name = self.schema_name,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
-            .bind(session_id.to_string())
-            .execute(&self.pool)
+        let started = self.slow_ops.start();
+        let res = self
+            .with_failover_retry(|mut conn| async move {
+                let res = self.delete_with_conn(&mut conn, session_id).await;
+                (conn, res)
+            })
             .await
-            .map_err(SqlxStoreError::Sqlx)?;
+            .map_err(Into::into);
+        self.slow_ops.finish(started, "delete", None);
+        res
+    }
+}
 
-        Ok(())
+#[async_trait]
+impl TouchSessionStore for PostgresStore {
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        let res = self
+            .with_conn(|mut conn| async move {
+                let res = sqlx::query(&self.queries.touch)
+                    .bind(self.ttl_policy.expiry_date(session_id, expiry_date))
+                    .bind(session_id.to_string())
+                    .bind(OffsetDateTime::now_utc())
+                    .persistent(!self.pgbouncer_compat)
+                    .execute(&mut *conn)
+                    .await;
+                (conn, res.map_err(SqlxStoreError::Sqlx))
+            })
+            .await?;
+
+        Ok(res.rows_affected() > 0)
     }
 }
 
+#[async_trait]
+impl ExportSessions for PostgresStore {
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        let after = after.map(|id| id.to_string()).unwrap_or_default();
+        let rows: Vec<(Vec<u8>, OffsetDateTime)> = self
+            .with_conn(|mut conn| async move {
+                let res = sqlx::query_as(&self.queries.export)
+                    .bind(after)
+                    .bind(i64::from(limit))
+                    .persistent(!self.pgbouncer_compat)
+                    .fetch_all(&mut *conn)
+                    .await;
+                (conn, res.map_err(SqlxStoreError::Sqlx))
+            })
+            .await?;
+
+        rows.into_iter()
+            .map(|(data, expiry_date)| {
+                let mut record: Record = decode_record(&data)?;
+                record.expiry_date = expiry_date;
+                Ok(record)
+            })
+            .collect()
+    }
+}
+
+/// Queries rendered once per schema and table name rather than on every
+/// operation.
+#[derive(Clone, Debug)]
+struct Queries {
+    create_schema: String,
+    create_table: String,
+    create_data_table: Option<String>,
+    insert: String,
+    upsert: String,
+    load: String,
+    delete: String,
+    delete_expired: String,
+    delete_expired_batch: String,
+    touch: String,
+    export: String,
+    purge_deleted: Option<String>,
+}
+
+impl Queries {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        schema_name: &str,
+        table_name: &str,
+        columns: &ColumnNames,
+        fixed_columns: &[(String, String)],
+        soft_delete_column: Option<&str>,
+        activity_columns: Option<(&str, &str)>,
+        audit_columns: Option<(&str, &str)>,
+        data_table: Option<&str>,
+    ) -> Self {
+        let ColumnNames {
+            id,
+            data,
+            expiry_date,
+        } = columns;
+        let upsert_live = soft_delete_column
+            .map(|deleted_at| {
+                format!(r#" where "{schema_name}"."{table_name}"."{deleted_at}" is null"#)
+            })
+            .unwrap_or_default();
+        let (soft_delete_definition, live) = match soft_delete_column {
+            Some(deleted_at) => (
+                format!(r#", "{deleted_at}" timestamptz"#),
+                format!(r#" and "{deleted_at}" is null"#),
+            ),
+            None => Default::default(),
+        };
+        // Audit columns are maintained like activity columns, except that
+        // loads leave them alone.
+        let (activity_definitions, seen_now) = match activity_columns.or(audit_columns) {
+            Some((created_at, last_seen)) => (
+                format!(
+                    r#", "{created_at}" timestamptz not null default now(), "{last_seen}" timestamptz not null default now()"#
+                ),
+                format!(r#", "{last_seen}" = now()"#),
+            ),
+            None => Default::default(),
+        };
+        let fixed_definitions: String = fixed_columns
+            .iter()
+            .map(|(name, _)| format!(r#", "{name}" text not null"#))
+            .collect();
+        let fixed_names: String = fixed_columns
+            .iter()
+            .map(|(name, _)| format!(r#", "{name}""#))
+            .collect();
+        let fixed_values: String = (0..fixed_columns.len())
+            .map(|i| format!(", ${}", i + 4))
+            .collect();
+
+        let queries = Self {
+            create_schema: format!(r#"create schema if not exists "{schema_name}""#),
+            create_table: format!(
+                r#"
+                create table if not exists "{schema_name}"."{table_name}"
+                (
+                    "{id}" text primary key not null,
+                    "{data}" bytea not null,
+                    "{expiry_date}" timestamptz not null{fixed_definitions}{soft_delete_definition}{activity_definitions}
+                )
+                "#
+            ),
+            create_data_table: None,
+            insert: format!(
+                r#"
+                insert into "{schema_name}"."{table_name}"
+                  ("{id}", "{data}", "{expiry_date}"{fixed_names})
+                values ($1, $2, $3{fixed_values})
+                on conflict ("{id}") do nothing
+                returning "{id}"
+                "#
+            ),
+            // `xmax` is zero only for rows inserted rather than updated.
+            upsert: format!(
+                r#"
+                insert into "{schema_name}"."{table_name}"
+                  ("{id}", "{data}", "{expiry_date}"{fixed_names})
+                values ($1, $2, $3{fixed_values})
+                on conflict ("{id}") do update
+                set
+                  "{data}" = excluded."{data}",
+                  "{expiry_date}" = excluded."{expiry_date}"{seen_now}{upsert_live}
+                returning (xmax = 0)
+                "#
+            ),
+            load: match activity_columns {
+                Some((_, last_seen)) => format!(
+                    r#"
+                    update "{schema_name}"."{table_name}" set "{last_seen}" = now()
+                    where "{id}" = $1 and "{expiry_date}" > $2{live}
+                    returning "{data}", "{expiry_date}"
+                    "#
+                ),
+                None => format!(
+                    r#"
+                    select "{data}", "{expiry_date}" from "{schema_name}"."{table_name}"
+                    where "{id}" = $1 and "{expiry_date}" > $2{live}
+                    "#
+                ),
+            },
+            delete: match soft_delete_column {
+                Some(deleted_at) => format!(
+                    r#"
+                    update "{schema_name}"."{table_name}" set "{deleted_at}" = $1
+                    where "{id}" = $2{live}
+                    "#
+                ),
+                None => format!(r#"delete from "{schema_name}"."{table_name}" where "{id}" = $1"#),
+            },
+            delete_expired: format!(
+                r#"
+                delete from "{schema_name}"."{table_name}"
+                where "{expiry_date}" < (now() at time zone 'utc'){live}
+                "#
+            ),
+            delete_expired_batch: format!(
+                r#"
+                delete from "{schema_name}"."{table_name}"
+                where "{id}" in (
+                  select "{id}" from "{schema_name}"."{table_name}"
+                  where "{expiry_date}" < (now() at time zone 'utc'){live}
+                  limit $1
+                  for update skip locked
+                )
+                "#
+            ),
+            touch: format!(
+                r#"
+                update "{schema_name}"."{table_name}" set "{expiry_date}" = $1{seen_now}
+                where "{id}" = $2 and "{expiry_date}" > $3{live}
+                "#
+            ),
+            export: format!(
+                r#"
+                select "{data}", "{expiry_date}" from "{schema_name}"."{table_name}"
+                where "{id}" > $1{live}
+                order by "{id}"
+                limit $2
+                "#
+            ),
+            purge_deleted: soft_delete_column.map(|deleted_at| {
+                format!(r#"delete from "{schema_name}"."{table_name}" where "{deleted_at}" < $1"#)
+            }),
+        };
+        let Some(data_table) = data_table else {
+            return queries;
+        };
+
+        // Session data lives in the data table, deleted along with its session
+        // through the foreign key, so only the queries reading or writing data
+        // differ.
+        Self {
+            create_table: format!(
+                r#"
+                create table if not exists "{schema_name}"."{table_name}"
+                (
+                    "{id}" text primary key not null,
+                    "{expiry_date}" timestamptz not null{fixed_definitions}{soft_delete_definition}{activity_definitions}
+                )
+                "#
+            ),
+            create_data_table: Some(format!(
+                r#"
+                create table if not exists "{schema_name}"."{data_table}"
+                (
+                    "{id}" text primary key not null
+                      references "{schema_name}"."{table_name}" ("{id}") on delete cascade,
+                    "{data}" bytea not null
+                )
+                "#
+            )),
+            insert: format!(
+                r#"
+                with session as (
+                  insert into "{schema_name}"."{table_name}"
+                    ("{id}", "{expiry_date}"{fixed_names})
+                  values ($1, $3{fixed_values})
+                  on conflict ("{id}") do nothing
+                  returning "{id}"
+                )
+                insert into "{schema_name}"."{data_table}" ("{id}", "{data}")
+                select "{id}", $2::bytea from session
+                returning "{id}"
+                "#
+            ),
+            // Unchanged data is left alone rather than rewritten.
+            upsert: format!(
+                r#"
+                with session as (
+                  insert into "{schema_name}"."{table_name}"
+                    ("{id}", "{expiry_date}"{fixed_names})
+                  values ($1, $3{fixed_values})
+                  on conflict ("{id}") do update
+                  set "{expiry_date}" = excluded."{expiry_date}"{seen_now}{upsert_live}
+                  returning "{id}", (xmax = 0) as inserted
+                ), session_data as (
+                  insert into "{schema_name}"."{data_table}" ("{id}", "{data}")
+                  select "{id}", $2::bytea from session
+                  on conflict ("{id}") do update
+                  set "{data}" = excluded."{data}"
+                  where "{schema_name}"."{data_table}"."{data}" is distinct from excluded."{data}"
+                )
+                select inserted from session
+                "#
+            ),
+            load: match activity_columns {
+                Some((_, last_seen)) => format!(
+                    r#"
+                    update "{schema_name}"."{table_name}" s set "{last_seen}" = now()
+                    from "{schema_name}"."{data_table}" d
+                    where d."{id}" = s."{id}" and s."{id}" = $1 and s."{expiry_date}" > $2{live}
+                    returning d."{data}", s."{expiry_date}"
+                    "#
+                ),
+                None => format!(
+                    r#"
+                    select d."{data}", s."{expiry_date}"
+                    from "{schema_name}"."{table_name}" s
+                    join "{schema_name}"."{data_table}" d on d."{id}" = s."{id}"
+                    where s."{id}" = $1 and s."{expiry_date}" > $2{live}
+                    "#
+                ),
+            },
+            export: format!(
+                r#"
+                select d."{data}", s."{expiry_date}"
+                from "{schema_name}"."{table_name}" s
+                join "{schema_name}"."{data_table}" d on d."{id}" = s."{id}"
+                where s."{id}" > $1{live}
+                order by s."{id}"
+                limit $2
+                "#
+            ),
+            ..queries
+        }
+    }
+}
+
+const ACTIVITY_WITH_AUDIT: &str =
+    "Activity columns can't be combined with audit columns, as both record when sessions were \
+     created.";
+
+fn invalid_column_name(column_name: &str) -> String {
+    format!(
+        "Invalid column name '{}'. Column names must start with a letter or underscore (including \
+         letters with diacritical marks and non-Latin letters). Subsequent characters can be \
+         letters, underscores, digits (0-9), or dollar signs ($).",
+        column_name
+    )
+}
+
 /// A valid PostreSQL identifier must start with a letter or underscore
 /// (including letters with diacritical marks and non-Latin letters). Subsequent
 /// characters in an identifier or key word can be letters, underscores, digits
 /// (0-9), or dollar signs ($). See https://www.postgresql.org/docs/current/sql-syntax-lexical.html#SQL-SYNTAX-IDENTIFIERS for details.
-fn is_valid_identifier(name: &str) -> bool {
+pub(crate) fn is_valid_identifier(name: &str) -> bool {
     !name.is_empty()
         && name
             .chars()
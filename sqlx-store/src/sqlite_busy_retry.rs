@@ -0,0 +1,84 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use tower_sessions_core::session_store;
+use tower_sessions_ext::{debug, is_transient};
+
+/// The delay before the first retry, which doubles with each attempt up to
+/// [`MAX_BACKOFF`].
+const INITIAL_BACKOFF: Duration = Duration::from_millis(5);
+
+const MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Bounds on retrying writes that fail because the database is locked; see
+/// [`SqliteStore::with_busy_retry`](crate::SqliteStore::with_busy_retry).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BusyRetry {
+    max_attempts: u32,
+    max_duration: Duration,
+}
+
+impl Default for BusyRetry {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(1))
+    }
+}
+
+impl BusyRetry {
+    pub(crate) fn new(max_attempts: u32, max_duration: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            max_duration,
+        }
+    }
+
+    pub(crate) fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub(crate) fn max_duration(&self) -> Duration {
+        self.max_duration
+    }
+
+    /// Run the write, retrying it with exponential backoff while it fails
+    /// with a transient error, such as `SQLITE_BUSY`, until the attempts or
+    /// the time allowed run out.
+    pub(crate) async fn run<T, F, Fut>(&self, mut write: F) -> session_store::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = session_store::Result<T>>,
+    {
+        let deadline = Instant::now() + self.max_duration;
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempts = 1;
+        loop {
+            match write().await {
+                Err(err)
+                    if is_transient(&err)
+                        && attempts < self.max_attempts
+                        && Instant::now() + backoff < deadline =>
+                {
+                    debug!(attempts, error = %err, "retrying locked SQLite write");
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempts += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Sleep on the current Tokio runtime, or, with the `runtime-async-std`
+/// feature and outside of Tokio, on async-std, as SQLx itself does.
+async fn sleep(duration: Duration) {
+    #[cfg(feature = "runtime-async-std")]
+    if tokio::runtime::Handle::try_current().is_err() {
+        async_std::task::sleep(duration).await;
+        return;
+    }
+
+    tokio::time::sleep(duration).await;
+}
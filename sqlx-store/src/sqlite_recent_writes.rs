@@ -0,0 +1,59 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tower_sessions_core::session::Id;
+
+/// The number of tracked sessions above which those written outside the
+/// window are pruned.
+const MIN_PRUNE_LEN: usize = 1_024;
+
+#[derive(Debug)]
+struct Written {
+    at: HashMap<Id, Instant>,
+    prune_len: usize,
+}
+
+/// Sessions recently written through a store, whose loads are pinned to the
+/// writer pool.
+#[derive(Clone, Debug)]
+pub(crate) struct RecentWrites {
+    window: Duration,
+    written: Arc<Mutex<Written>>,
+}
+
+impl RecentWrites {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            written: Arc::new(Mutex::new(Written {
+                at: HashMap::new(),
+                prune_len: MIN_PRUNE_LEN,
+            })),
+        }
+    }
+
+    pub(crate) fn record(&self, session_id: Id) {
+        let now = Instant::now();
+        let mut written = self.written.lock().unwrap_or_else(|err| err.into_inner());
+        if written.at.len() >= written.prune_len {
+            written
+                .at
+                .retain(|_, at| now.duration_since(*at) < self.window);
+            // Only prune again once the map has doubled, so that a burst of
+            // writes within the window doesn't prune on every write.
+            written.prune_len = (written.at.len() * 2).max(MIN_PRUNE_LEN);
+        }
+        written.at.insert(session_id, now);
+    }
+
+    pub(crate) fn contains(&self, session_id: &Id) -> bool {
+        let written = self.written.lock().unwrap_or_else(|err| err.into_inner());
+        written
+            .at
+            .get(session_id)
+            .is_some_and(|at| at.elapsed() < self.window)
+    }
+}
@@ -1,19 +1,86 @@
+use std::{path::Path, str::FromStr, time::Duration};
+
 use async_trait::async_trait;
-use sqlx::{sqlite::SqlitePool, SqliteConnection};
+use serde::Deserialize;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
+    SqliteConnection,
+};
 use time::OffsetDateTime;
 use tower_sessions_core::{
     session::{Id, Record},
     session_store::{self, ExpiredDeletion},
     SessionStore,
 };
+use tower_sessions_ext::{
+    is_transient, DecodeFailurePolicy, ExportSessions, IdCollisionPolicy, SlowOpTracer,
+    StoreOptions, StoreOptionsConfig, TouchSessionStore, TtlPolicy,
+};
+
+use crate::{
+    decode_record, encode_record,
+    sqlite_busy_retry::BusyRetry,
+    sqlite_recent_writes::RecentWrites,
+    sqlite_write_queue::{QueuedWrite, Write, WriteQueue, Written},
+    ColumnNames, PoolStatus, SqlxStoreError,
+};
 
-use crate::SqlxStoreError;
+/// The settings of a [`SqliteStore`], as read from a configuration file.
+///
+/// The options shared by every store, such as the `namespace` naming the
+/// session table, are written next to these settings. Settings that are left
+/// out keep the store's defaults.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_sqlx_store::SqliteStoreConfig;
+///
+/// let config: SqliteStoreConfig =
+///     serde_json::from_str(r#"{ "namespace": "sessions", "write_queue_batch_size": 64 }"#)
+///         .unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct SqliteStoreConfig {
+    /// The options shared by every store.
+    #[serde(flatten)]
+    pub options: StoreOptionsConfig,
+
+    /// Passed to [`SqliteStore::with_soft_delete`].
+    pub soft_delete_column: Option<String>,
+
+    /// Passed to [`SqliteStore::with_write_queue`].
+    pub write_queue_batch_size: Option<usize>,
+
+    /// The attempts passed to [`SqliteStore::with_busy_retry`], keeping the
+    /// default time allowed if `busy_retry_max_ms` is left out.
+    pub busy_retry_attempts: Option<u32>,
+
+    /// The time allowed, in milliseconds, passed to
+    /// [`SqliteStore::with_busy_retry`], keeping the default attempts if
+    /// `busy_retry_attempts` is left out.
+    pub busy_retry_max_ms: Option<u64>,
+}
 
 /// A SQLite session store.
 #[derive(Clone, Debug)]
 pub struct SqliteStore {
     pool: SqlitePool,
+    read_pool: Option<SqlitePool>,
+    recent_writes: Option<RecentWrites>,
     table_name: String,
+    columns: ColumnNames,
+    fixed_columns: Vec<(String, String)>,
+    soft_delete_column: Option<String>,
+    audit_columns: Option<(String, String)>,
+    ttl_policy: TtlPolicy,
+    id_collision_policy: IdCollisionPolicy,
+    decode_failure_policy: DecodeFailurePolicy,
+    slow_ops: SlowOpTracer,
+    write_queue: Option<WriteQueue>,
+    busy_retry: BusyRetry,
+    queries: Queries,
 }
 
 impl SqliteStore {
@@ -30,12 +97,91 @@ impl SqliteStore {
     /// # })
     /// ```
     pub fn new(pool: SqlitePool) -> Self {
+        let table_name = "tower_sessions".to_string();
+        let columns = ColumnNames::default();
         Self {
             pool,
-            table_name: "tower_sessions".into(),
+            read_pool: None,
+            recent_writes: None,
+            queries: Queries::new(&table_name, &columns, &[], None, None),
+            table_name,
+            columns,
+            fixed_columns: Vec::new(),
+            soft_delete_column: None,
+            audit_columns: None,
+            ttl_policy: TtlPolicy::default(),
+            id_collision_policy: IdCollisionPolicy::default(),
+            decode_failure_policy: DecodeFailurePolicy::default(),
+            slow_ops: SlowOpTracer::new("sqlite"),
+            write_queue: None,
+            busy_retry: BusyRetry::default(),
         }
     }
 
+    /// Create a new SQLite store with the provided connection pool,
+    /// configured with the provided settings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, SqliteStore, SqliteStoreConfig};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+    /// let config: SqliteStoreConfig =
+    ///     serde_json::from_str(r#"{ "namespace": "sessions" }"#).unwrap();
+    /// let session_store = SqliteStore::try_from_config(pool, &config).unwrap();
+    /// # })
+    /// ```
+    pub fn try_from_config(pool: SqlitePool, config: &SqliteStoreConfig) -> Result<Self, String> {
+        let mut store = config.options.apply(Self::new(pool))?;
+        if let Some(column_name) = &config.soft_delete_column {
+            store = store.with_soft_delete(column_name)?;
+        }
+        if let Some(max_batch_size) = config.write_queue_batch_size {
+            store = store.with_write_queue(max_batch_size);
+        }
+        if config.busy_retry_attempts.is_some() || config.busy_retry_max_ms.is_some() {
+            let max_attempts = config
+                .busy_retry_attempts
+                .unwrap_or(store.busy_retry.max_attempts());
+            let max_duration = config
+                .busy_retry_max_ms
+                .map_or(store.busy_retry.max_duration(), Duration::from_millis);
+            store = store.with_busy_retry(max_attempts, max_duration);
+        }
+        Ok(store)
+    }
+
+    /// Create a new SQLite store backed by a new in-memory database, e.g. for
+    /// tests.
+    ///
+    /// The database is shared by all connections of the store's pool, which
+    /// keeps a connection open for as long as it lives: SQLite drops an
+    /// in-memory database as soon as its last connection closes, which pools
+    /// otherwise do once connections are idle. Each call creates a separate
+    /// database.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_sqlx_store::SqliteStore;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session_store = SqliteStore::in_memory().await.unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn in_memory() -> sqlx::Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:")?)
+            .await?;
+        Ok(Self::new(pool))
+    }
+
     /// Set the session table name with the provided name.
     pub fn with_table_name(mut self, table_name: impl AsRef<str>) -> Result<Self, String> {
         let table_name = table_name.as_ref();
@@ -48,44 +194,550 @@ impl SqliteStore {
         }
 
         table_name.clone_into(&mut self.table_name);
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Set the session table column names with the provided names.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, ColumnNames, SqliteStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+    /// let session_store = SqliteStore::new(pool)
+    ///     .with_table_name("django_session")
+    ///     .unwrap()
+    ///     .with_column_names(ColumnNames {
+    ///         id: "session_key".to_string(),
+    ///         data: "session_data".to_string(),
+    ///         expiry_date: "expire_date".to_string(),
+    ///     })
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub fn with_column_names(mut self, columns: ColumnNames) -> Result<Self, String> {
+        if let Some(column_name) = columns.find_invalid(is_valid_table_name) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.columns = columns;
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Add a column that is populated with the provided value whenever a
+    /// session is inserted.
+    ///
+    /// This is useful for pre-existing session tables with additional
+    /// non-null columns the store does not otherwise know about.
+    pub fn with_fixed_column(
+        mut self,
+        column_name: impl AsRef<str>,
+        value: impl Into<String>,
+    ) -> Result<Self, String> {
+        let column_name = column_name.as_ref();
+        if !is_valid_table_name(column_name) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.fixed_columns
+            .push((column_name.to_string(), value.into()));
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Mark deleted sessions with a deletion timestamp in the provided column
+    /// instead of removing their rows.
+    ///
+    /// Soft-deleted sessions can no longer be loaded, saved, or touched, and
+    /// are kept by [`ExpiredDeletion::delete_expired`] so that they remain
+    /// available for later investigation. Use [`SqliteStore::purge_deleted`]
+    /// to remove them for good.
+    ///
+    /// The column is created by [`SqliteStore::migrate`]; existing tables must
+    /// be altered to add a nullable column with this name.
+    pub fn with_soft_delete(mut self, column_name: impl AsRef<str>) -> Result<Self, String> {
+        let column_name = column_name.as_ref();
+        if !is_valid_table_name(column_name) {
+            return Err(invalid_column_name(column_name));
+        }
+
+        self.soft_delete_column = Some(column_name.to_string());
+        self.render_queries();
+        Ok(self)
+    }
+
+    /// Record when each session was created and last written in the provided
+    /// columns.
+    ///
+    /// The creation column is set when a session is first saved, and the
+    /// update column whenever the session is saved or touched, both as UTC
+    /// `current_timestamp` text. This suits session analytics in SQL, e.g.
+    /// counting sessions created per day, without decoding session data.
+    ///
+    /// The columns are created by [`SqliteStore::migrate`]; existing tables
+    /// must be altered to add them, e.g. as `text not null default
+    /// current_timestamp`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, SqliteStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+    /// let session_store = SqliteStore::new(pool)
+    ///     .with_audit_columns("created_at", "updated_at")
+    ///     .unwrap();
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_audit_columns(
+        mut self,
+        created_at_column: impl AsRef<str>,
+        updated_at_column: impl AsRef<str>,
+    ) -> Result<Self, String> {
+        let created_at_column = created_at_column.as_ref();
+        let updated_at_column = updated_at_column.as_ref();
+        for column_name in [created_at_column, updated_at_column] {
+            if !is_valid_table_name(column_name) {
+                return Err(invalid_column_name(column_name));
+            }
+        }
+
+        self.audit_columns = Some((created_at_column.to_string(), updated_at_column.to_string()));
+        self.render_queries();
         Ok(self)
     }
 
+    /// Set the policy used to replace session IDs that collide with existing
+    /// sessions in `create`.
+    pub fn with_id_collision_policy(mut self, id_collision_policy: IdCollisionPolicy) -> Self {
+        self.id_collision_policy = id_collision_policy;
+        self
+    }
+
+    /// Set how `load` responds to a session whose stored data can't be
+    /// decoded, e.g. after the serialization format has changed.
+    pub fn with_decode_failure_policy(
+        mut self,
+        decode_failure_policy: DecodeFailurePolicy,
+    ) -> Self {
+        self.decode_failure_policy = decode_failure_policy;
+        self
+    }
+
+    /// Log operations taking longer than the provided threshold with
+    /// `tracing::warn!`.
+    pub fn with_slow_op_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_ops = self.slow_ops.with_threshold(threshold);
+        self
+    }
+
+    /// Set the policy adjusting the expiry dates written to the expiry
+    /// column.
+    pub fn with_ttl_policy(mut self, ttl_policy: TtlPolicy) -> Self {
+        self.ttl_policy = ttl_policy;
+        self
+    }
+
+    /// Apply writes through a single background task instead of from each
+    /// request, committing up to `max_batch_size` queued writes per
+    /// transaction.
+    ///
+    /// SQLite allows only one writer at a time, so under write-heavy session
+    /// churn concurrent writers mostly wait on the database lock or fail with
+    /// `SQLITE_BUSY`. Queueing writes removes that contention, and batching
+    /// them amortizes the cost of each commit. Creates, saves, deletes, and
+    /// touches are queued; loads still use the pool directly.
+    ///
+    /// The writer task is spawned on the runtime of the first write, Tokio or,
+    /// with the `runtime-async-std` feature, async-std, and exits once every
+    /// clone of the store has been dropped.
+    pub fn with_write_queue(mut self, max_batch_size: usize) -> Self {
+        self.write_queue = Some(WriteQueue::new(max_batch_size));
+        self
+    }
+
+    /// Retry creates, saves, deletes, and touches that fail because the
+    /// database is locked, up to `max_attempts` times in all and within
+    /// `max_duration`, waiting a little longer before each retry.
+    ///
+    /// SQLite allows only one writer at a time. A connection waits up to its
+    /// busy timeout for the lock, but fails with `SQLITE_BUSY`, reported as
+    /// "database is locked", right away when waiting could deadlock, e.g.
+    /// when a transaction that has read from the database needs to write, or
+    /// once the busy timeout runs out. Retrying the whole write then usually
+    /// succeeds, rather than failing the request.
+    ///
+    /// Writes failing with other transient errors, such as pool timeouts,
+    /// are retried as well. Defaults to 5 attempts within one second; pass 1
+    /// to turn retries off. With [`SqliteStore::with_write_queue`], each
+    /// batch is retried as a whole.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, SqliteStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite://sessions.db").await.unwrap();
+    /// let session_store = SqliteStore::new(pool).with_busy_retry(10, Duration::from_secs(2));
+    /// # })
+    /// ```
+    pub fn with_busy_retry(mut self, max_attempts: u32, max_duration: Duration) -> Self {
+        self.busy_retry = BusyRetry::new(max_attempts, max_duration);
+        self
+    }
+
+    /// Load and export sessions through a separate pool of reader
+    /// connections.
+    ///
+    /// In WAL mode, SQLite lets readers run alongside the single writer, so a
+    /// pool of read-only connections, e.g. opened with
+    /// [`SqliteConnectOptions::read_only`](sqlx::sqlite::SqliteConnectOptions::read_only),
+    /// keeps loads from waiting on the connections busy writing. Creates,
+    /// saves, deletes, and touches still use the store's pool.
+    ///
+    /// A reader connection may still be reading from a snapshot taken before
+    /// a write committed through another connection, so a load right after a
+    /// save can return the previous session. Use
+    /// [`SqliteStore::with_read_your_writes`] to rule that out.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions_sqlx_store::{
+    ///     sqlx::{
+    ///         sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    ///         SqlitePool,
+    ///     },
+    ///     SqliteStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let options = SqliteConnectOptions::new()
+    ///     .filename("sessions.db")
+    ///     .create_if_missing(true)
+    ///     .journal_mode(SqliteJournalMode::Wal);
+    /// let pool = SqlitePool::connect_with(options.clone()).await.unwrap();
+    /// let read_pool = SqlitePool::connect_with(options.read_only(true))
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// let session_store = SqliteStore::new(pool)
+    ///     .with_read_pool(read_pool)
+    ///     .with_read_your_writes(Duration::from_secs(5));
+    /// # })
+    /// ```
+    pub fn with_read_pool(mut self, read_pool: SqlitePool) -> Self {
+        self.read_pool = Some(read_pool);
+        self
+    }
+
+    /// Load sessions written through this store within the provided window
+    /// from the store's pool rather than the read pool, so that a load
+    /// following a create, save, delete, or touch always sees the write.
+    ///
+    /// Only writes made through this store and its clones are tracked. This
+    /// has no effect without [`SqliteStore::with_read_pool`].
+    pub fn with_read_your_writes(mut self, window: Duration) -> Self {
+        self.recent_writes = Some(RecentWrites::new(window));
+        self
+    }
+
+    /// The pool to load the session from, which is the store's pool if the
+    /// session was written too recently to be read from the read pool.
+    fn load_pool(&self, session_id: &Id) -> &SqlitePool {
+        match (&self.read_pool, &self.recent_writes) {
+            (Some(_), Some(recent_writes)) if recent_writes.contains(session_id) => &self.pool,
+            (Some(read_pool), _) => read_pool,
+            (None, _) => &self.pool,
+        }
+    }
+
+    fn record_write(&self, session_id: Id) {
+        if let Some(recent_writes) = &self.recent_writes {
+            recent_writes.record(session_id);
+        }
+    }
+
+    /// A copy of the store for the writer task, which applies writes
+    /// directly.
+    pub(crate) fn writer(&self) -> Self {
+        Self {
+            write_queue: None,
+            ..self.clone()
+        }
+    }
+
+    /// Apply the batch of writes in a single transaction and reply to each,
+    /// retrying the transaction while the database is locked.
+    pub(crate) async fn write_batch(&self, batch: impl Iterator<Item = QueuedWrite>) {
+        let (writes, replies): (Vec<_>, Vec<_>) = batch
+            .map(|QueuedWrite { write, reply }| (write, reply))
+            .unzip();
+
+        match self.busy_retry.run(|| self.try_write_batch(&writes)).await {
+            Ok(results) => {
+                for (reply, res) in replies.into_iter().zip(results) {
+                    let _ = reply.send(res);
+                }
+            }
+            Err(err) => {
+                for reply in replies {
+                    let _ = reply.send(Err(copy_error(&err)));
+                }
+            }
+        }
+    }
+
+    /// Apply the batch of writes in a single transaction, failing as a whole
+    /// if the transaction can't be committed or a write fails transiently,
+    /// in which case the transaction is rolled back.
+    async fn try_write_batch(
+        &self,
+        writes: &[Write],
+    ) -> session_store::Result<Vec<session_store::Result<Written>>> {
+        let mut tx = self.pool.begin().await.map_err(SqlxStoreError::Sqlx)?;
+
+        let mut results = Vec::with_capacity(writes.len());
+        for write in writes {
+            match self.write_with_conn(&mut tx, write.clone()).await {
+                Err(err) if is_transient(&err) => return Err(err),
+                res => results.push(res),
+            }
+        }
+
+        tx.commit().await.map_err(SqlxStoreError::Sqlx)?;
+        Ok(results)
+    }
+
+    async fn write_with_conn(
+        &self,
+        conn: &mut SqliteConnection,
+        write: Write,
+    ) -> session_store::Result<Written> {
+        match write {
+            Write::Create(mut record) => {
+                let mut collisions = 0;
+                while !self.try_create_with_conn(conn, &record).await? {
+                    collisions += 1;
+                    record.id = self.id_collision_policy.next_id(collisions)?;
+                }
+                Ok(Written::Created(record.id))
+            }
+            Write::Save(record) => {
+                self.save_with_conn(conn, &record).await?;
+                Ok(Written::Saved)
+            }
+            Write::Delete(session_id) => {
+                self.delete_with_conn(conn, &session_id).await?;
+                Ok(Written::Deleted)
+            }
+            Write::Touch(session_id, expiry_date) => Ok(Written::Touched(
+                self.touch_with_conn(conn, &session_id, expiry_date).await?,
+            )),
+        }
+    }
+
+    fn render_queries(&mut self) {
+        self.queries = Queries::new(
+            &self.table_name,
+            &self.columns,
+            &self.fixed_columns,
+            self.soft_delete_column.as_deref(),
+            self.audit_columns
+                .as_ref()
+                .map(|(created_at, updated_at)| (created_at.as_str(), updated_at.as_str())),
+        );
+    }
+
+    /// Open a pooled connection ahead of the first operation.
+    ///
+    /// A store created with a lazily connecting pool, e.g. via
+    /// [`SqlitePool::connect_lazy`](sqlx::SqlitePool::connect_lazy), lets an
+    /// application start while the database is unavailable, with each session
+    /// operation connecting as needed. Call this at startup to connect early
+    /// and surface connection problems.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, SqliteStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect_lazy("sqlite://sessions.db").unwrap();
+    /// let session_store = SqliteStore::new(pool);
+    /// session_store.warm_up().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn warm_up(&self) -> sqlx::Result<()> {
+        self.pool.acquire().await?;
+        Ok(())
+    }
+
+    /// Take a snapshot of the connection pool's health, including how long
+    /// it currently takes to acquire a connection.
+    ///
+    /// Measuring the wait acquires a connection, opening one if none are idle
+    /// and the pool isn't full.
+    pub async fn pool_status(&self) -> sqlx::Result<PoolStatus> {
+        PoolStatus::probe(&self.pool).await
+    }
+
+    /// Log a snapshot of the connection pool's health with `tracing::info!`,
+    /// or with `tracing::warn!` if the pool is saturated, e.g. periodically.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub async fn log_pool_status(&self) {
+        PoolStatus::log(self.pool_status().await, "sqlite");
+    }
+
+    /// The statements [`SqliteStore::migrate`] runs to create the session
+    /// schema, e.g. for reviewing them or applying them with other migration
+    /// tooling.
+    pub fn migration_sql(&self) -> Vec<String> {
+        vec![self.queries.create_table.clone()]
+    }
+
     /// Migrate the session schema.
+    ///
+    /// This fails if the store's pool opens in-memory databases that aren't
+    /// shared between its connections, e.g. with a private cache, as each
+    /// connection would then see its own, empty database; see
+    /// [`SqliteStore::in_memory`].
     pub async fn migrate(&self) -> sqlx::Result<()> {
-        let query = format!(
-            r#"
-            create table if not exists {}
-            (
-                id text primary key not null,
-                data blob not null,
-                expiry_date integer not null
-            )
-            "#,
-            self.table_name
-        );
-        sqlx::query(&query).execute(&self.pool).await?;
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query(&self.queries.create_table)
+            .execute(&mut *conn)
+            .await?;
+        check_shared_in_memory(&self.pool, &mut conn, &self.table_name).await
+    }
+
+    /// Permanently remove sessions soft-deleted before the provided date,
+    /// returning the number of sessions removed.
+    ///
+    /// This does nothing unless soft deletion is enabled via
+    /// [`SqliteStore::with_soft_delete`].
+    pub async fn purge_deleted(
+        &self,
+        deleted_before: OffsetDateTime,
+    ) -> session_store::Result<u64> {
+        let Some(purge_deleted) = &self.queries.purge_deleted else {
+            return Ok(0);
+        };
+
+        let res = sqlx::query(purge_deleted)
+            .bind(deleted_before)
+            .execute(&self.pool)
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Write a consistent snapshot of the sessions database to the provided
+    /// path while the store remains in use.
+    ///
+    /// This uses SQLite's `VACUUM INTO`, so the snapshot includes every table
+    /// in the database, not only the sessions table, and the operation fails if
+    /// a file already exists at `path`. The database must be file-backed, as
+    /// snapshots of in-memory databases are kept in memory as well. Writes
+    /// still pending in the queue enabled by [`SqliteStore::with_write_queue`]
+    /// are not included.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, SqliteStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite://sessions.db").await.unwrap();
+    /// let session_store = SqliteStore::new(pool);
+    /// session_store
+    ///     .backup_to("sessions-backup.db")
+    ///     .await
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub async fn backup_to(&self, path: impl AsRef<Path>) -> sqlx::Result<()> {
+        let path = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| sqlx::Error::Configuration("Backup path must be valid UTF-8".into()))?;
+
+        sqlx::query("vacuum into ?")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
+    /// Build a custom query against the session table, e.g. for analytics or
+    /// maintenance, with the table's quoted identifier.
+    ///
+    /// The closure is given the table name quoted for use in SQL, so that
+    /// queries keep working with a name set via
+    /// [`SqliteStore::with_table_name`] without formatting it by hand.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{
+    ///     sqlx::{self, SqlitePool},
+    ///     SqliteStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+    /// let session_store = SqliteStore::new(pool.clone());
+    /// let sql = session_store.with_session_table(|table| format!("select count(*) from {table}"));
+    /// let (count,): (i64,) = sqlx::query_as(&sql).fetch_one(&pool).await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_session_table<T>(&self, f: impl FnOnce(&str) -> T) -> T {
+        f(&format!(r#""{}""#, self.table_name))
+    }
+
+    /// Insert the record in a transaction of its own, replacing its ID
+    /// while it collides with an existing session, and return the ID it was
+    /// stored under.
+    async fn create_in_tx(&self, record: &Record) -> session_store::Result<Id> {
+        let mut tx = self.pool.begin().await.map_err(SqlxStoreError::Sqlx)?;
+
+        let mut record = record.clone();
+        let mut collisions = 0;
+        while !self.try_create_with_conn(&mut tx, &record).await? {
+            collisions += 1;
+            record.id = self.id_collision_policy.next_id(collisions)?;
+        }
+
+        tx.commit().await.map_err(SqlxStoreError::Sqlx)?;
+        Ok(record.id)
+    }
+
     async fn try_create_with_conn(
         &self,
         conn: &mut SqliteConnection,
         record: &Record,
     ) -> session_store::Result<bool> {
-        let query = format!(
-            r#"
-            insert or abort into {table_name}
-              (id, data, expiry_date) values (?, ?, ?)
-            "#,
-            table_name = self.table_name
-        );
-        let res = sqlx::query(&query)
+        let record = &*self.ttl_policy.apply(record);
+        let mut query = sqlx::query(&self.queries.insert)
             .bind(record.id.to_string())
-            .bind(rmp_serde::to_vec(record).map_err(SqlxStoreError::Encode)?)
-            .bind(record.expiry_date)
-            .execute(conn)
-            .await;
+            .bind(encode_record(record)?)
+            .bind(record.expiry_date);
+        for (_, value) in &self.fixed_columns {
+            query = query.bind(value.as_str());
+        }
+        let res = query.execute(conn).await;
 
         match res {
             Ok(_) => Ok(true),
@@ -99,39 +751,81 @@ impl SqliteStore {
         conn: &mut SqliteConnection,
         record: &Record,
     ) -> session_store::Result<()> {
-        let query = format!(
-            r#"
-            insert into {table_name}
-              (id, data, expiry_date) values (?, ?, ?)
-            on conflict(id) do update set
-              data = excluded.data,
-              expiry_date = excluded.expiry_date
-            "#,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
+        let record = &*self.ttl_policy.apply(record);
+        let mut query = sqlx::query(&self.queries.upsert)
             .bind(record.id.to_string())
-            .bind(rmp_serde::to_vec(record).map_err(SqlxStoreError::Encode)?)
-            .bind(record.expiry_date)
+            .bind(encode_record(record)?)
+            .bind(record.expiry_date);
+        for (_, value) in &self.fixed_columns {
+            query = query.bind(value.as_str());
+        }
+        query.execute(conn).await.map_err(SqlxStoreError::Sqlx)?;
+
+        Ok(())
+    }
+
+    async fn delete_with_conn(
+        &self,
+        conn: &mut SqliteConnection,
+        session_id: &Id,
+    ) -> session_store::Result<()> {
+        let mut query = sqlx::query(&self.queries.delete);
+        if self.soft_delete_column.is_some() {
+            query = query.bind(OffsetDateTime::now_utc());
+        }
+        query
+            .bind(session_id.to_string())
             .execute(conn)
             .await
             .map_err(SqlxStoreError::Sqlx)?;
 
         Ok(())
     }
+
+    async fn touch_with_conn(
+        &self,
+        conn: &mut SqliteConnection,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        let res = sqlx::query(&self.queries.touch)
+            .bind(self.ttl_policy.expiry_date(session_id, expiry_date))
+            .bind(session_id.to_string())
+            .bind(OffsetDateTime::now_utc())
+            .execute(conn)
+            .await
+            .map_err(SqlxStoreError::Sqlx)?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+impl StoreOptions for SqliteStore {
+    fn with_namespace(self, namespace: &str) -> Result<Self, String> {
+        self.with_table_name(namespace)
+    }
+
+    fn with_id_collision_policy(self, id_collision_policy: IdCollisionPolicy) -> Self {
+        Self::with_id_collision_policy(self, id_collision_policy)
+    }
+
+    fn with_decode_failure_policy(self, decode_failure_policy: DecodeFailurePolicy) -> Self {
+        Self::with_decode_failure_policy(self, decode_failure_policy)
+    }
+
+    fn with_slow_op_threshold(self, threshold: Duration) -> Self {
+        Self::with_slow_op_threshold(self, threshold)
+    }
+
+    fn with_ttl_policy(self, ttl_policy: TtlPolicy) -> Self {
+        Self::with_ttl_policy(self, ttl_policy)
+    }
 }
 
 #[async_trait]
 impl ExpiredDeletion for SqliteStore {
     async fn delete_expired(&self) -> session_store::Result<()> {
-        let query = format!(
-            r#"
-            delete from {table_name}
-            where datetime(expiry_date) < datetime('now')
-            "#,
-            table_name = self.table_name
-        );
-        sqlx::query(&query)
+        sqlx::query(&self.queries.delete_expired)
             .execute(&self.pool)
             .await
             .map_err(SqlxStoreError::Sqlx)?;
@@ -142,61 +836,325 @@ impl ExpiredDeletion for SqliteStore {
 #[async_trait]
 impl SessionStore for SqliteStore {
     async fn create(&self, record: &mut Record) -> session_store::Result<()> {
-        let mut tx = self.pool.begin().await.map_err(SqlxStoreError::Sqlx)?;
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            if let Some(write_queue) = &self.write_queue {
+                if let Written::Created(id) = write_queue
+                    .write(self, Write::Create(record.clone()))
+                    .await?
+                {
+                    record.id = id;
+                }
+                return Ok(());
+            }
 
-        while !self.try_create_with_conn(&mut tx, record).await? {
-            record.id = Id::default(); // Generate a new ID
+            let pending = &*record;
+            record.id = self.busy_retry.run(|| self.create_in_tx(pending)).await?;
+            Ok(())
         }
-
-        tx.commit().await.map_err(SqlxStoreError::Sqlx)?;
-
-        Ok(())
+        .await;
+        if res.is_ok() {
+            self.record_write(record.id);
+        }
+        self.slow_ops.finish(started, "create", Some(record));
+        res
     }
 
     async fn save(&self, record: &Record) -> session_store::Result<()> {
-        let mut conn = self.pool.acquire().await.map_err(SqlxStoreError::Sqlx)?;
-        self.save_with_conn(&mut conn, record).await
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            if let Some(write_queue) = &self.write_queue {
+                write_queue.write(self, Write::Save(record.clone())).await?;
+                return Ok(());
+            }
+
+            self.busy_retry
+                .run(|| async {
+                    let mut conn = self.pool.acquire().await.map_err(SqlxStoreError::Sqlx)?;
+                    self.save_with_conn(&mut conn, record).await
+                })
+                .await
+        }
+        .await;
+        if res.is_ok() {
+            self.record_write(record.id);
+        }
+        self.slow_ops.finish(started, "save", Some(record));
+        res
     }
 
     async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
-        let query = format!(
-            r#"
-            select data from {}
-            where id = ? and expiry_date > ?
-            "#,
-            self.table_name
-        );
-        let data: Option<(Vec<u8>,)> = sqlx::query_as(&query)
-            .bind(session_id.to_string())
-            .bind(OffsetDateTime::now_utc())
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(SqlxStoreError::Sqlx)?;
+        let started = self.slow_ops.start();
+        let res: session_store::Result<Option<Record>> = async {
+            let data: Option<(Vec<u8>, OffsetDateTime)> = sqlx::query_as(&self.queries.load)
+                .bind(session_id.to_string())
+                .bind(OffsetDateTime::now_utc())
+                .fetch_optional(self.load_pool(session_id))
+                .await
+                .map_err(SqlxStoreError::Sqlx)?;
 
-        if let Some((data,)) = data {
-            Ok(Some(
-                rmp_serde::from_slice(&data).map_err(SqlxStoreError::Decode)?,
-            ))
-        } else {
-            Ok(None)
+            if let Some((data, expiry_date)) = data {
+                let mut record: Record = decode_record(&data)?;
+                record.expiry_date = expiry_date;
+                Ok(Some(record))
+            } else {
+                Ok(None)
+            }
         }
+        .await;
+        self.slow_ops
+            .finish(started, "load", res.as_ref().ok().and_then(Option::as_ref));
+        self.decode_failure_policy
+            .apply(self, session_id, res)
+            .await
     }
 
     async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
-        let query = format!(
-            r#"
-            delete from {} where id = ?
-            "#,
-            self.table_name
-        );
-        sqlx::query(&query)
-            .bind(session_id.to_string())
-            .execute(&self.pool)
+        let started = self.slow_ops.start();
+        let res: session_store::Result<()> = async {
+            if let Some(write_queue) = &self.write_queue {
+                write_queue.write(self, Write::Delete(*session_id)).await?;
+                return Ok(());
+            }
+
+            self.busy_retry
+                .run(|| async {
+                    let mut conn = self.pool.acquire().await.map_err(SqlxStoreError::Sqlx)?;
+                    self.delete_with_conn(&mut conn, session_id).await
+                })
+                .await
+        }
+        .await;
+        if res.is_ok() {
+            self.record_write(*session_id);
+        }
+        self.slow_ops.finish(started, "delete", None);
+        res
+    }
+}
+
+#[async_trait]
+impl TouchSessionStore for SqliteStore {
+    async fn touch(
+        &self,
+        session_id: &Id,
+        expiry_date: OffsetDateTime,
+    ) -> session_store::Result<bool> {
+        let touched = if let Some(write_queue) = &self.write_queue {
+            let written = write_queue
+                .write(self, Write::Touch(*session_id, expiry_date))
+                .await?;
+            matches!(written, Written::Touched(true))
+        } else {
+            self.busy_retry
+                .run(|| async {
+                    let mut conn = self.pool.acquire().await.map_err(SqlxStoreError::Sqlx)?;
+                    self.touch_with_conn(&mut conn, session_id, expiry_date)
+                        .await
+                })
+                .await?
+        };
+        self.record_write(*session_id);
+        Ok(touched)
+    }
+}
+
+#[async_trait]
+impl ExportSessions for SqliteStore {
+    async fn export(&self, after: Option<Id>, limit: u32) -> session_store::Result<Vec<Record>> {
+        let rows: Vec<(Vec<u8>, OffsetDateTime)> = sqlx::query_as(&self.queries.export)
+            .bind(after.map(|id| id.to_string()).unwrap_or_default())
+            .bind(i64::from(limit))
+            .fetch_all(self.read_pool.as_ref().unwrap_or(&self.pool))
             .await
             .map_err(SqlxStoreError::Sqlx)?;
 
-        Ok(())
+        rows.into_iter()
+            .map(|(data, expiry_date)| {
+                let mut record: Record = decode_record(&data)?;
+                record.expiry_date = expiry_date;
+                Ok(record)
+            })
+            .collect()
+    }
+}
+
+/// Returns an error if the connection uses an in-memory database that the
+/// pool's other connections don't share.
+async fn check_shared_in_memory(
+    pool: &SqlitePool,
+    conn: &mut SqliteConnection,
+    table_name: &str,
+) -> sqlx::Result<()> {
+    let file: String =
+        sqlx::query_scalar("select file from pragma_database_list where name = 'main'")
+            .fetch_one(&mut *conn)
+            .await?;
+    let max_connections = pool.options().get_max_connections();
+    if !file.is_empty() || max_connections < 2 {
+        return Ok(());
     }
+
+    // The provided connection is still held, so this is another one.
+    let mut other = pool.acquire().await?;
+    let shared: bool =
+        sqlx::query_scalar("select exists (select 1 from sqlite_schema where name = ?)")
+            .bind(table_name)
+            .fetch_one(&mut *other)
+            .await?;
+    if !shared {
+        return Err(sqlx::Error::Configuration(
+            format!(
+                "The in-memory SQLite database isn't shared between the pool's {max_connections} \
+                 connections, so each would see its own, empty database. Connect with \
+                 `sqlite::memory:`, which shares the database, or use `SqliteStore::in_memory`."
+            )
+            .into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Queries rendered once per table name rather than on every operation.
+#[derive(Clone, Debug)]
+struct Queries {
+    create_table: String,
+    insert: String,
+    upsert: String,
+    load: String,
+    delete: String,
+    delete_expired: String,
+    touch: String,
+    export: String,
+    purge_deleted: Option<String>,
+}
+
+impl Queries {
+    fn new(
+        table_name: &str,
+        columns: &ColumnNames,
+        fixed_columns: &[(String, String)],
+        soft_delete_column: Option<&str>,
+        audit_columns: Option<(&str, &str)>,
+    ) -> Self {
+        let ColumnNames {
+            id,
+            data,
+            expiry_date,
+        } = columns;
+        let (soft_delete_definition, live, upsert_live) = match soft_delete_column {
+            Some(deleted_at) => (
+                format!(r#", "{deleted_at}" text"#),
+                format!(r#" and "{deleted_at}" is null"#),
+                format!(r#" where "{deleted_at}" is null"#),
+            ),
+            None => Default::default(),
+        };
+        let (audit_definitions, updated_now) = match audit_columns {
+            Some((created_at, updated_at)) => (
+                format!(
+                    r#", "{created_at}" text not null default current_timestamp, "{updated_at}" text not null default current_timestamp"#
+                ),
+                format!(r#", "{updated_at}" = current_timestamp"#),
+            ),
+            None => Default::default(),
+        };
+        let fixed_definitions: String = fixed_columns
+            .iter()
+            .map(|(name, _)| format!(r#", "{name}" text not null"#))
+            .collect();
+        let fixed_names: String = fixed_columns
+            .iter()
+            .map(|(name, _)| format!(r#", "{name}""#))
+            .collect();
+        let fixed_values = ", ?".repeat(fixed_columns.len());
+
+        Self {
+            create_table: format!(
+                r#"
+                create table if not exists "{table_name}"
+                (
+                    "{id}" text primary key not null,
+                    "{data}" blob not null,
+                    "{expiry_date}" integer not null{fixed_definitions}{soft_delete_definition}{audit_definitions}
+                )
+                "#
+            ),
+            insert: format!(
+                r#"
+                insert or abort into "{table_name}"
+                  ("{id}", "{data}", "{expiry_date}"{fixed_names})
+                  values (?, ?, ?{fixed_values})
+                "#
+            ),
+            upsert: format!(
+                r#"
+                insert into "{table_name}"
+                  ("{id}", "{data}", "{expiry_date}"{fixed_names})
+                  values (?, ?, ?{fixed_values})
+                on conflict("{id}") do update set
+                  "{data}" = excluded."{data}",
+                  "{expiry_date}" = excluded."{expiry_date}"{updated_now}{upsert_live}
+                "#
+            ),
+            load: format!(
+                r#"
+                select "{data}", "{expiry_date}" from "{table_name}"
+                where "{id}" = ? and "{expiry_date}" > ?{live}
+                "#
+            ),
+            delete: match soft_delete_column {
+                Some(deleted_at) => format!(
+                    r#"
+                    update "{table_name}" set "{deleted_at}" = ?
+                    where "{id}" = ?{live}
+                    "#
+                ),
+                None => format!(r#"delete from "{table_name}" where "{id}" = ?"#),
+            },
+            delete_expired: format!(
+                r#"
+                delete from "{table_name}"
+                where datetime("{expiry_date}") < datetime('now'){live}
+                "#
+            ),
+            touch: format!(
+                r#"
+                update "{table_name}" set "{expiry_date}" = ?{updated_now}
+                where "{id}" = ? and "{expiry_date}" > ?{live}
+                "#
+            ),
+            export: format!(
+                r#"
+                select "{data}", "{expiry_date}" from "{table_name}"
+                where "{id}" > ?{live}
+                order by "{id}"
+                limit ?
+                "#
+            ),
+            purge_deleted: soft_delete_column.map(|deleted_at| {
+                format!(r#"delete from "{table_name}" where "{deleted_at}" < ?"#)
+            }),
+        }
+    }
+}
+
+/// Copy an error so that it can be reported to every write in a failed batch.
+fn copy_error(err: &session_store::Error) -> session_store::Error {
+    match err {
+        session_store::Error::Encode(msg) => session_store::Error::Encode(msg.clone()),
+        session_store::Error::Decode(msg) => session_store::Error::Decode(msg.clone()),
+        session_store::Error::Backend(msg) => session_store::Error::Backend(msg.clone()),
+    }
+}
+
+fn invalid_column_name(column_name: &str) -> String {
+    format!(
+        "Invalid column name '{}'. Column names must be alphanumeric and may contain hyphens or \
+         underscores.",
+        column_name
+    )
 }
 
 fn is_valid_table_name(name: &str) -> bool {
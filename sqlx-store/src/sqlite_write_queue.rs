@@ -0,0 +1,100 @@
+use std::{
+    future::Future,
+    sync::{Arc, OnceLock},
+};
+
+use time::OffsetDateTime;
+use tokio::sync::{mpsc, oneshot};
+use tower_sessions_core::{
+    session::{Id, Record},
+    session_store,
+};
+
+use crate::SqliteStore;
+
+/// A write to be applied by the writer task.
+#[derive(Clone, Debug)]
+pub(crate) enum Write {
+    Create(Record),
+    Save(Record),
+    Delete(Id),
+    Touch(Id, OffsetDateTime),
+}
+
+/// The outcome of a [`Write`].
+#[derive(Debug)]
+pub(crate) enum Written {
+    /// The session was created with the contained ID.
+    Created(Id),
+    Saved,
+    Deleted,
+    Touched(bool),
+}
+
+#[derive(Debug)]
+pub(crate) struct QueuedWrite {
+    pub(crate) write: Write,
+    pub(crate) reply: oneshot::Sender<session_store::Result<Written>>,
+}
+
+/// A queue of writes applied by a single background task.
+///
+/// The task is spawned on the first write, so that it sees the store's final
+/// configuration, and exits once every clone of the store has been dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct WriteQueue {
+    max_batch_size: usize,
+    sender: Arc<OnceLock<mpsc::Sender<QueuedWrite>>>,
+}
+
+impl WriteQueue {
+    pub(crate) fn new(max_batch_size: usize) -> Self {
+        Self {
+            max_batch_size: max_batch_size.max(1),
+            sender: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub(crate) async fn write(
+        &self,
+        store: &SqliteStore,
+        write: Write,
+    ) -> session_store::Result<Written> {
+        let sender = self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel(self.max_batch_size);
+            spawn(run(store.writer(), receiver, self.max_batch_size));
+            sender
+        });
+
+        let (reply, written) = oneshot::channel();
+        sender
+            .send(QueuedWrite { write, reply })
+            .await
+            .map_err(|_| writer_stopped())?;
+        written.await.map_err(|_| writer_stopped())?
+    }
+}
+
+/// Spawn the writer task on the current Tokio runtime, or, with the
+/// `runtime-async-std` feature and outside of Tokio, on async-std, as SQLx
+/// itself does.
+fn spawn(task: impl Future<Output = ()> + Send + 'static) {
+    #[cfg(feature = "runtime-async-std")]
+    if tokio::runtime::Handle::try_current().is_err() {
+        async_std::task::spawn(task);
+        return;
+    }
+
+    tokio::spawn(task);
+}
+
+async fn run(store: SqliteStore, mut receiver: mpsc::Receiver<QueuedWrite>, max_batch_size: usize) {
+    let mut batch = Vec::with_capacity(max_batch_size);
+    while receiver.recv_many(&mut batch, max_batch_size).await > 0 {
+        store.write_batch(batch.drain(..)).await;
+    }
+}
+
+fn writer_stopped() -> session_store::Error {
+    session_store::Error::Backend("SQLite writer task stopped".to_string())
+}
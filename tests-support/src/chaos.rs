@@ -0,0 +1,171 @@
+//! Faults injected into backend containers while a test runs.
+
+use std::{
+    env,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+/// Restarts or pauses a backend container at random while a test runs.
+///
+/// Faults are injected with the `docker` CLI, so the container must be one
+/// started for the tests, or one named by the `<BACKEND>_CONTAINER` variable
+/// (e.g. `POSTGRES_CONTAINER`) when its URL is taken from the environment.
+/// The random schedule is printed along with its seed, which `CHAOS_SEED`
+/// sets to replay a failing run.
+#[derive(Debug)]
+pub struct Chaos {
+    container_id: String,
+    seed: u64,
+}
+
+/// A fault injected into the container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fault {
+    /// Kill the container and start it again, dropping every connection.
+    Restart,
+
+    /// Freeze the container for a while, stalling every connection.
+    Pause(Duration),
+}
+
+impl Chaos {
+    pub(crate) fn new(container_id: String) -> Self {
+        let seed = env::var("CHAOS_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_nanos() as u64)
+                    .unwrap_or_default()
+            });
+        Self { container_id, seed }
+    }
+
+    /// Inject faults into the container every `min` to `max` until the
+    /// returned handle is stopped.
+    pub fn unleash(self, min: Duration, max: Duration) -> ChaosHandle {
+        eprintln!(
+            "injecting faults into container {} with CHAOS_SEED={}",
+            self.container_id, self.seed
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            let container_id = self.container_id.clone();
+            let mut rng = SplitMix64(self.seed);
+            move || {
+                let mut faults = 0;
+                while !sleep_unless_stopped(&stop, rng.between(min, max)) {
+                    let fault = if rng.next() < u64::MAX / 2 {
+                        Fault::Restart
+                    } else {
+                        Fault::Pause(rng.between(min, max))
+                    };
+                    inject(&container_id, fault, &stop);
+                    faults += 1;
+                }
+                faults
+            }
+        });
+
+        ChaosHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Stops injecting faults when dropped; see [`Chaos::unleash`].
+#[derive(Debug)]
+pub struct ChaosHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<u32>>,
+}
+
+impl ChaosHandle {
+    /// Stop injecting faults, returning the number of faults injected.
+    ///
+    /// The container is left running, though the backend may still be
+    /// starting up.
+    pub fn stop(mut self) -> u32 {
+        self.stop_thread()
+    }
+
+    fn stop_thread(&mut self) -> u32 {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread
+            .take()
+            .map(|thread| thread.join().expect("chaos thread panicked"))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for ChaosHandle {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            self.stop_thread();
+        }
+    }
+}
+
+fn inject(container_id: &str, fault: Fault, stop: &AtomicBool) {
+    eprintln!("chaos: {fault:?}");
+    match fault {
+        Fault::Restart => docker(&["restart", "--time", "0", container_id]),
+        Fault::Pause(duration) => {
+            docker(&["pause", container_id]);
+            sleep_unless_stopped(stop, duration);
+            docker(&["unpause", container_id]);
+        }
+    }
+}
+
+fn docker(args: &[&str]) {
+    let status = Command::new("docker")
+        .args(args)
+        .status()
+        .expect("failed to run docker");
+    assert!(status.success(), "docker {} failed", args.join(" "));
+}
+
+/// Sleep for the duration, returning early with `true` once stopped.
+fn sleep_unless_stopped(stop: &AtomicBool, duration: Duration) -> bool {
+    const TICK: Duration = Duration::from_millis(50);
+
+    let mut remaining = duration;
+    while !stop.load(Ordering::SeqCst) {
+        if remaining.is_zero() {
+            return false;
+        }
+        let tick = remaining.min(TICK);
+        thread::sleep(tick);
+        remaining -= tick;
+    }
+    true
+}
+
+/// A small seeded generator, so that a schedule can be replayed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn between(&mut self, min: Duration, max: Duration) -> Duration {
+        let span = max.saturating_sub(min).as_millis() as u64;
+        min + Duration::from_millis(self.next() % (span + 1))
+    }
+}
@@ -0,0 +1,192 @@
+//! Backing services for the integration tests.
+//!
+//! Each function returns the URL of a running backend. If the corresponding
+//! environment variable (e.g. `POSTGRES_URL`) is set, that backend is used as
+//! is; otherwise a throwaway container is started via `testcontainers` on first
+//! use and shared by every test in the process. Containers are removed by the
+//! `testcontainers` reaper once the test process exits.
+//!
+//! This makes `cargo test -p tests` run the full suite against every store,
+//! given a local Docker daemon.
+//!
+//! Each backend also has a [`Chaos`] controller for tests that inject faults
+//! into its container while they run, and the backends most tests share have
+//! a function returning a connected pool, e.g. [`pg_pool`].
+
+use std::{env, net::TcpListener, sync::OnceLock, thread};
+
+use fred::{
+    clients::Pool,
+    prelude::{ClientLike, Config},
+};
+use sqlx::{PgPool, SqlitePool};
+use testcontainers_modules::{
+    mongo::Mongo,
+    mysql::Mysql,
+    postgres::Postgres,
+    redis::Redis,
+    testcontainers::{
+        core::IntoContainerPort, runners::SyncRunner, Container, Image, ImageExt,
+        TestcontainersError,
+    },
+};
+
+pub use self::chaos::{Chaos, ChaosHandle};
+
+mod chaos;
+
+static POSTGRES: OnceLock<Backend> = OnceLock::new();
+static MYSQL: OnceLock<Backend> = OnceLock::new();
+static REDIS: OnceLock<Backend> = OnceLock::new();
+static MONGODB: OnceLock<Backend> = OnceLock::new();
+
+/// A backend URL, along with the container serving it if one was started.
+struct Backend {
+    url: String,
+    container_id: Option<String>,
+    _container: Option<Box<dyn std::any::Any + Send + Sync>>,
+}
+
+impl Backend {
+    fn chaos(&self, container_var: &str) -> Chaos {
+        let container_id = self
+            .container_id
+            .clone()
+            .or_else(|| env::var(container_var).ok())
+            .unwrap_or_else(|| panic!("set {container_var} to inject faults into the backend"));
+        Chaos::new(container_id)
+    }
+}
+
+fn backend<I: Image + Send + 'static>(
+    cell: &'static OnceLock<Backend>,
+    var: &str,
+    image: impl FnOnce() -> I + Send + 'static,
+    port: u16,
+    url: impl FnOnce(&str, u16) -> String,
+) -> &'static str
+where
+    Container<I>: Send + Sync,
+{
+    &cell
+        .get_or_init(|| {
+            if let Ok(url) = env::var(var) {
+                return Backend {
+                    url,
+                    container_id: None,
+                    _container: None,
+                };
+            }
+
+            // Docker would map a restarted container to another random port, so
+            // the port is chosen up front for chaos tests to restart it.
+            let host_port = TcpListener::bind("127.0.0.1:0")
+                .and_then(|listener| listener.local_addr())
+                .expect("failed to find a free port")
+                .port();
+
+            // The blocking runner drives its own runtime, which cannot be done
+            // from within the async tests that call this.
+            let (container, host, port) = thread::spawn(move || {
+                let container = image().with_mapped_port(host_port, port.tcp()).start()?;
+                let host = container.get_host()?.to_string();
+                let port = container.get_host_port_ipv4(port)?;
+                Ok::<_, TestcontainersError>((container, host, port))
+            })
+            .join()
+            .unwrap()
+            .unwrap_or_else(|err| panic!("failed to start container for {var}: {err}"));
+
+            Backend {
+                url: url(&host, port),
+                container_id: Some(container.id().to_string()),
+                _container: Some(Box::new(container)),
+            }
+        })
+        .url
+}
+
+/// The URL of a PostgreSQL server, from `POSTGRES_URL` or a container.
+pub fn postgres_url() -> &'static str {
+    backend(
+        &POSTGRES,
+        "POSTGRES_URL",
+        Postgres::default,
+        5432,
+        |host, port| format!("postgres://postgres:postgres@{host}:{port}/postgres"),
+    )
+}
+
+/// The URL of a MySQL server, from `MYSQL_URL` or a container.
+pub fn mysql_url() -> &'static str {
+    backend(&MYSQL, "MYSQL_URL", Mysql::default, 3306, |host, port| {
+        format!("mysql://root@{host}:{port}/test")
+    })
+}
+
+/// The URL of a Redis server, from `REDIS_URL` or a container.
+pub fn redis_url() -> &'static str {
+    backend(&REDIS, "REDIS_URL", Redis::default, 6379, |host, port| {
+        format!("redis://{host}:{port}/1")
+    })
+}
+
+/// The URL of a MongoDB server, from `MONGODB_URL` or a container.
+pub fn mongodb_url() -> &'static str {
+    backend(
+        &MONGODB,
+        "MONGODB_URL",
+        Mongo::default,
+        27017,
+        |host, port| format!("mongodb://{host}:{port}"),
+    )
+}
+
+/// A pool connected to the Redis server of [`redis_url`].
+pub async fn redis_pool() -> Pool {
+    let config = Config::from_url(redis_url()).unwrap();
+    let pool = Pool::new(config, None, None, None, 6).unwrap();
+
+    pool.connect();
+    pool.wait_for_connect().await.unwrap();
+
+    pool
+}
+
+/// A pool connected to the PostgreSQL server of [`postgres_url`].
+pub async fn pg_pool() -> PgPool {
+    PgPool::connect(postgres_url()).await.unwrap()
+}
+
+/// A pool connected to a new in-memory SQLite database.
+pub async fn sqlite_pool() -> SqlitePool {
+    SqlitePool::connect("sqlite::memory:").await.unwrap()
+}
+
+/// Fault injection for the PostgreSQL server of [`postgres_url`], or the
+/// container named by `POSTGRES_CONTAINER`.
+pub fn postgres_chaos() -> Chaos {
+    postgres_url();
+    POSTGRES.get().unwrap().chaos("POSTGRES_CONTAINER")
+}
+
+/// Fault injection for the MySQL server of [`mysql_url`], or the container
+/// named by `MYSQL_CONTAINER`.
+pub fn mysql_chaos() -> Chaos {
+    mysql_url();
+    MYSQL.get().unwrap().chaos("MYSQL_CONTAINER")
+}
+
+/// Fault injection for the Redis server of [`redis_url`], or the container
+/// named by `REDIS_CONTAINER`.
+pub fn redis_chaos() -> Chaos {
+    redis_url();
+    REDIS.get().unwrap().chaos("REDIS_CONTAINER")
+}
+
+/// Fault injection for the MongoDB server of [`mongodb_url`], or the
+/// container named by `MONGODB_CONTAINER`.
+pub fn mongodb_chaos() -> Chaos {
+    mongodb_url();
+    MONGODB.get().unwrap().chaos("MONGODB_CONTAINER")
+}
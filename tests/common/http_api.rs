@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use http::{header, Method, Request, Response, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+use tower_sessions_http_store::{HttpClient, HttpClientError};
+
+/// A client serving the session API from memory instead of sending
+/// requests.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryApi {
+    sessions: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+#[async_trait]
+impl HttpClient for InMemoryApi {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, HttpClientError> {
+        let id = request
+            .uri()
+            .path()
+            .strip_prefix("/sessions/")
+            .ok_or("unexpected path")?
+            .to_string();
+        let mut sessions = self.sessions.lock().unwrap();
+
+        let (status, body) = match *request.method() {
+            Method::GET => match sessions.get(&id) {
+                Some(data) => (StatusCode::OK, data.clone()),
+                None => (StatusCode::NOT_FOUND, Vec::new()),
+            },
+            Method::PUT
+                if request.headers().contains_key(header::IF_NONE_MATCH)
+                    && sessions.contains_key(&id) =>
+            {
+                (StatusCode::PRECONDITION_FAILED, Vec::new())
+            }
+            Method::PUT => {
+                sessions.insert(id, request.into_body());
+                (StatusCode::NO_CONTENT, Vec::new())
+            }
+            Method::DELETE => match sessions.remove(&id) {
+                Some(_) => (StatusCode::NO_CONTENT, Vec::new()),
+                None => (StatusCode::NOT_FOUND, Vec::new()),
+            },
+            _ => (StatusCode::METHOD_NOT_ALLOWED, Vec::new()),
+        };
+
+        Ok(Response::builder().status(status).body(body)?)
+    }
+}
+
+/// A client sending requests straight to a router serving the session API.
+#[derive(Clone, Debug)]
+pub struct RouterClient(pub axum::Router);
+
+#[async_trait]
+impl HttpClient for RouterClient {
+    async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, HttpClientError> {
+        let request = request.map(axum::body::Body::from);
+        let response = self.0.clone().oneshot(request).await?;
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await?.to_bytes().to_vec();
+        Ok(Response::from_parts(parts, body))
+    }
+}
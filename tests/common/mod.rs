@@ -5,6 +5,11 @@ use time::Duration;
 use tower_cookies::{cookie, Cookie};
 use tower_sessions::{Expiry, Session, SessionManagerLayer, SessionStore};
 
+pub mod http_api;
+pub mod records;
+pub mod scenarios;
+pub mod snapshots;
+
 fn routes() -> Router {
     Router::new()
         .route("/", get(|_: Session| async move { "Hello, world!" }))
@@ -325,3 +330,353 @@ macro_rules! route_tests {
         }
     };
 }
+
+#[macro_export]
+macro_rules! record_tests {
+    ($create_store:expr) => {
+        record_tests!(
+            $create_store,
+            $crate::common::records::RecordLimits::default()
+        );
+    };
+
+    ($create_store:expr, $limits:expr) => {
+        mod record_round_trip {
+            use tower_sessions::{session::Record, SessionStore};
+            use $crate::common::records::{assert_round_trip, RecordGenerator};
+
+            use super::*;
+
+            #[tokio::test]
+            async fn records_round_trip() {
+                let limits = $limits;
+                let store = $create_store().await;
+                let mut records = RecordGenerator::new(0x5e55_1075, limits.clone());
+
+                for _ in 0..32 {
+                    let mut created = records.record();
+                    store.create(&mut created).await.unwrap();
+                    let loaded = store.load(&created.id).await.unwrap();
+                    assert_round_trip(&created, loaded, &limits);
+
+                    let saved = Record {
+                        id: created.id,
+                        ..records.record()
+                    };
+                    store.save(&saved).await.unwrap();
+                    let loaded = store.load(&saved.id).await.unwrap();
+                    assert_round_trip(&saved, loaded, &limits);
+
+                    store.delete(&saved.id).await.unwrap();
+                    assert!(store.load(&saved.id).await.unwrap().is_none());
+                }
+            }
+        }
+    };
+}
+
+/// Tests for stores implementing `TouchSessionStore`.
+#[macro_export]
+macro_rules! touch_tests {
+    ($create_store:expr) => {
+        mod touch {
+            use std::collections::HashMap;
+
+            use time::OffsetDateTime;
+            use tower_sessions::{
+                session::{Id, Record},
+                SessionStore,
+            };
+            use tower_sessions_ext::TouchSessionStore;
+
+            use super::*;
+
+            fn record(expires_in: time::Duration) -> Record {
+                Record {
+                    id: Id::default(),
+                    data: HashMap::new(),
+                    expiry_date: OffsetDateTime::now_utc() + expires_in,
+                }
+            }
+
+            #[tokio::test]
+            async fn missing_session() {
+                let store = $create_store().await;
+                let expiry_date = OffsetDateTime::now_utc() + time::Duration::hours(1);
+                assert!(!store.touch(&Id::default(), expiry_date).await.unwrap());
+            }
+
+            #[tokio::test]
+            async fn expired_session() {
+                let store = $create_store().await;
+                let mut record = record(time::Duration::seconds(1));
+                store.create(&mut record).await.unwrap();
+                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+                let expiry_date = OffsetDateTime::now_utc() + time::Duration::hours(1);
+                assert!(!store.touch(&record.id, expiry_date).await.unwrap());
+                assert!(store.load(&record.id).await.unwrap().is_none());
+            }
+
+            #[tokio::test]
+            async fn touched_session_outlives_its_expiry_date() {
+                let store = $create_store().await;
+                let mut record = record(time::Duration::seconds(2));
+                store.create(&mut record).await.unwrap();
+
+                let expiry_date = OffsetDateTime::now_utc() + time::Duration::hours(1);
+                assert!(store.touch(&record.id, expiry_date).await.unwrap());
+                tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+
+                let loaded = store.load(&record.id).await.unwrap().unwrap();
+                assert_eq!(loaded.data, record.data);
+                assert_eq!(
+                    loaded.expiry_date.unix_timestamp(),
+                    expiry_date.unix_timestamp()
+                );
+
+                store.delete(&record.id).await.unwrap();
+            }
+        }
+    };
+}
+
+/// Tests for stores implementing `EraseUserSessions`, created by
+/// `$create_store` with sessions belonging to the user in their `user_id`
+/// field.
+#[macro_export]
+macro_rules! erase_tests {
+    ($create_store:expr) => {
+        mod erase {
+            use std::collections::HashMap;
+
+            use serde_json::json;
+            use time::OffsetDateTime;
+            use tower_sessions::{
+                session::{Id, Record},
+                SessionStore,
+            };
+            use tower_sessions_ext::{EraseUserSessions, ErasureReport};
+
+            use super::*;
+
+            const KEY: &[u8] = b"erasure-key";
+
+            fn record(user_id: &str) -> Record {
+                Record {
+                    id: Id::default(),
+                    data: HashMap::from([("user_id".to_string(), json!(user_id))]),
+                    expiry_date: OffsetDateTime::now_utc() + time::Duration::hours(1),
+                }
+            }
+
+            #[tokio::test]
+            async fn erases_only_the_users_sessions() {
+                let store = $create_store().await;
+                let (alice, bob) = (Id::default().to_string(), Id::default().to_string());
+                let mut records = [record(&alice), record(&alice), record(&bob)];
+                for record in &mut records {
+                    store.create(record).await.unwrap();
+                }
+
+                let report = store.erase_user(&json!(alice), KEY).await.unwrap();
+                assert_eq!(report.erased, 2);
+                assert_eq!(
+                    report.user_id_hash,
+                    ErasureReport::user_id_hash(&json!(alice), KEY)
+                );
+                assert!(store.load(&records[0].id).await.unwrap().is_none());
+                assert!(store.load(&records[1].id).await.unwrap().is_none());
+                assert!(store.load(&records[2].id).await.unwrap().is_some());
+
+                let report = store.erase_user(&json!(alice), KEY).await.unwrap();
+                assert_eq!(report.erased, 0);
+
+                store.delete(&records[2].id).await.unwrap();
+            }
+
+            #[tokio::test]
+            async fn signs_reports() {
+                let store = $create_store().await;
+                let alice = Id::default().to_string();
+                store.create(&mut record(&alice)).await.unwrap();
+
+                let report = store.erase_user(&json!(alice), KEY).await.unwrap();
+                assert_ne!(
+                    report.user_id_hash,
+                    ErasureReport::user_id_hash(&json!(alice), b"other-key")
+                );
+                let signature = report.sign(KEY);
+                assert!(report.verify(KEY, &signature));
+                assert!(!report.verify(b"other-key", &signature));
+                assert!(!report.verify(KEY, "not hex"));
+
+                let tampered = ErasureReport {
+                    erased: report.erased + 1,
+                    ..report
+                };
+                assert!(!tampered.verify(KEY, &signature));
+            }
+        }
+    };
+}
+
+/// Tests that store errors keep their own classification as transient or
+/// permanent once converted to `tower-sessions` errors.
+#[macro_export]
+macro_rules! transient_error_tests {
+    (transient: [$($transient:expr),+ $(,)?], permanent: [$($permanent:expr),+ $(,)?] $(,)?) => {
+        mod transient_errors {
+            use tower_sessions::session_store;
+            use tower_sessions_ext::is_transient;
+
+            #[test]
+            fn keep_their_classification() {
+                $(
+                    let err = $transient;
+                    assert!(err.is_transient(), "{err}");
+                    let err: session_store::Error = err.into();
+                    assert!(is_transient(&err), "{err}");
+                )+
+                $(
+                    let err = $permanent;
+                    assert!(!err.is_transient(), "{err}");
+                    let err: session_store::Error = err.into();
+                    assert!(!is_transient(&err), "{err}");
+                )+
+            }
+        }
+    };
+}
+
+/// Tests for stores taking an `IdCollisionPolicy`.
+#[macro_export]
+macro_rules! id_collision_tests {
+    ($create_store:expr) => {
+        mod id_collision {
+            use std::collections::HashMap;
+
+            use time::OffsetDateTime;
+            use tower_sessions::{
+                session::{Id, Record},
+                SessionStore,
+            };
+            use tower_sessions_ext::IdCollisionPolicy;
+
+            use super::*;
+
+            fn record(id: Id) -> Record {
+                Record {
+                    id,
+                    data: HashMap::new(),
+                    expiry_date: OffsetDateTime::now_utc() + time::Duration::hours(1),
+                }
+            }
+
+            #[tokio::test]
+            async fn replaces_colliding_ids_with_generated_ones() {
+                let replacement = Id::default();
+                let store = $create_store().await.with_id_collision_policy(
+                    IdCollisionPolicy::default().with_id_generator(move || replacement),
+                );
+                let mut taken = record(Id::default());
+                store.create(&mut taken).await.unwrap();
+
+                let mut colliding = record(taken.id);
+                store.create(&mut colliding).await.unwrap();
+                assert_eq!(colliding.id, replacement);
+                assert!(store.load(&replacement).await.unwrap().is_some());
+
+                store.delete(&taken.id).await.unwrap();
+                store.delete(&replacement).await.unwrap();
+            }
+
+            #[tokio::test]
+            async fn fails_once_attempts_are_exhausted() {
+                let taken_id = Id::default();
+                let store = $create_store().await.with_id_collision_policy(
+                    IdCollisionPolicy::default()
+                        .with_max_attempts(3)
+                        .with_id_generator(move || taken_id),
+                );
+                let mut taken = record(taken_id);
+                store.create(&mut taken).await.unwrap();
+
+                let mut colliding = record(taken_id);
+                assert!(store.create(&mut colliding).await.is_err());
+                assert!(store.load(&taken_id).await.unwrap().is_some());
+
+                store.delete(&taken_id).await.unwrap();
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! concurrency_tests {
+    ($create_store:expr) => {
+        mod concurrency {
+            use $crate::common::scenarios;
+
+            use super::*;
+
+            #[tokio::test(flavor = "multi_thread")]
+            async fn colliding_creates() {
+                scenarios::colliding_creates($create_store().await).await;
+            }
+
+            #[tokio::test(flavor = "multi_thread")]
+            async fn racing_saves_and_deletes() {
+                scenarios::racing_saves_and_deletes($create_store().await).await;
+            }
+        }
+    };
+}
+
+/// Tests for stores that keep sessions in files, opened from the directory
+/// passed to `$open_store`.
+#[macro_export]
+macro_rules! recovery_tests {
+    ($open_store:expr) => {
+        mod recovery {
+            use $crate::common::scenarios;
+
+            use super::*;
+
+            #[tokio::test(flavor = "multi_thread")]
+            async fn recovers_after_kill() {
+                scenarios::recovers_after_kill($open_store).await;
+            }
+        }
+    };
+}
+
+/// Tests injecting faults into the backend container returned by `$chaos`
+/// while sessions are written; see [`scenarios::survives_backend_faults`].
+///
+/// They restart the backend shared by every test, so they are ignored unless
+/// run on their own, e.g. with `cargo test -p tests chaos -- --ignored`.
+/// Stores are wrapped in a [`TimeoutStore`](tower_sessions_ext::TimeoutStore),
+/// so that operations stalled by a paused backend fail as transient errors.
+#[macro_export]
+macro_rules! chaos_tests {
+    ($create_store:expr, $chaos:expr) => {
+        chaos_tests!($create_store, $chaos, durable: true);
+    };
+    ($create_store:expr, $chaos:expr, durable: $durable:expr) => {
+        mod chaos {
+            use tower_sessions_ext::TimeoutStore;
+            use $crate::common::scenarios;
+
+            use super::*;
+
+            #[tokio::test(flavor = "multi_thread")]
+            #[ignore = "restarts the shared backend; run alone with --ignored"]
+            async fn survives_backend_faults() {
+                let store =
+                    TimeoutStore::new($create_store().await, std::time::Duration::from_secs(5));
+                scenarios::survives_backend_faults(store, $chaos(), $durable).await;
+            }
+        }
+    };
+}
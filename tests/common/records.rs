@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+use time::{macros::datetime, Duration, OffsetDateTime};
+use tower_sessions::session::{Id, Record};
+
+/// The limits of how faithfully a store keeps session records.
+#[derive(Clone, Debug)]
+pub struct RecordLimits {
+    /// The precision to which loaded expiry dates must match saved ones.
+    pub expiry_precision: Duration,
+
+    /// The latest expiry date the store can represent.
+    pub max_expiry: OffsetDateTime,
+}
+
+impl Default for RecordLimits {
+    fn default() -> Self {
+        Self {
+            expiry_precision: Duration::nanoseconds(1),
+            max_expiry: datetime!(9999-12-31 23:59:59.999_999_999 UTC),
+        }
+    }
+}
+
+/// A deterministic source of session records, seeded so that any failure can
+/// be reproduced.
+///
+/// The first records cover known edge cases, such as empty data, extreme
+/// numbers, unusual strings, and expiry dates with full nanosecond precision
+/// or at the latest date the store supports; the rest are random.
+pub struct RecordGenerator {
+    state: u64,
+    limits: RecordLimits,
+    edge_cases: Vec<Record>,
+}
+
+impl RecordGenerator {
+    pub fn new(seed: u64, limits: RecordLimits) -> Self {
+        let now = OffsetDateTime::now_utc();
+        let soon = now.replace_nanosecond(999_999_999).unwrap() + Duration::seconds(1);
+        let edge_cases = vec![
+            record(HashMap::new(), soon),
+            record(
+                HashMap::from([
+                    (String::new(), json!("")),
+                    ("nul".to_string(), json!("\0 \u{1}")),
+                    ("unicode".to_string(), json!("été 🦀 ∑ \u{202e}")),
+                    ("long".to_string(), json!("x".repeat(64 * 1024))),
+                ]),
+                limits.max_expiry,
+            ),
+            record(
+                HashMap::from([
+                    ("i64_min".to_string(), json!(i64::MIN)),
+                    ("i64_max".to_string(), json!(i64::MAX)),
+                    ("u64_max".to_string(), json!(u64::MAX)),
+                    ("f64_min_positive".to_string(), json!(f64::MIN_POSITIVE)),
+                    ("f64_max".to_string(), json!(f64::MAX)),
+                    ("nested".to_string(), json!([[[[{"a": [null, true, {}]}]]]])),
+                ]),
+                soon,
+            ),
+        ];
+
+        Self {
+            state: seed,
+            limits,
+            edge_cases,
+        }
+    }
+
+    /// Produce the next record.
+    pub fn record(&mut self) -> Record {
+        if let Some(record) = self.edge_cases.pop() {
+            return record;
+        }
+
+        let data = (0..self.below(8))
+            .map(|_| (self.string(), self.value(3)))
+            .collect();
+        let expiry_date = self.expiry_date();
+        record(data, expiry_date)
+    }
+
+    fn expiry_date(&mut self) -> OffsetDateTime {
+        let now = OffsetDateTime::now_utc();
+        let max_seconds = (self.limits.max_expiry - now).whole_seconds() as u64;
+        // Favor expiry dates within a year, as sessions usually have, while
+        // still reaching the latest supported date.
+        let seconds = match self.below(4) {
+            0 => self.below(max_seconds),
+            _ => self.below(max_seconds.min(365 * 24 * 60 * 60)),
+        };
+        let expiry_date = now
+            + Duration::seconds(seconds as i64 + 1)
+            + Duration::nanoseconds(self.below(1_000_000_000) as i64);
+        expiry_date.min(self.limits.max_expiry)
+    }
+
+    fn value(&mut self, depth: u32) -> Value {
+        let kinds = if depth == 0 { 6 } else { 8 };
+        match self.below(kinds) {
+            0 => Value::Null,
+            1 => json!(self.below(2) == 1),
+            2 => json!(self.next() as i64),
+            3 => json!(self.next()),
+            4 => json!(f64::from_bits(self.next())),
+            5 => json!(self.string()),
+            6 => Value::Array((0..self.below(4)).map(|_| self.value(depth - 1)).collect()),
+            _ => Value::Object(
+                (0..self.below(4))
+                    .map(|_| (self.string(), self.value(depth - 1)))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn string(&mut self) -> String {
+        (0..self.below(16))
+            .map(|_| loop {
+                // Include control characters and characters outside the basic
+                // multilingual plane.
+                let code = match self.below(3) {
+                    0 => self.below(0x80),
+                    1 => self.below(0x800),
+                    _ => self.below(0x11_0000),
+                };
+                if let Some(c) = char::from_u32(code as u32) {
+                    break c;
+                }
+            })
+            .collect()
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next() % bound
+        }
+    }
+
+    /// SplitMix64.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+fn record(data: HashMap<String, Value>, expiry_date: OffsetDateTime) -> Record {
+    Record {
+        id: Id::default(),
+        data,
+        expiry_date,
+    }
+}
+
+/// Assert that a loaded record matches the saved one within the store's
+/// limits.
+pub fn assert_round_trip(saved: &Record, loaded: Option<Record>, limits: &RecordLimits) {
+    let loaded = loaded.unwrap_or_else(|| panic!("record {} was not loaded", saved.id));
+    assert_eq!(loaded.id, saved.id);
+    assert_eq!(loaded.data, saved.data, "data of record {}", saved.id);
+    assert!(
+        (loaded.expiry_date - saved.expiry_date).abs() < limits.expiry_precision,
+        "expiry date of record {}: saved {}, loaded {}",
+        saved.id,
+        saved.expiry_date,
+        loaded.expiry_date,
+    );
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    path::PathBuf,
+    time::{Duration as StdDuration, Instant},
+};
+
+use serde_json::json;
+use tests_support::Chaos;
+use time::{Duration, OffsetDateTime};
+use tokio::task::JoinSet;
+use tower_sessions::{
+    session::{Id, Record},
+    SessionStore,
+};
+use tower_sessions_ext::is_transient;
+
+/// The number of operations raced against each other.
+const RACERS: u64 = 16;
+
+fn record(id: Id, n: u64) -> Record {
+    Record {
+        id,
+        data: HashMap::from([("n".to_string(), json!(n))]),
+        expiry_date: OffsetDateTime::now_utc() + Duration::hours(1),
+    }
+}
+
+/// Create sessions with the same ID at once, each of which must end up
+/// stored under its own ID without overwriting another.
+pub async fn colliding_creates<S: SessionStore + Clone>(store: S) {
+    let id = Id::default();
+    let mut creates = JoinSet::new();
+    for n in 0..RACERS {
+        let store = store.clone();
+        creates.spawn(async move {
+            let mut created = record(id, n);
+            store.create(&mut created).await.unwrap();
+            created
+        });
+    }
+    let created = creates.join_all().await;
+
+    let ids: HashSet<Id> = created.iter().map(|created| created.id).collect();
+    assert_eq!(ids.len(), created.len(), "colliding creates shared an ID");
+    for created in created {
+        let loaded = store.load(&created.id).await.unwrap();
+        assert_eq!(
+            loaded.map(|loaded| loaded.data),
+            Some(created.data),
+            "record {} was overwritten",
+            created.id
+        );
+    }
+}
+
+/// Race saves of a session against deletes of it, after which the session
+/// must either be gone or hold the data of one of the saves, and deleting it
+/// must still work.
+pub async fn racing_saves_and_deletes<S: SessionStore + Clone>(store: S) {
+    let mut created = record(Id::default(), RACERS);
+    store.create(&mut created).await.unwrap();
+    let id = created.id;
+
+    let mut racers = JoinSet::new();
+    for n in 0..RACERS {
+        let store = store.clone();
+        racers.spawn(async move {
+            if n % 2 == 0 {
+                store.save(&record(id, n)).await.unwrap();
+            } else {
+                store.delete(&id).await.unwrap();
+            }
+        });
+    }
+    racers.join_all().await;
+
+    if let Some(loaded) = store.load(&id).await.unwrap() {
+        let n = loaded.data["n"].as_u64().unwrap();
+        assert!(
+            n == RACERS || n % 2 == 0,
+            "record {id} holds data that was never saved: {n}"
+        );
+    }
+
+    store.delete(&id).await.unwrap();
+    assert!(store.load(&id).await.unwrap().is_none());
+}
+
+/// Abort a store in the middle of writes, as a killed process would, and
+/// reopen it from the same directory: every acknowledged write must have
+/// survived, and the store must accept writes again.
+pub async fn recovers_after_kill<S, F, Fut>(open_store: F)
+where
+    S: SessionStore + Clone,
+    F: Fn(PathBuf) -> Fut,
+    Fut: Future<Output = S>,
+{
+    let dir = tempfile::tempdir().unwrap().keep();
+    let store = open_store(dir.clone()).await;
+
+    let mut acknowledged = Vec::new();
+    for n in 0..RACERS {
+        let mut created = record(Id::default(), n);
+        store.create(&mut created).await.unwrap();
+        acknowledged.push(created);
+    }
+    let deleted = acknowledged.split_off(RACERS as usize / 2);
+    for deleted in &deleted {
+        store.delete(&deleted.id).await.unwrap();
+    }
+
+    let mut in_flight = JoinSet::new();
+    for _ in 0..4 {
+        let store = store.clone();
+        in_flight.spawn(async move {
+            for n in 0.. {
+                let mut created = record(Id::default(), n);
+                store.create(&mut created).await.unwrap();
+                // Stores that never wait on I/O would otherwise never yield
+                // to be aborted.
+                tokio::task::yield_now().await;
+            }
+        });
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    in_flight.abort_all();
+    while in_flight.join_next().await.is_some() {}
+    drop(store);
+
+    let store = open_store(dir).await;
+    for acknowledged in &acknowledged {
+        let loaded = store.load(&acknowledged.id).await.unwrap();
+        assert_eq!(
+            loaded.map(|loaded| loaded.data),
+            Some(acknowledged.data.clone()),
+            "acknowledged record {} was lost",
+            acknowledged.id
+        );
+    }
+    for deleted in &deleted {
+        assert!(
+            store.load(&deleted.id).await.unwrap().is_none(),
+            "deleted record {} came back",
+            deleted.id
+        );
+    }
+
+    let mut created = record(Id::default(), 0);
+    store.create(&mut created).await.unwrap();
+    assert!(store.load(&created.id).await.unwrap().is_some());
+}
+
+/// How long sessions are written while faults are injected.
+const CHAOS_DURATION: StdDuration = StdDuration::from_secs(20);
+
+/// How long the backend may take to come back once faults stop.
+const RECOVERY_TIMEOUT: StdDuration = StdDuration::from_secs(60);
+
+/// Save and load sessions while the backend is restarted and paused at
+/// random: every operation must either succeed or fail with a transient
+/// error, and every session must hold the data of one of its saves, both
+/// while faults are injected and once the backend is back.
+///
+/// Saves that failed may or may not have been applied, so any of them is
+/// accepted until a later save succeeds. With `durable` unset, sessions may
+/// also be lost, as with Redis restarted without persistence.
+pub async fn survives_backend_faults<S: SessionStore + Clone>(
+    store: S,
+    chaos: Chaos,
+    durable: bool,
+) {
+    let mut created = Vec::new();
+    for n in 0..RACERS {
+        let mut record = record(Id::default(), n);
+        store.create(&mut record).await.unwrap();
+        created.push(record);
+    }
+
+    let chaos = chaos.unleash(StdDuration::from_millis(500), StdDuration::from_secs(3));
+    let mut writers = JoinSet::new();
+    for created in created {
+        let store = store.clone();
+        writers.spawn(async move {
+            let id = created.id;
+            // The values the session may hold, given the saves so far.
+            let mut possible = vec![created.data["n"].as_u64().unwrap()];
+            let started = Instant::now();
+            let mut n = created.data["n"].as_u64().unwrap();
+            while started.elapsed() < CHAOS_DURATION {
+                n += RACERS;
+                match store.save(&record(id, n)).await {
+                    Ok(()) => possible = vec![n],
+                    Err(err) => {
+                        assert!(is_transient(&err), "save failed with {err:?}");
+                        possible.push(n);
+                    }
+                }
+                match store.load(&id).await {
+                    Ok(loaded) => check_loaded(id, loaded, &possible, durable),
+                    Err(err) => assert!(is_transient(&err), "load failed with {err:?}"),
+                }
+            }
+            (id, possible)
+        });
+    }
+    let written = writers.join_all().await;
+    let faults = chaos.stop();
+    assert!(faults > 0, "no faults were injected");
+
+    let started = Instant::now();
+    for (id, possible) in written {
+        let loaded = loop {
+            match store.load(&id).await {
+                Ok(loaded) => break loaded,
+                Err(err) if is_transient(&err) && started.elapsed() < RECOVERY_TIMEOUT => {
+                    tokio::time::sleep(StdDuration::from_millis(200)).await;
+                }
+                Err(err) => panic!("backend didn't recover: {err:?}"),
+            }
+        };
+        check_loaded(id, loaded, &possible, durable);
+    }
+
+    let mut created = record(Id::default(), 0);
+    store.create(&mut created).await.unwrap();
+    assert!(store.load(&created.id).await.unwrap().is_some());
+}
+
+fn check_loaded(id: Id, loaded: Option<Record>, possible: &[u64], durable: bool) {
+    match loaded {
+        Some(loaded) => {
+            let n = loaded.data["n"].as_u64();
+            assert!(
+                n.is_some_and(|n| possible.contains(&n)),
+                "record {id} holds data that was never saved: {:?}",
+                loaded.data
+            );
+        }
+        None => assert!(!durable, "record {id} was lost"),
+    }
+}
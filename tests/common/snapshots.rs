@@ -0,0 +1,59 @@
+use std::{env, fs, path::PathBuf};
+
+/// Compare SQL statements against the golden file `snapshots/<name>.sql`.
+///
+/// Statements are normalized by removing their common indentation and
+/// trailing whitespace, and terminated with a semicolon, so that the golden
+/// files read as plain SQL scripts. Run the tests with `UPDATE_SNAPSHOTS=1` to
+/// write the golden files instead, then review the changes to them.
+pub fn assert_sql_snapshot(name: &str, statements: &[String]) {
+    let actual = statements
+        .iter()
+        .map(|statement| format!("{};\n", normalize(statement)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("snapshots")
+        .join(format!("{name}.sql"));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "Failed to read snapshot {}: {err}. Run the tests with UPDATE_SNAPSHOTS=1 to create it.",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "Snapshot {} doesn't match; if the change is intended, run the tests with \
+         UPDATE_SNAPSHOTS=1 and review the diff.\n\nExpected:\n{expected}\nActual:\n{actual}",
+        path.display()
+    );
+}
+
+fn normalize(statement: &str) -> String {
+    let lines: Vec<&str> = statement
+        .trim_start_matches('\n')
+        .trim_end()
+        .lines()
+        .map(str::trim_end)
+        .collect();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end_matches(';')
+        .to_string()
+}
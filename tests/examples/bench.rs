@@ -0,0 +1,237 @@
+//! Load test a session store.
+//!
+//! Creates a pool of sessions and then issues a mix of loads and saves against
+//! them from concurrent tasks, printing latency percentiles and throughput.
+//!
+//! ```sh
+//! cargo run --release -p tests --example bench -- \
+//!     --store postgres --concurrency 64 --ops 100000 --read-ratio 0.9 --payload-bytes 512
+//! ```
+//!
+//! Backends are located as in the integration tests: via `REDIS_URL`,
+//! `MONGODB_URL`, `POSTGRES_URL`, and `MYSQL_URL`, or a throwaway container
+//! otherwise.
+
+use std::{
+    collections::HashMap,
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use time::OffsetDateTime;
+use tower_sessions::{
+    session::{Id, Record},
+    SessionStore,
+};
+use tower_sessions_moka_store::MokaStore;
+use tower_sessions_mongodb_store::{mongodb, MongoDBStore};
+use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+use tower_sessions_sqlx_store::{
+    sqlx::{sqlite::SqliteConnectOptions, MySqlPool, PgPool, SqlitePool},
+    MySqlStore, PostgresStore, SqliteStore,
+};
+
+#[derive(Debug)]
+struct Options {
+    store: String,
+    concurrency: usize,
+    ops: usize,
+    sessions: usize,
+    read_ratio: f64,
+    payload_bytes: usize,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self {
+            store: "moka".to_string(),
+            concurrency: 32,
+            ops: 10_000,
+            sessions: 1_000,
+            read_ratio: 0.9,
+            payload_bytes: 256,
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .unwrap_or_else(|| panic!("missing value for `{flag}`"));
+            match flag.as_str() {
+                "--store" => options.store = value,
+                "--concurrency" => options.concurrency = value.parse().unwrap(),
+                "--ops" => options.ops = value.parse().unwrap(),
+                "--sessions" => options.sessions = value.parse().unwrap(),
+                "--read-ratio" => options.read_ratio = value.parse().unwrap(),
+                "--payload-bytes" => options.payload_bytes = value.parse().unwrap(),
+                _ => panic!("unknown flag `{flag}`"),
+            }
+        }
+
+        options.concurrency = options.concurrency.max(1);
+        options.sessions = options.sessions.max(1);
+        options
+    }
+}
+
+/// A xorshift generator; statistical quality is irrelevant here.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn record(payload_bytes: usize) -> Record {
+    Record {
+        id: Id::default(),
+        data: HashMap::from([("payload".to_string(), "x".repeat(payload_bytes).into())]),
+        expiry_date: OffsetDateTime::now_utc() + time::Duration::hours(1),
+    }
+}
+
+async fn run<S>(store: S, options: &Options)
+where
+    S: SessionStore + Clone,
+{
+    let mut records = Vec::with_capacity(options.sessions);
+    for _ in 0..options.sessions {
+        let mut record = record(options.payload_bytes);
+        store.create(&mut record).await.unwrap();
+        records.push(record);
+    }
+    let records = Arc::new(records);
+
+    let started = Instant::now();
+    let ops_per_task = options.ops / options.concurrency;
+    let tasks: Vec<_> = (0..options.concurrency)
+        .map(|task| {
+            let store = store.clone();
+            let records = records.clone();
+            let read_ratio = options.read_ratio;
+            tokio::spawn(async move {
+                let mut rng = Rng(0x9e37_79b9_7f4a_7c15 ^ (task as u64 + 1));
+                let mut loads = Vec::with_capacity(ops_per_task);
+                let mut saves = Vec::with_capacity(ops_per_task);
+                for _ in 0..ops_per_task {
+                    let record = &records[rng.next() as usize % records.len()];
+                    let op_started = Instant::now();
+                    if rng.next_f64() < read_ratio {
+                        store.load(&record.id).await.unwrap();
+                        loads.push(op_started.elapsed());
+                    } else {
+                        store.save(record).await.unwrap();
+                        saves.push(op_started.elapsed());
+                    }
+                }
+                (loads, saves)
+            })
+        })
+        .collect();
+
+    let mut loads = Vec::new();
+    let mut saves = Vec::new();
+    for task in tasks {
+        let (task_loads, task_saves) = task.await.unwrap();
+        loads.extend(task_loads);
+        saves.extend(task_saves);
+    }
+    let elapsed = started.elapsed();
+
+    println!("{options:?}");
+    report("load", &mut loads);
+    report("save", &mut saves);
+    println!(
+        "{} ops in {elapsed:.2?} ({:.0} ops/s)",
+        loads.len() + saves.len(),
+        (loads.len() + saves.len()) as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn report(operation: &str, latencies: &mut [Duration]) {
+    if latencies.is_empty() {
+        return;
+    }
+
+    latencies.sort_unstable();
+    let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p) as usize];
+    println!(
+        "{operation}: n={} p50={:.2?} p99={:.2?} max={:.2?}",
+        latencies.len(),
+        percentile(0.50),
+        percentile(0.99),
+        latencies[latencies.len() - 1],
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let options = Options::from_args();
+
+    match options.store.as_str() {
+        "moka" => run(MokaStore::new(None), &options).await,
+        store @ ("sqlite" | "sqlite-queued") => {
+            // An in-memory database is private to each pooled connection, so use a
+            // file.
+            let path = env::temp_dir().join("tower-sessions-bench.db");
+            let _ = std::fs::remove_file(&path);
+            let pool = SqlitePool::connect_with(
+                SqliteConnectOptions::new()
+                    .filename(&path)
+                    .create_if_missing(true),
+            )
+            .await
+            .unwrap();
+            let mut sqlite_store = SqliteStore::new(pool);
+            if store == "sqlite-queued" {
+                sqlite_store = sqlite_store.with_write_queue(64);
+            }
+            sqlite_store.migrate().await.unwrap();
+            run(sqlite_store, &options).await
+        }
+        "postgres" => {
+            let pool = PgPool::connect(tests_support::postgres_url())
+                .await
+                .unwrap();
+            let store = PostgresStore::new(pool);
+            store.migrate().await.unwrap();
+            run(store, &options).await
+        }
+        "mysql" => {
+            let pool = MySqlPool::connect(tests_support::mysql_url())
+                .await
+                .unwrap();
+            let store = MySqlStore::new(pool);
+            store.migrate().await.unwrap();
+            run(store, &options).await
+        }
+        "redis" => {
+            let config = Config::from_url(tests_support::redis_url()).unwrap();
+            let pool = Pool::new(config, None, None, None, 6).unwrap();
+            pool.connect();
+            pool.wait_for_connect().await.unwrap();
+            run(RedisStore::new(pool), &options).await
+        }
+        "mongodb" => {
+            let client = mongodb::Client::with_uri_str(tests_support::mongodb_url())
+                .await
+                .unwrap();
+            let store = MongoDBStore::new(client, "tower-sessions".to_string());
+            store.migrate().await.unwrap();
+            run(store, &options).await
+        }
+        store => panic!(
+            "unknown store `{store}`; expected one of moka, sqlite, sqlite-queued, postgres, mysql, \
+             redis, mongodb"
+        ),
+    }
+}
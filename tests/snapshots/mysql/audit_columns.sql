@@ -0,0 +1,9 @@
+create schema if not exists tower_sessions;
+
+create table if not exists `tower_sessions`.`session`
+(
+    `id` char(22) not null,
+    `data` blob not null,
+    `expiry_date` timestamp(6) not null, `created_at` timestamp(6) not null default current_timestamp(6), `updated_at` timestamp(6) not null default current_timestamp(6),
+    primary key (`id`)
+);
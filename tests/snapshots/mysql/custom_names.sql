@@ -0,0 +1,9 @@
+create schema if not exists app;
+
+create table if not exists `app`.`web_sessions`
+(
+    `session_id` char(22) not null,
+    `payload` blob not null,
+    `expires_at` timestamp(6) not null,
+    primary key (`session_id`)
+);
@@ -0,0 +1,9 @@
+create schema if not exists tower_sessions;
+
+create table if not exists `tower_sessions`.`session`
+(
+    `id` char(22) not null,
+    `data` blob not null,
+    `expiry_date` timestamp(6) not null,
+    primary key (`id`)
+);
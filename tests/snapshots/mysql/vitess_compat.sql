@@ -0,0 +1,7 @@
+create table if not exists `session`
+(
+    `id` char(22) not null,
+    `data` blob not null,
+    `expiry_date` timestamp(6) not null,
+    primary key (`id`)
+);
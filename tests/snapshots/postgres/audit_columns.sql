@@ -0,0 +1,8 @@
+create schema if not exists "tower_sessions";
+
+create table if not exists "tower_sessions"."session"
+(
+    "id" text primary key not null,
+    "data" bytea not null,
+    "expiry_date" timestamptz not null, "created_at" timestamptz not null default now(), "updated_at" timestamptz not null default now()
+);
@@ -0,0 +1,8 @@
+create schema if not exists "app";
+
+create table if not exists "app"."web_sessions"
+(
+    "session_id" text primary key not null,
+    "payload" bytea not null,
+    "expires_at" timestamptz not null
+);
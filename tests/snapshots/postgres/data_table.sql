@@ -0,0 +1,14 @@
+create schema if not exists "tower_sessions";
+
+create table if not exists "tower_sessions"."session"
+(
+    "id" text primary key not null,
+    "expiry_date" timestamptz not null
+);
+
+create table if not exists "tower_sessions"."session_data"
+(
+    "id" text primary key not null
+      references "tower_sessions"."session" ("id") on delete cascade,
+    "data" bytea not null
+);
@@ -0,0 +1,6 @@
+create table if not exists "web_sessions"
+(
+    "session_id" text primary key not null,
+    "payload" blob not null,
+    "expires_at" integer not null
+);
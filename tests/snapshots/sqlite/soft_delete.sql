@@ -0,0 +1,6 @@
+create table if not exists "tower_sessions"
+(
+    "id" text primary key not null,
+    "data" blob not null,
+    "expiry_date" integer not null, "deleted_at" text
+);